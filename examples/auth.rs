@@ -0,0 +1,104 @@
+//! Protects a router with `tower_http`'s `RequireAuthorizationLayer`.
+//!
+//! The layer runs before the router, so unauthorized requests are rejected before any route is
+//! matched. Authorized requests carry the caller's claims in their extensions, where handlers can
+//! read them.
+//!
+//! ```text
+//! $ curl -i localhost:3030/me
+//! HTTP/1.1 401 Unauthorized
+//!
+//! $ curl -H 'authorization: Bearer alice-token' localhost:3030/me
+//! hello, alice
+//!
+//! $ curl -i -H 'authorization: Bearer alice-token' localhost:3030/admin
+//! HTTP/1.1 403 Forbidden
+//! ```
+
+use http::{header::AUTHORIZATION, Request, StatusCode};
+use hyper::{Body, Response, Server};
+use router_service::Router;
+use tower::{make::Shared, ServiceBuilder};
+use tower_http::auth::{AuthorizeRequest, RequireAuthorizationLayer};
+
+/// The identity of an authenticated caller, inserted into the request's extensions.
+#[derive(Clone, Debug)]
+struct Claims {
+    user: String,
+    admin: bool,
+}
+
+/// Authorizes requests carrying a known bearer token.
+///
+/// A real application would verify a signed token (e.g. a JWT) here instead of looking it up in a
+/// fixed list.
+#[derive(Clone)]
+struct BearerAuth;
+
+impl BearerAuth {
+    fn claims(token: &str) -> Option<Claims> {
+        match token {
+            "alice-token" => Some(Claims {
+                user: "alice".into(),
+                admin: false,
+            }),
+            "root-token" => Some(Claims {
+                user: "root".into(),
+                admin: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl<B> AuthorizeRequest<B> for BearerAuth {
+    type ResponseBody = Body;
+
+    fn authorize(&mut self, request: &mut Request<B>) -> Result<(), Response<Body>> {
+        let claims = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(Self::claims);
+
+        match claims {
+            Some(claims) => {
+                request.extensions_mut().insert(claims);
+                Ok(())
+            }
+            None => Err(Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(Body::empty())
+                .unwrap()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let router = Router::new()
+        .get("/me", |req: Request<Body>, _| async move {
+            // The layer only lets requests through after inserting their claims.
+            let claims = req.extensions().get::<Claims>().unwrap();
+            Response::builder().body(Body::from(format!("hello, {}", claims.user)))
+        })
+        .get("/admin", |req: Request<Body>, _| async move {
+            let claims = req.extensions().get::<Claims>().unwrap();
+            let status = if claims.admin {
+                StatusCode::OK
+            } else {
+                StatusCode::FORBIDDEN
+            };
+            Response::builder().status(status).body(Body::empty())
+        });
+
+    let service = ServiceBuilder::new()
+        .layer(RequireAuthorizationLayer::custom(BearerAuth))
+        .service(router);
+
+    let addr = ([127, 0, 0, 1], 3030).into();
+    Server::bind(&addr).serve(Shared::new(service)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use std::{
+    fmt::Write,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use http::{
+    header::{CONTENT_LENGTH, REFERER, USER_AGENT},
+    Request, Response, Version,
+};
+
+/// The format of the access log lines a router emits, set with
+/// [`Router::with_logging_format`](crate::Router::with_logging_format).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// One JSON object per request, e.g.
+    /// `{"method":"GET","path":"/users/7","status":200,"bytes":12,"duration_ms":0.42,...}`.
+    Json,
+    /// The NCSA combined log format used by Apache and nginx, e.g.
+    /// `- - - [10/Oct/2023:13:55:36 +0000] "GET /users/7 HTTP/1.1" 200 12 "-" "curl/8.0.1"`.
+    ///
+    /// The router doesn't know the client's address, so the host is always `-`.
+    Combined,
+    /// Space separated `key=value` pairs, e.g.
+    /// `method=GET path=/users/7 status=200 bytes=12 duration_ms=0.42 ...`.
+    Logfmt,
+}
+
+/// What's known about a request when it arrives, completed into a log line once it's answered.
+pub(crate) struct AccessLogEntry {
+    format: LogFormat,
+    method: String,
+    path: String,
+    version: Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    time: SystemTime,
+    start: Instant,
+}
+
+/// What's known about a request once it's answered.
+struct Outcome {
+    status: Option<u16>,
+    bytes: Option<u64>,
+    duration_ms: f64,
+}
+
+impl AccessLogEntry {
+    pub(crate) fn new<Body>(format: LogFormat, req: &Request<Body>) -> Self {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .map(|value: &http::HeaderValue| String::from_utf8_lossy(value.as_bytes()).into())
+        };
+
+        Self {
+            format,
+            method: req.method().to_string(),
+            path: req
+                .uri()
+                .path_and_query()
+                .map_or_else(|| req.uri().path().to_owned(), |pq| pq.to_string()),
+            version: req.version(),
+            referer: header(REFERER),
+            user_agent: header(USER_AGENT),
+            time: SystemTime::now(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Emits the log line for the request, answered with `resp` unless the handler failed.
+    pub(crate) fn finish<Body>(self, resp: Option<&Response<Body>>) {
+        let outcome = Outcome {
+            status: resp.map(|resp| resp.status().as_u16()),
+            bytes: resp
+                .and_then(|resp| resp.headers().get(CONTENT_LENGTH))
+                .and_then(|value| value.to_str().ok()?.parse().ok()),
+            duration_ms: self.start.elapsed().as_secs_f64() * 1000.0,
+        };
+
+        tracing::info!(target: "router_service::access", "{}", self.format(&outcome));
+    }
+
+    fn format(&self, outcome: &Outcome) -> String {
+        match self.format {
+            LogFormat::Json => self.json(outcome),
+            LogFormat::Combined => self.combined(outcome),
+            LogFormat::Logfmt => self.logfmt(outcome),
+        }
+    }
+
+    fn json(&self, outcome: &Outcome) -> String {
+        let string = |value: Option<&str>| value.map_or_else(|| "null".into(), json_string);
+        let number = |value: Option<u64>| value.map_or_else(|| "null".into(), |n| n.to_string());
+
+        format!(
+            concat!(
+                r#"{{"method":{},"path":{},"version":"{:?}","status":{},"bytes":{},"#,
+                r#""duration_ms":{:.3},"referer":{},"user_agent":{}}}"#,
+            ),
+            json_string(&self.method),
+            json_string(&self.path),
+            self.version,
+            number(outcome.status.map(u64::from)),
+            number(outcome.bytes),
+            outcome.duration_ms,
+            string(self.referer.as_deref()),
+            string(self.user_agent.as_deref()),
+        )
+    }
+
+    fn combined(&self, outcome: &Outcome) -> String {
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+        let quoted =
+            |value: Option<&str>| value.map_or_else(|| "-".into(), |v| v.replace('"', r#"\""#));
+
+        format!(
+            r#"- - - [{}] "{} {} {:?}" {} {} "{}" "{}""#,
+            clf_time(self.time),
+            self.method,
+            self.path,
+            self.version,
+            or_dash(outcome.status.map(|status| status.to_string())),
+            or_dash(outcome.bytes.map(|bytes| bytes.to_string())),
+            quoted(self.referer.as_deref()),
+            quoted(self.user_agent.as_deref()),
+        )
+    }
+
+    fn logfmt(&self, outcome: &Outcome) -> String {
+        let mut line = String::new();
+        let mut pair = |key: &str, value: &str| {
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            let _ = write!(line, "{key}={}", logfmt_value(value));
+        };
+
+        pair("method", &self.method);
+        pair("path", &self.path);
+        pair("version", &format!("{:?}", self.version));
+        if let Some(status) = outcome.status {
+            pair("status", &status.to_string());
+        }
+        if let Some(bytes) = outcome.bytes {
+            pair("bytes", &bytes.to_string());
+        }
+        pair("duration_ms", &format!("{:.3}", outcome.duration_ms));
+        if let Some(referer) = &self.referer {
+            pair("referer", referer);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            pair("user_agent", user_agent);
+        }
+        line
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str(r#"\""#),
+            '\\' => out.push_str(r"\\"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            '\t' => out.push_str(r"\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Quotes a logfmt value if it's empty or contains spaces, quotes, or `=`.
+fn logfmt_value(value: &str) -> String {
+    if !value.is_empty() && !value.contains([' ', '"', '=']) {
+        return value.to_owned();
+    }
+    format!(r#""{}""#, value.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+/// Formats `time` like `10/Oct/2023:13:55:36 +0000`, the way the common log format does.
+fn clf_time(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http::Request;
+
+    use super::{AccessLogEntry, LogFormat, Outcome};
+
+    fn entry(format: LogFormat) -> AccessLogEntry {
+        let req = Request::get("/users/7?page=2")
+            .header("user-agent", "curl/8.0.1")
+            .body(())
+            .unwrap();
+        let mut entry = AccessLogEntry::new(format, &req);
+        entry.time = UNIX_EPOCH + Duration::from_secs(1_696_946_136);
+        entry
+    }
+
+    const OUTCOME: Outcome = Outcome {
+        status: Some(200),
+        bytes: Some(12),
+        duration_ms: 0.5,
+    };
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            entry(LogFormat::Json).format(&OUTCOME),
+            concat!(
+                r#"{"method":"GET","path":"/users/7?page=2","version":"HTTP/1.1","status":200,"#,
+                r#""bytes":12,"duration_ms":0.500,"referer":null,"user_agent":"curl/8.0.1"}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn combined() {
+        assert_eq!(
+            entry(LogFormat::Combined).format(&OUTCOME),
+            concat!(
+                r#"- - - [10/Oct/2023:13:55:36 +0000] "GET /users/7?page=2 HTTP/1.1" 200 12 "-" "#,
+                r#""curl/8.0.1""#,
+            )
+        );
+    }
+
+    #[test]
+    fn logfmt() {
+        let outcome = Outcome {
+            status: None,
+            ..OUTCOME
+        };
+        let mut entry = entry(LogFormat::Logfmt);
+        entry.user_agent = Some("Mozilla/5.0 (X11)".into());
+        assert_eq!(
+            entry.format(&outcome),
+            concat!(
+                r#"method=GET path="/users/7?page=2" version=HTTP/1.1 bytes=12 duration_ms=0.500 "#,
+                r#"user_agent="Mozilla/5.0 (X11)""#,
+            )
+        );
+    }
+}
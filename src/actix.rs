@@ -0,0 +1,140 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Service as ActixServiceTrait, ServiceFactory, ServiceRequest, ServiceResponse},
+    error::ErrorInternalServerError,
+    HttpResponse,
+};
+use bytes::{Bytes, BytesMut};
+use futures_util::{
+    future::{poll_fn, ready, LocalBoxFuture, Ready},
+    StreamExt,
+};
+use http::{Request, Response};
+use tower::Service;
+
+/// Adapts a router to an actix-web [`ServiceFactory`], so it can be mounted in an actix-web `App`.
+///
+/// Created with [`Router::into_actix_service`](crate::Router::into_actix_service) or
+/// [`unsync::Router::into_actix_service`](crate::unsync::Router::into_actix_service). Request
+/// bodies are read into memory before the router is called, and errors returned by the router
+/// are reported to actix-web as `500 Internal Server Error`. Requires the `actix-web` feature.
+///
+/// # Example
+/// ```
+/// # actix_web::rt::System::new().block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use actix_web::{test, App};
+/// use bytes::Bytes;
+/// use http::Response;
+/// use router_service::Router;
+///
+/// let router: Router<Bytes, (), Infallible> = Router::new()
+///     .get("/legacy/users", |_, _| async move { Ok(Response::new(Bytes::from("users"))) });
+///
+/// let app = test::init_service(App::new().default_service(router.into_actix_service())).await;
+///
+/// let req = test::TestRequest::get().uri("/legacy/users").to_request();
+/// let body = test::call_and_read_body(&app, req).await;
+/// assert_eq!(body, "users");
+/// # });
+/// ```
+pub struct ActixService<S, Body> {
+    service: S,
+    _body: PhantomData<fn() -> Body>,
+}
+
+impl<S, Body> ActixService<S, Body> {
+    pub(crate) fn new(service: S) -> Self {
+        Self {
+            service,
+            _body: PhantomData,
+        }
+    }
+}
+
+impl<S: Clone, Body> Clone for ActixService<S, Body> {
+    fn clone(&self) -> Self {
+        Self::new(self.service.clone())
+    }
+}
+
+impl<S, Body> ServiceFactory<ServiceRequest> for ActixService<S, Body>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + 'static,
+    S::Error: fmt::Debug + fmt::Display,
+    Body: From<Bytes> + MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+
+    type Error = actix_web::Error;
+
+    type Config = ();
+
+    type Service = Self;
+
+    type InitError = ();
+
+    type Future = Ready<Result<Self, ()>>;
+
+    fn new_service(&self, _: ()) -> Self::Future {
+        ready(Ok(self.clone()))
+    }
+}
+
+impl<S, Body> ActixServiceTrait<ServiceRequest> for ActixService<S, Body>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + 'static,
+    S::Error: fmt::Debug + fmt::Display,
+    Body: From<Bytes> + MessageBody + 'static,
+{
+    type Response = ServiceResponse;
+
+    type Error = actix_web::Error;
+
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse, actix_web::Error>>;
+
+    fn poll_ready(&self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let mut service = self.service.clone();
+
+        Box::pin(async move {
+            let (http_req, mut payload) = req.into_parts();
+
+            let mut body = BytesMut::new();
+            while let Some(chunk) = payload.next().await {
+                body.extend_from_slice(&chunk?);
+            }
+
+            let mut req = Request::new(Body::from(body.freeze()));
+            *req.method_mut() = http_req.method().clone();
+            *req.uri_mut() = http_req.uri().clone();
+            *req.version_mut() = http_req.version();
+            for (name, value) in http_req.headers() {
+                req.headers_mut().append(name, value.clone());
+            }
+
+            poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(ErrorInternalServerError)?;
+            let resp = service.call(req).await.map_err(ErrorInternalServerError)?;
+
+            let (parts, body) = resp.into_parts();
+            let mut builder = HttpResponse::build(parts.status);
+            for (name, value) in &parts.headers {
+                builder.append_header((name, value));
+            }
+
+            Ok(ServiceResponse::new(http_req, builder.body(body)))
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use std::{collections::HashMap, sync::Arc};
+
+use http::{header::HOST, uri::Authority, Request};
+
+/// The routers serving the hosts set up with
+/// [`Router::with_authority_routing`](crate::Router::with_authority_routing), by lowercase host.
+pub(crate) struct Hosts<R>(Arc<HashMap<String, R>>);
+
+impl<R> Hosts<R> {
+    pub(crate) fn new(hosts: HashMap<String, R>) -> Self {
+        let hosts = hosts
+            .into_iter()
+            .map(|(host, router)| (host.to_ascii_lowercase(), router))
+            .collect();
+        Self(Arc::new(hosts))
+    }
+
+    /// Finds the router for the host `req` was sent to, taken from its `Host` header or, for
+    /// HTTP/2 requests, its URI.
+    ///
+    /// A router registered for the host and port is preferred over one for just the host.
+    pub(crate) fn find<Body>(&self, req: &Request<Body>) -> Option<&R> {
+        let authority = match req.headers().get(HOST) {
+            Some(host) => host.to_str().ok()?.parse::<Authority>().ok()?,
+            None => req.uri().authority()?.clone(),
+        };
+        let authority = authority.as_str().to_ascii_lowercase();
+
+        self.0.get(&authority).or_else(|| {
+            let host = authority.parse::<Authority>().ok()?;
+            self.0.get(host.host())
+        })
+    }
+}
+
+impl<R> Clone for Hosts<R> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use http::Request;
+
+    use super::Hosts;
+
+    #[test]
+    fn find() {
+        let hosts = Hosts::new(HashMap::from([
+            ("Example.com".to_string(), "example"),
+            ("example.com:8080".to_string(), "example on 8080"),
+            ("[::1]".to_string(), "localhost"),
+        ]));
+        let find = |host: Option<&str>, uri: &str| {
+            let mut req = Request::get(uri);
+            if let Some(host) = host {
+                req = req.header("host", host);
+            }
+            hosts.find(&req.body(()).unwrap()).copied()
+        };
+
+        assert_eq!(find(Some("example.com"), "/"), Some("example"));
+        assert_eq!(find(Some("EXAMPLE.COM:443"), "/"), Some("example"));
+        assert_eq!(find(Some("example.com:8080"), "/"), Some("example on 8080"));
+        assert_eq!(find(Some("[::1]:3000"), "/"), Some("localhost"));
+        assert_eq!(find(None, "https://example.com/"), Some("example"));
+        assert_eq!(find(Some("other.com"), "https://example.com/"), None);
+        assert_eq!(find(None, "/"), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use bytes::Bytes;
+use http::{header::CONTENT_LENGTH, Request};
+
+/// The limit set with [`Router::with_max_request_size`](crate::Router::with_max_request_size) on
+/// the size of a request's body.
+///
+/// Clones share whether the limit was exceeded.
+#[derive(Clone, Debug)]
+pub struct BodyLimit {
+    max: u64,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl BodyLimit {
+    pub(crate) fn new(max: u64) -> Self {
+        Self {
+            max,
+            exceeded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the number of bytes the body may have.
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Checks that `read`, the number of bytes read from the body so far, is within the limit.
+    ///
+    /// Once this fails, the router answers the request with `413 Payload Too Large`, whatever
+    /// its handler responds.
+    pub fn check(&self, read: u64) -> Result<(), BodyTooLarge> {
+        if read > self.max {
+            self.exceeded.store(true, Ordering::Relaxed);
+            return Err(BodyTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Whether a check of the body failed.
+    pub(crate) fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+/// The error a body limited with [`LimitBody`] fails with once it's read past its limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyTooLarge;
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("request body is over the size limit")
+    }
+}
+
+impl Error for BodyTooLarge {}
+
+/// Request bodies whose size can be limited with
+/// [`Router::with_max_request_size`](crate::Router::with_max_request_size).
+///
+/// Bodies that are already in memory are checked as a whole, and streamed bodies count their
+/// bytes as they're read, failing with [`BodyTooLarge`] past the limit.
+pub trait LimitBody: Sized {
+    /// Returns the body limited to `limit`, or an error if it's already known to be over it.
+    fn limit(self, limit: BodyLimit) -> Result<Self, BodyTooLarge>;
+}
+
+impl LimitBody for () {
+    fn limit(self, _: BodyLimit) -> Result<Self, BodyTooLarge> {
+        Ok(())
+    }
+}
+
+impl LimitBody for String {
+    fn limit(self, limit: BodyLimit) -> Result<Self, BodyTooLarge> {
+        limit.check(self.len() as u64)?;
+        Ok(self)
+    }
+}
+
+impl LimitBody for Vec<u8> {
+    fn limit(self, limit: BodyLimit) -> Result<Self, BodyTooLarge> {
+        limit.check(self.len() as u64)?;
+        Ok(self)
+    }
+}
+
+impl LimitBody for Bytes {
+    fn limit(self, limit: BodyLimit) -> Result<Self, BodyTooLarge> {
+        limit.check(self.len() as u64)?;
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl LimitBody for hyper::Body {
+    fn limit(self, limit: BodyLimit) -> Result<Self, BodyTooLarge> {
+        use futures_util::StreamExt;
+
+        let mut read = 0;
+        let chunks = self.map(move |chunk| {
+            let chunk = chunk?;
+            read += chunk.len() as u64;
+            limit.check(read)?;
+            Ok::<_, Box<dyn Error + Send + Sync>>(chunk)
+        });
+        Ok(hyper::Body::wrap_stream(chunks))
+    }
+}
+
+/// Limits the body of a request with [`LimitBody::limit`], keeping the router generic over body
+/// types that can't be limited.
+pub(crate) type LimitFn<Body> = fn(Body, BodyLimit) -> Result<Body, BodyTooLarge>;
+
+/// Whether `req` declares a body longer than `max` bytes in its `Content-Length`, so it can be
+/// rejected before its body is read.
+pub(crate) fn exceeds_max_size<Body>(req: &Request<Body>, max: u64) -> bool {
+    req.headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+        .is_some_and(|len| len > max)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::{exceeds_max_size, BodyLimit, BodyTooLarge, LimitBody};
+
+    #[test]
+    fn exceeds() {
+        let req = |len: Option<&str>| {
+            let mut req = Request::post("/");
+            if let Some(len) = len {
+                req = req.header("content-length", len);
+            }
+            req.body(()).unwrap()
+        };
+
+        assert!(exceeds_max_size(&req(Some("1025")), 1024));
+        assert!(!exceeds_max_size(&req(Some("1024")), 1024));
+        assert!(!exceeds_max_size(&req(Some("0")), 0));
+        assert!(!exceeds_max_size(&req(Some("lots")), 1024));
+        assert!(!exceeds_max_size(&req(None), 1024));
+    }
+
+    #[test]
+    fn limit() {
+        let limit = BodyLimit::new(4);
+        assert_eq!("four".to_string().limit(limit.clone()).unwrap(), "four");
+        assert!(!limit.exceeded());
+
+        assert_eq!("five!".to_string().limit(limit.clone()), Err(BodyTooLarge));
+        assert!(limit.exceeded());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use http::{
+    header::{CACHE_CONTROL, SET_COOKIE, VARY},
+    HeaderMap, HeaderName, HeaderValue, Response, StatusCode, Version,
+};
+use lru::LruCache;
+
+/// The number of distinct URIs a cached route remembers responses for.
+const CAPACITY: usize = 1024;
+
+/// What a cached route remembers about a URI.
+struct Entry<Body> {
+    /// The request headers named by the `Vary` header of the last response for the URI, kept
+    /// even when the response can't be cached so the next request's values can be captured.
+    vary: Vec<HeaderName>,
+    response: Option<CachedResponse<Body>>,
+}
+
+struct CachedResponse<Body> {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Body,
+    /// The values the headers in [`Entry::vary`] had in the request the response was made for.
+    vary: Vec<Option<HeaderValue>>,
+    expires_at: Instant,
+}
+
+/// The values of the headers a response for a URI varies on, captured from a request that
+/// missed the cache.
+#[derive(Default)]
+pub(crate) struct VaryHeaders(Vec<(HeaderName, Option<HeaderValue>)>);
+
+/// A bounded, time-limited cache of successful responses keyed by request URI.
+pub(crate) struct ResponseCache<Body> {
+    ttl: Duration,
+    entries: Arc<Mutex<LruCache<String, Entry<Body>>>>,
+}
+
+impl<Body> ResponseCache<Body>
+where
+    Body: Clone,
+{
+    pub(crate) fn new(ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(CAPACITY).unwrap();
+
+        Self {
+            ttl,
+            entries: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// Returns a copy of the cached response for `key` if it hasn't expired yet and was made for
+    /// a request with the same values as `headers` for the headers the response varies on.
+    ///
+    /// Otherwise, returns the values in `headers` of the headers the last response for `key`
+    /// varied on, to pass to [`ResponseCache::insert`] along with the new response.
+    pub(crate) fn get(
+        &self,
+        key: &str,
+        headers: &HeaderMap,
+    ) -> Result<Response<Body>, VaryHeaders> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = match entries.get(key) {
+            Some(entry) => entry,
+            None => return Err(VaryHeaders::default()),
+        };
+
+        let fresh = entry.response.as_ref().filter(|cached| {
+            cached.expires_at > Instant::now()
+                && entry
+                    .vary
+                    .iter()
+                    .zip(&cached.vary)
+                    .all(|(name, value)| headers.get(name) == value.as_ref())
+        });
+        let cached = match fresh {
+            Some(cached) => cached,
+            None => {
+                let vary = entry.vary.iter().map(|name| {
+                    let value = headers.get(name).cloned();
+                    (name.clone(), value)
+                });
+                return Err(VaryHeaders(vary.collect()));
+            }
+        };
+
+        let mut resp = Response::new(cached.body.clone());
+        *resp.status_mut() = cached.status;
+        *resp.version_mut() = cached.version;
+        *resp.headers_mut() = cached.headers.clone();
+
+        Ok(resp)
+    }
+
+    /// Stores a copy of `resp` under `key` if it was successful and can be shared between
+    /// clients, `vary` being the headers [`ResponseCache::get`] captured from its request.
+    ///
+    /// Responses setting cookies or marked `private` or `no-store` by their `Cache-Control`
+    /// header are meant for a single client, and responses with `Vary: *` can't be matched to
+    /// other requests, so they aren't stored. Responses varying on headers that weren't captured
+    /// aren't stored either, but the headers are remembered so the next request captures them.
+    pub(crate) fn insert(&self, key: String, vary: VaryHeaders, resp: &Response<Body>) {
+        if !resp.status().is_success() || resp.headers().contains_key(SET_COOKIE) {
+            return;
+        }
+        if has_token(resp.headers(), &CACHE_CONTROL, &["private", "no-store"]) {
+            return;
+        }
+        if has_token(resp.headers(), &VARY, &["*"]) {
+            return;
+        }
+
+        let names: Vec<HeaderName> = resp
+            .headers()
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|name| HeaderName::try_from(name.trim()).ok())
+            .collect();
+        let values: Option<Vec<_>> = names
+            .iter()
+            .map(|name| {
+                let (_, value) = vary.0.iter().find(|(captured, _)| captured == name)?;
+                Some(value.clone())
+            })
+            .collect();
+
+        let response = values.map(|values| CachedResponse {
+            status: resp.status(),
+            version: resp.version(),
+            headers: resp.headers().clone(),
+            body: resp.body().clone(),
+            vary: values,
+            expires_at: Instant::now() + self.ttl,
+        });
+        let entry = Entry {
+            vary: names,
+            response,
+        };
+
+        self.entries.lock().unwrap().put(key, entry);
+    }
+}
+
+/// Whether one of the comma-separated directives of the `name` headers in `headers` is one of
+/// `tokens`, ignoring case and directive arguments.
+fn has_token(headers: &HeaderMap, name: &HeaderName, tokens: &[&str]) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.split('=').next().unwrap_or_default().trim())
+        .any(|directive| {
+            tokens
+                .iter()
+                .any(|token| directive.eq_ignore_ascii_case(token))
+        })
+}
+
+impl<Body> Clone for ResponseCache<Body> {
+    fn clone(&self) -> Self {
+        Self {
+            ttl: self.ttl,
+            entries: self.entries.clone(),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::task::{Context, Poll};
+
+use futures_util::future::{poll_fn, BoxFuture};
+use http::{Request, Response, StatusCode};
+use tower::Service;
+
+/// A service that tries several services in order, returning the first response that isn't
+/// `404 Not Found`.
+///
+/// This composes routers without baking fallback logic into every handler, e.g. serving a `v2`
+/// router and falling back to the `v1` router for routes `v2` doesn't implement. If every service
+/// responds with `404`, the last service's response is returned.
+///
+/// Since the request has to be replayed, its body must be [`Clone`]. Every service but the last
+/// receives a copy of the request's method, URI, version, headers, and body, but not its
+/// extensions, which are only passed to the last service.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{Request, Response};
+/// use tower::Service;
+/// use router_service::{FallbackChain, Router};
+///
+/// let v1: Router<String, (), Infallible> = Router::new()
+///     .get("/users", |_, _| async move { Ok(Response::new("v1 users".into())) })
+///     .get("/posts", |_, _| async move { Ok(Response::new("v1 posts".into())) });
+/// let v2 = Router::new()
+///     .get("/users", |_, _| async move { Ok(Response::new("v2 users".into())) });
+///
+/// let mut chain = FallbackChain::new(vec![v2, v1]);
+///
+/// let resp = chain.call(Request::get("/users").body(String::new()).unwrap()).await.unwrap();
+/// assert_eq!(resp.body(), "v2 users");
+///
+/// let resp = chain.call(Request::get("/posts").body(String::new()).unwrap()).await.unwrap();
+/// assert_eq!(resp.body(), "v1 posts");
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct FallbackChain<S> {
+    services: Vec<S>,
+}
+
+impl<S> FallbackChain<S> {
+    /// Creates a chain that tries `services` in order.
+    pub fn new(services: Vec<S>) -> Self {
+        Self { services }
+    }
+}
+
+impl<S, Body, ResBody> Service<Request<Body>> for FallbackChain<S>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    S: Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: 'static,
+    Body: Clone + Send + 'static,
+    ResBody: Default + Send + 'static,
+{
+    type Response = Response<ResBody>;
+
+    type Error = S::Error;
+
+    type Future = BoxFuture<'static, Result<Response<ResBody>, S::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        Box::pin(call_chain(self.services.clone(), req))
+    }
+}
+
+/// Calls `services` in order until one responds with something other than `404 Not Found`.
+pub(crate) async fn call_chain<S, Body, ResBody>(
+    services: Vec<S>,
+    req: Request<Body>,
+) -> Result<Response<ResBody>, S::Error>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    Body: Clone,
+    ResBody: Default,
+{
+    let mut req = Some(req);
+    let mut services = services.into_iter().peekable();
+
+    while let Some(mut service) = services.next() {
+        let is_last = services.peek().is_none();
+        let attempt = if is_last {
+            req.take().unwrap()
+        } else {
+            clone_request(req.as_ref().unwrap())
+        };
+
+        poll_fn(|cx| service.poll_ready(cx)).await?;
+        let resp = service.call(attempt).await?;
+        if is_last || resp.status() != StatusCode::NOT_FOUND {
+            return Ok(resp);
+        }
+    }
+
+    let mut resp = Response::new(ResBody::default());
+    *resp.status_mut() = StatusCode::NOT_FOUND;
+    Ok(resp)
+}
+
+/// Copies everything but the extensions of `req`, which can't be cloned.
+fn clone_request<Body: Clone>(req: &Request<Body>) -> Request<Body> {
+    let mut clone = Request::new(req.body().clone());
+    *clone.method_mut() = req.method().clone();
+    *clone.uri_mut() = req.uri().clone();
+    *clone.version_mut() = req.version();
+    *clone.headers_mut() = req.headers().clone();
+    clone
+}
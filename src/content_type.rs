@@ -0,0 +1,75 @@
+use std::{collections::HashMap, sync::Arc};
+
+use http::{header::CONTENT_TYPE, Method, Request};
+use mime::Mime;
+
+/// The media types requests with certain methods must declare in their `Content-Type`, set up
+/// with [`Router::require_content_type`](crate::Router::require_content_type).
+#[derive(Clone, Default)]
+pub(crate) struct RequiredContentTypes(Arc<HashMap<Method, Vec<Mime>>>);
+
+impl RequiredContentTypes {
+    /// Requires requests with `method` to have the media type `mime`, or any other media type
+    /// required for the method.
+    pub(crate) fn require(&mut self, method: Method, mime: Mime) {
+        Arc::make_mut(&mut self.0)
+            .entry(method)
+            .or_default()
+            .push(mime);
+    }
+
+    /// Whether `req` has one of the media types required for its method, if any are.
+    ///
+    /// Parameters, like `charset`, are ignored.
+    pub(crate) fn allows<Body>(&self, req: &Request<Body>) -> bool {
+        let required = match self.0.get(req.method()) {
+            Some(required) => required,
+            None => return true,
+        };
+
+        let actual = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok()?.parse::<Mime>().ok());
+
+        match actual {
+            Some(actual) => required
+                .iter()
+                .any(|mime| mime.essence_str() == actual.essence_str()),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{Method, Request};
+
+    use super::RequiredContentTypes;
+
+    #[test]
+    fn allows() {
+        let mut types = RequiredContentTypes::default();
+        types.require(Method::POST, mime::APPLICATION_JSON);
+        types.require(Method::POST, mime::APPLICATION_WWW_FORM_URLENCODED);
+
+        let req = |method: Method, content_type: Option<&str>| {
+            let mut req = Request::builder().method(method);
+            if let Some(content_type) = content_type {
+                req = req.header("content-type", content_type);
+            }
+            req.body(()).unwrap()
+        };
+
+        assert!(types.allows(&req(Method::POST, Some("application/json"))));
+        assert!(types.allows(&req(Method::POST, Some("Application/JSON; charset=utf-8"))));
+        assert!(types.allows(&req(
+            Method::POST,
+            Some("application/x-www-form-urlencoded")
+        )));
+        assert!(!types.allows(&req(Method::POST, Some("text/plain"))));
+        assert!(!types.allows(&req(Method::POST, Some("not a media type"))));
+        assert!(!types.allows(&req(Method::POST, None)));
+        assert!(types.allows(&req(Method::PUT, None)));
+    }
+}
@@ -0,0 +1,140 @@
+use http::{
+    header::{
+        ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD,
+        ORIGIN, VARY,
+    },
+    HeaderValue, Method, Request, Response, StatusCode,
+};
+
+/// How long browsers may cache the result of a preflight request, in seconds.
+const PREFLIGHT_MAX_AGE: u32 = 600;
+
+/// The cross-origin requests a router accepts.
+pub(crate) enum CorsConfig {
+    Any,
+    Origins(Vec<HeaderValue>),
+}
+
+impl CorsConfig {
+    pub(crate) fn origins(origins: &[&str]) -> Self {
+        Self::Origins(
+            origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin).expect("invalid origin"))
+                .collect(),
+        )
+    }
+
+    /// Returns the `Access-Control-Allow-Origin` to send for `req`, if its origin is allowed.
+    pub(crate) fn allowed_origin<Body>(&self, req: &Request<Body>) -> Option<AllowedOrigin> {
+        let origin = req.headers().get(ORIGIN)?;
+
+        match self {
+            Self::Any => Some(AllowedOrigin {
+                value: HeaderValue::from_static("*"),
+                vary: false,
+            }),
+            Self::Origins(origins) => {
+                origins
+                    .iter()
+                    .any(|allowed| allowed == origin)
+                    .then(|| AllowedOrigin {
+                        value: origin.clone(),
+                        vary: true,
+                    })
+            }
+        }
+    }
+
+    /// Answers `req` if it's a preflight request from an allowed origin.
+    ///
+    /// The returned response doesn't include `Access-Control-Allow-Origin`, which is added to
+    /// every response by [`AllowedOrigin::apply`].
+    pub(crate) fn preflight<Body>(&self, req: &Request<Body>) -> Option<Response<()>> {
+        if req.method() != Method::OPTIONS {
+            return None;
+        }
+
+        let method = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD)?;
+        self.allowed_origin(req)?;
+
+        let mut resp = Response::new(());
+        *resp.status_mut() = StatusCode::NO_CONTENT;
+
+        let headers = resp.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_METHODS, method.clone());
+        if let Some(requested) = req.headers().get(ACCESS_CONTROL_REQUEST_HEADERS) {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+        }
+        headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(PREFLIGHT_MAX_AGE));
+
+        Some(resp)
+    }
+}
+
+/// The `Access-Control-Allow-Origin` value for a request from an allowed origin.
+pub(crate) struct AllowedOrigin {
+    value: HeaderValue,
+    vary: bool,
+}
+
+impl AllowedOrigin {
+    pub(crate) fn apply<Body>(self, resp: &mut Response<Body>) {
+        let headers = resp.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, self.value);
+        if self.vary {
+            headers.append(VARY, HeaderValue::from_static("origin"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{
+        header::{ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN},
+        Request,
+    };
+
+    use super::CorsConfig;
+
+    #[test]
+    fn allowed_origins() {
+        let cors = CorsConfig::origins(&["https://example.com"]);
+
+        let req = Request::get("/")
+            .header(ORIGIN, "https://example.com")
+            .body(())
+            .unwrap();
+        assert!(cors.allowed_origin(&req).is_some());
+
+        let req = Request::get("/")
+            .header(ORIGIN, "https://evil.example")
+            .body(())
+            .unwrap();
+        assert!(cors.allowed_origin(&req).is_none());
+
+        let req = Request::get("/").body(()).unwrap();
+        assert!(CorsConfig::Any.allowed_origin(&req).is_none());
+    }
+
+    #[test]
+    fn preflight() {
+        let cors = CorsConfig::Any;
+
+        let req = Request::options("/")
+            .header(ORIGIN, "https://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .body(())
+            .unwrap();
+        let resp = cors.preflight(&req).unwrap();
+        assert_eq!(resp.status(), 204);
+        assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_METHODS], "PUT");
+
+        let req = Request::options("/")
+            .header(ORIGIN, "https://example.com")
+            .body(())
+            .unwrap();
+        assert!(cors.preflight(&req).is_none());
+    }
+}
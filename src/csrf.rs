@@ -0,0 +1,156 @@
+use bytes::Bytes;
+use http::{
+    header::{CACHE_CONTROL, CONTENT_TYPE, SET_COOKIE},
+    HeaderMap, Method, Response, StatusCode,
+};
+
+use crate::signed_cookie::{cookies, random_value, Signer};
+
+/// Converts the body of a token response to the body type of a router's responses.
+pub(crate) type IntoBody<Body> = fn(Bytes) -> Body;
+
+/// The path of the endpoint handing out tokens.
+pub(crate) const TOKEN_PATH: &str = "/csrf-token";
+
+/// The header requests changing state send their token in.
+const CSRF_HEADER: &str = "x-csrf-token";
+
+/// The name of the cookie holding the token handed out by the token endpoint.
+const CSRF_COOKIE: &str = "csrf_token";
+
+/// The CSRF protection set up with
+/// [`Router::csrf_protection`](crate::Router::csrf_protection).
+///
+/// Tokens are random values signed with the router's secret. The token endpoint hands a token to
+/// the client both in its body and in a cookie, and requests changing state have to send the
+/// cookie's token back in a header. Other sites can make browsers send the cookie, but can't read
+/// it to set the header.
+#[derive(Clone)]
+pub(crate) struct Csrf {
+    signer: Signer,
+}
+
+impl Csrf {
+    pub(crate) fn new(secret: &[u8]) -> Self {
+        Self {
+            signer: Signer::new(secret),
+        }
+    }
+
+    /// Checks the token of a request, returning the `403 Forbidden` response rejecting it if its
+    /// method changes state and it's missing a token or its token doesn't match its cookie.
+    pub(crate) fn check(&self, method: &Method, headers: &HeaderMap) -> Result<(), Response<()>> {
+        if !matches!(
+            *method,
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        ) {
+            return Ok(());
+        }
+
+        let token = headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok());
+        match (token, self.cookie_token(headers)) {
+            (Some(token), Some(cookie)) if constant_time_eq(token, cookie) => Ok(()),
+            _ => {
+                let mut resp = Response::new(());
+                *resp.status_mut() = StatusCode::FORBIDDEN;
+                Err(resp)
+            }
+        }
+    }
+
+    /// Answers a request to the token endpoint with the token of its cookie, or a new token if
+    /// it doesn't have a valid one, as `{"token": "..."}`.
+    pub(crate) fn token_response(&self, headers: &HeaderMap) -> Response<Bytes> {
+        let token = match self.cookie_token(headers) {
+            Some(token) => token.to_owned(),
+            None => self.signer.sign(&random_value()),
+        };
+
+        Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .header(CACHE_CONTROL, "no-store")
+            .header(
+                SET_COOKIE,
+                format!("{CSRF_COOKIE}={token}; Path=/; HttpOnly; SameSite=Strict"),
+            )
+            .body(Bytes::from(format!(r#"{{"token":"{token}"}}"#)))
+            .unwrap()
+    }
+
+    /// The token in the cookie of a request, if it's signed with the router's secret.
+    fn cookie_token<'h>(&self, headers: &'h HeaderMap) -> Option<&'h str> {
+        cookies(headers, CSRF_COOKIE).find(|token| self.signer.verify(token).is_some())
+    }
+}
+
+/// Compares `a` and `b` in a time that only depends on their lengths, so that a client can't
+/// find the token it's compared with by timing guesses.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue, Method, StatusCode};
+
+    use super::Csrf;
+
+    #[test]
+    fn check() {
+        let csrf = Csrf::new(&[7; 32]);
+        let resp = csrf.token_response(&HeaderMap::new());
+        let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap();
+        let token = cookie.strip_prefix("csrf_token=").unwrap();
+        assert_eq!(resp.body(), &format!(r#"{{"token":"{token}"}}"#));
+
+        let headers = |token: Option<&str>, cookie: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(token) = token {
+                headers.insert("x-csrf-token", HeaderValue::try_from(token).unwrap());
+            }
+            if let Some(cookie) = cookie {
+                headers.insert("cookie", HeaderValue::try_from(cookie).unwrap());
+            }
+            headers
+        };
+
+        assert!(csrf
+            .check(&Method::POST, &headers(Some(token), Some(cookie)))
+            .is_ok());
+        assert!(csrf.check(&Method::GET, &headers(None, None)).is_ok());
+        for method in [Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            let resp = csrf
+                .check(&method, &headers(None, Some(cookie)))
+                .unwrap_err();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(csrf
+            .check(&Method::POST, &headers(Some(token), None))
+            .is_err());
+
+        // Tokens have to be signed with the secret, even if the header matches the cookie.
+        let forged = format!("{}.00", token.split('.').next().unwrap());
+        let forged_cookie = format!("csrf_token={forged}");
+        assert!(csrf
+            .check(&Method::POST, &headers(Some(&forged), Some(&forged_cookie)))
+            .is_err());
+        let other = Csrf::new(&[8; 32]).token_response(&HeaderMap::new());
+        let other = other.headers()["set-cookie"].to_str().unwrap();
+        let other = other.split(';').next().unwrap();
+        let other_token = other.strip_prefix("csrf_token=").unwrap();
+        assert!(csrf
+            .check(&Method::POST, &headers(Some(other_token), Some(other)))
+            .is_err());
+
+        // Clients with a valid token keep it.
+        let resp = csrf.token_response(&headers(None, Some(cookie)));
+        assert_eq!(resp.body(), &format!(r#"{{"token":"{token}"}}"#));
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use http::{HeaderMap, HeaderValue};
+
+const TRACE_ID: &str = "x-datadog-trace-id";
+const PARENT_ID: &str = "x-datadog-parent-id";
+const SAMPLING_PRIORITY: &str = "x-datadog-sampling-priority";
+const ORIGIN: &str = "x-datadog-origin";
+
+/// The Datadog trace a request is part of, added to the extensions of requests handled by
+/// routers with [`Router::with_datadog_apm`](crate::Router::with_datadog_apm).
+///
+/// The trace is continued from the request's `X-Datadog-*` headers, or started if they're
+/// missing, and every request gets a span of its own within it. Handlers that call other
+/// services should [`inject`](DatadogTrace::inject) the trace into their requests so the
+/// downstream spans show up as children of this one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatadogTrace {
+    trace_id: u64,
+    span_id: u64,
+    parent_id: Option<u64>,
+    sampling_priority: Option<i32>,
+    origin: Option<HeaderValue>,
+    service: Arc<str>,
+    resource: String,
+}
+
+impl DatadogTrace {
+    /// Continues the trace propagated in `headers`, or starts a new one, with a new span for
+    /// the given resource.
+    pub(crate) fn extract(headers: &HeaderMap, service: Arc<str>, resource: String) -> Self {
+        let id = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok()?.parse().ok())
+                .filter(|&id: &u64| id != 0)
+        };
+        let trace_id = id(TRACE_ID);
+
+        Self {
+            trace_id: trace_id.unwrap_or_else(new_id),
+            span_id: new_id(),
+            // A parent without a trace can't be linked to anything.
+            parent_id: trace_id.and(id(PARENT_ID)),
+            sampling_priority: headers
+                .get(SAMPLING_PRIORITY)
+                .and_then(|value| value.to_str().ok()?.parse().ok()),
+            origin: headers.get(ORIGIN).cloned(),
+            service,
+            resource,
+        }
+    }
+
+    /// The ID of the trace, shared by every span in it.
+    pub fn trace_id(&self) -> u64 {
+        self.trace_id
+    }
+
+    /// The ID of the request's span.
+    pub fn span_id(&self) -> u64 {
+        self.span_id
+    }
+
+    /// The ID of the span in the calling service that the request's span is a child of, if the
+    /// trace was continued from the request's headers.
+    pub fn parent_id(&self) -> Option<u64> {
+        self.parent_id
+    }
+
+    /// The name of the service the span belongs to.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// The resource the span is for, the request's method and the matched route's template,
+    /// e.g. `GET /users/:id`.
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// Adds the headers that continue the trace to the headers of an outbound request, making
+    /// the request's span the parent of the called service's.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{DatadogTrace, Router};
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |req: Request<()>, _| async move {
+    ///         let trace = req.extensions().get::<DatadogTrace>().unwrap();
+    ///         let mut outbound = Request::get("http://billing/invoices").body(()).unwrap();
+    ///         trace.inject(outbound.headers_mut());
+    ///         // ... send `outbound` with an HTTP client.
+    ///         Ok::<_, Infallible>(Response::new(()))
+    ///     })
+    ///     .with_datadog_apm("web");
+    ///
+    /// let resp = router.call(Request::get("/").body(()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.status(), 200);
+    /// # });
+    /// ```
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        headers.insert(TRACE_ID, HeaderValue::from(self.trace_id));
+        headers.insert(PARENT_ID, HeaderValue::from(self.span_id));
+        if let Some(priority) = self.sampling_priority {
+            headers.insert(SAMPLING_PRIORITY, HeaderValue::from(priority));
+        }
+        if let Some(origin) = &self.origin {
+            headers.insert(ORIGIN, origin.clone());
+        }
+    }
+
+    /// A `tracing` span for the request, with the tags Datadog uses to identify spans.
+    pub(crate) fn span(&self) -> tracing::Span {
+        tracing::info_span!(
+            "http.request",
+            service.name = %self.service,
+            resource.name = %self.resource,
+            span.kind = "server",
+            dd.trace_id = self.trace_id,
+            dd.span_id = self.span_id,
+            dd.parent_id = self.parent_id,
+        )
+    }
+}
+
+/// Generates a random, non-zero 63-bit ID, which every Datadog tracer accepts.
+fn new_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    (hasher.finish() >> 1).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue};
+
+    use super::DatadogTrace;
+
+    #[test]
+    fn continues_trace() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-datadog-trace-id", HeaderValue::from_static("123"));
+        headers.insert("x-datadog-parent-id", HeaderValue::from_static("456"));
+        headers.insert("x-datadog-sampling-priority", HeaderValue::from_static("2"));
+        headers.insert("x-datadog-origin", HeaderValue::from_static("synthetics"));
+
+        let trace = DatadogTrace::extract(&headers, "web".into(), "GET /".into());
+        assert_eq!(trace.trace_id(), 123);
+        assert_eq!(trace.parent_id(), Some(456));
+        assert_ne!(trace.span_id(), 456);
+
+        let mut outbound = HeaderMap::new();
+        trace.inject(&mut outbound);
+        assert_eq!(outbound["x-datadog-trace-id"], "123");
+        assert_eq!(outbound["x-datadog-parent-id"], trace.span_id().to_string());
+        assert_eq!(outbound["x-datadog-sampling-priority"], "2");
+        assert_eq!(outbound["x-datadog-origin"], "synthetics");
+    }
+
+    #[test]
+    fn starts_trace() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-datadog-parent-id", HeaderValue::from_static("456"));
+
+        let first = DatadogTrace::extract(&headers, "web".into(), "GET /".into());
+        let second = DatadogTrace::extract(&headers, "web".into(), "GET /".into());
+        assert_ne!(first.trace_id(), 0);
+        assert_ne!(first.trace_id(), second.trace_id());
+        assert_eq!(first.parent_id(), None);
+
+        let mut outbound = HeaderMap::new();
+        first.inject(&mut outbound);
+        assert!(!outbound.contains_key("x-datadog-sampling-priority"));
+    }
+}
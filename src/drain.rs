@@ -0,0 +1,174 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "tokio")]
+use futures_util::future::{select, Either};
+use futures_util::task::AtomicWaker;
+use http::Response;
+#[cfg(feature = "tokio")]
+use http::StatusCode;
+
+#[derive(Default)]
+pub(crate) struct DrainState {
+    in_flight: AtomicUsize,
+    draining: AtomicBool,
+    waker: AtomicWaker,
+    #[cfg(feature = "tokio")]
+    expired: AtomicBool,
+    #[cfg(feature = "tokio")]
+    expire: tokio::sync::Notify,
+}
+
+impl DrainState {
+    /// Tracks a new in-flight request, or returns `None` if the router is draining.
+    pub(crate) fn enter(self: &Arc<Self>) -> Option<DrainGuard> {
+        if self.draining.load(Ordering::SeqCst) {
+            return None;
+        }
+
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Some(DrainGuard(self.clone()))
+    }
+
+    /// Aborts every in-flight request, now and in the future.
+    #[cfg(feature = "tokio")]
+    fn expire(&self) {
+        self.expired.store(true, Ordering::SeqCst);
+        self.expire.notify_waiters();
+    }
+
+    /// Resolves once [`DrainState::expire`] has been called.
+    #[cfg(feature = "tokio")]
+    async fn expired(&self) {
+        loop {
+            let notified = self.expire.notified();
+            futures_util::pin_mut!(notified);
+            notified.as_mut().enable();
+
+            if self.expired.load(Ordering::SeqCst) {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+/// Marks a request as in flight until it's dropped.
+pub(crate) struct DrainGuard(Arc<DrainState>);
+
+impl DrainGuard {
+    /// Drives `fut` to completion while the request is marked as in flight.
+    ///
+    /// If the graceful shutdown deadline passes first, `fut` is dropped and the request is
+    /// answered with `503 Service Unavailable` instead.
+    pub(crate) async fn run<Body, Error, Fut>(self, fut: Fut) -> Result<Response<Body>, Error>
+    where
+        Body: Default,
+        Fut: Future<Output = Result<Response<Body>, Error>>,
+    {
+        #[cfg(feature = "tokio")]
+        {
+            let expired = self.0.expired();
+            futures_util::pin_mut!(fut, expired);
+
+            match select(fut, expired).await {
+                Either::Left((resp, _)) => resp,
+                Either::Right(_) => Ok(Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .body(Body::default())
+                    .unwrap()),
+            }
+        }
+
+        #[cfg(not(feature = "tokio"))]
+        fut.await
+    }
+}
+
+impl Drop for DrainGuard {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.waker.wake();
+        }
+    }
+}
+
+/// A handle used to start draining a router created with
+/// [`Router::with_graceful_drain`](crate::Router::with_graceful_drain).
+#[derive(Clone)]
+pub struct DrainSignal(pub(crate) Arc<DrainState>);
+
+impl DrainSignal {
+    /// Stops the router from accepting new requests, which are answered with
+    /// `503 Service Unavailable` from now on. Requests already in flight are unaffected.
+    pub fn drain(&self) {
+        self.0.draining.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether [`DrainSignal::drain`] has been called.
+    pub fn is_draining(&self) -> bool {
+        self.0.draining.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of requests whose response futures haven't completed or been dropped.
+    pub fn in_flight(&self) -> usize {
+        self.0.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// A [`Future`] that resolves once a router created with
+/// [`Router::with_graceful_drain`](crate::Router::with_graceful_drain) has no requests in flight.
+pub struct DrainWatcher(pub(crate) Arc<DrainState>);
+
+impl Future for DrainWatcher {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0.in_flight.load(Ordering::SeqCst) == 0 {
+            return Poll::Ready(());
+        }
+
+        self.0.waker.register(cx.waker());
+
+        if self.0.in_flight.load(Ordering::SeqCst) == 0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Drains a router created with [`Router::graceful_timeout`](crate::Router::graceful_timeout),
+/// giving in-flight requests a deadline to complete.
+#[cfg(feature = "tokio")]
+pub struct GracefulShutdown {
+    pub(crate) signal: DrainSignal,
+    pub(crate) watcher: DrainWatcher,
+    pub(crate) timeout: std::time::Duration,
+}
+
+#[cfg(feature = "tokio")]
+impl GracefulShutdown {
+    /// Stops the router from accepting new requests and waits for in-flight requests to finish.
+    ///
+    /// Requests still in flight when the timeout elapses have their handlers dropped and are
+    /// answered with `503 Service Unavailable`, after which the returned future resolves.
+    pub async fn shutdown(self) {
+        self.signal.drain();
+
+        if tokio::time::timeout(self.timeout, self.watcher)
+            .await
+            .is_err()
+        {
+            self.signal.0.expire();
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::task::{Context, Poll};
+
+use http::{Request, Response};
+use tower::Service;
+
+use crate::{ResponseFuture, Router};
+
+/// A router whose routes are all registered, created with [`Router::build_and_check`].
+///
+/// A [`FrozenRouter`] serves requests exactly like the router it was built from, but has none of
+/// its builder methods, so code that's handed one can't register further routes through it.
+/// Routes registered through clones of the original router are still shared with it.
+pub struct FrozenRouter<Body, Data, Error, ResBody = Body> {
+    router: Router<Body, Data, Error, ResBody>,
+}
+
+impl<Body, Data, Error, ResBody> FrozenRouter<Body, Data, Error, ResBody> {
+    pub(crate) fn new(router: Router<Body, Data, Error, ResBody>) -> Self {
+        Self { router }
+    }
+
+    /// Returns the router this was built from, e.g. to register more routes.
+    pub fn into_inner(self) -> Router<Body, Data, Error, ResBody> {
+        self.router
+    }
+}
+
+impl<Body, Data, Error, ResBody> Clone for FrozenRouter<Body, Data, Error, ResBody> {
+    fn clone(&self) -> Self {
+        Self {
+            router: self.router.clone(),
+        }
+    }
+}
+
+impl<Body, Data, Error, ResBody> Service<Request<Body>> for FrozenRouter<Body, Data, Error, ResBody>
+where
+    Body: Default + 'static,
+    Data: Clone,
+    Error: 'static,
+    ResBody: Default + 'static,
+{
+    type Response = Response<ResBody>;
+
+    type Error = Error;
+
+    type Future = ResponseFuture<ResBody, Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.router.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        self.router.call(req)
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    io::{self, SeekFrom},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use bytes::Bytes;
+use http::{
+    header::{
+        ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH, RANGE,
+    },
+    HeaderMap, HeaderValue, Response, StatusCode,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+/// The portion of a file requested with a `Range` header.
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+/// Returns the name of the catchall parameter ending `path`, e.g. `file` for `/static/*file`.
+pub(crate) fn catchall_name(path: &str) -> Option<&str> {
+    path.rsplit('/')
+        .next()?
+        .strip_prefix('*')
+        .filter(|name| !name.is_empty())
+}
+
+/// Serves the file at `relative` under `root`, honoring `If-None-Match` and `Range` headers.
+pub(crate) async fn serve_file<Body>(
+    root: &Path,
+    relative: &str,
+    headers: &HeaderMap,
+) -> Response<Body>
+where
+    Body: From<Bytes>,
+{
+    match try_serve_file(root, relative, headers).await {
+        Ok(resp) => resp,
+        Err(err) => match err.kind() {
+            io::ErrorKind::NotFound => status(StatusCode::NOT_FOUND),
+            io::ErrorKind::PermissionDenied => status(StatusCode::FORBIDDEN),
+            _ => status(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+    }
+}
+
+async fn try_serve_file<Body>(
+    root: &Path,
+    relative: &str,
+    headers: &HeaderMap,
+) -> io::Result<Response<Body>>
+where
+    Body: From<Bytes>,
+{
+    let path = match resolve(root, relative) {
+        Some(path) => path,
+        None => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+
+    let mut file = File::open(&path).await?;
+    let metadata = file.metadata().await?;
+    if !metadata.is_file() {
+        return Ok(status(StatusCode::NOT_FOUND));
+    }
+
+    let len = metadata.len();
+    let etag = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .and_then(|modified| {
+            HeaderValue::try_from(format!("\"{:x}-{:x}\"", modified.as_secs(), len)).ok()
+        });
+
+    if let Some(etag) = &etag {
+        if matches_etag(headers, etag) {
+            let mut resp = status(StatusCode::NOT_MODIFIED);
+            resp.headers_mut().insert(ETAG, etag.clone());
+            return Ok(resp);
+        }
+    }
+
+    let range = headers
+        .get(RANGE)
+        .and_then(|range| range.to_str().ok())
+        .map_or(ByteRange::Full, |range| parse_range(range, len));
+
+    let (status_code, start, end) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, len),
+        ByteRange::Partial { start, end } => (StatusCode::PARTIAL_CONTENT, start, end + 1),
+        ByteRange::Unsatisfiable => {
+            let mut resp = status(StatusCode::RANGE_NOT_SATISFIABLE);
+            resp.headers_mut().insert(
+                CONTENT_RANGE,
+                HeaderValue::try_from(format!("bytes */{len}")).unwrap(),
+            );
+            return Ok(resp);
+        }
+    };
+
+    let mut contents = vec![0; (end - start) as usize];
+    file.seek(SeekFrom::Start(start)).await?;
+    file.read_exact(&mut contents).await?;
+
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    let mut resp = Response::new(Body::from(Bytes::from(contents)));
+    *resp.status_mut() = status_code;
+
+    let resp_headers = resp.headers_mut();
+    resp_headers.insert(CONTENT_TYPE, HeaderValue::try_from(mime.as_ref()).unwrap());
+    resp_headers.insert(CONTENT_LENGTH, HeaderValue::from(end - start));
+    resp_headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(etag) = etag {
+        resp_headers.insert(ETAG, etag);
+    }
+    if status_code == StatusCode::PARTIAL_CONTENT {
+        let content_range = format!("bytes {}-{}/{}", start, end - 1, len);
+        resp_headers.insert(CONTENT_RANGE, HeaderValue::try_from(content_range).unwrap());
+    }
+
+    Ok(resp)
+}
+
+/// Resolves `relative` under `root`, rejecting any path that could escape `root`.
+fn resolve(root: &Path, relative: &str) -> Option<PathBuf> {
+    let mut resolved = root.to_path_buf();
+
+    for component in Path::new(relative.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(segment) => resolved.push(segment),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    Some(resolved)
+}
+
+fn matches_etag(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let etag = etag.to_str().unwrap_or_default();
+
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Parses a single-range `Range` header, ignoring anything malformed or unsupported.
+fn parse_range(range: &str, len: u64) -> ByteRange {
+    let spec = match range.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec,
+        _ => return ByteRange::Full,
+    };
+    let (start, end) = match spec.split_once('-') {
+        Some((start, end)) => (start.trim(), end.trim()),
+        None => return ByteRange::Full,
+    };
+
+    if start.is_empty() {
+        return match end.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial {
+                start: len.saturating_sub(suffix),
+                end: len - 1,
+            },
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let start = match start.parse::<u64>() {
+        Ok(start) => start,
+        Err(_) => return ByteRange::Full,
+    };
+    let end = match end {
+        "" => len.saturating_sub(1),
+        end => match end.parse::<u64>() {
+            Ok(end) => end.min(len.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+    };
+
+    if start >= len || start > end {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial { start, end }
+    }
+}
+
+fn status<Body>(status: StatusCode) -> Response<Body>
+where
+    Body: From<Bytes>,
+{
+    let mut resp = Response::new(Body::from(Bytes::new()));
+    *resp.status_mut() = status;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{catchall_name, parse_range, resolve, ByteRange};
+
+    #[test]
+    fn catchall_names() {
+        assert_eq!(catchall_name("/static/*file"), Some("file"));
+        assert_eq!(catchall_name("/static/:file"), None);
+        assert_eq!(catchall_name("/static/*"), None);
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        let root = Path::new("/srv/public");
+        assert_eq!(
+            resolve(root, "css/site.css"),
+            Some(root.join("css/site.css"))
+        );
+        assert_eq!(resolve(root, "../secret"), None);
+        assert_eq!(resolve(root, "css/../../secret"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            parse_range("bytes=0-3", 10),
+            ByteRange::Partial { start: 0, end: 3 }
+        );
+        assert_eq!(
+            parse_range("bytes=5-", 10),
+            ByteRange::Partial { start: 5, end: 9 }
+        );
+        assert_eq!(
+            parse_range("bytes=-4", 10),
+            ByteRange::Partial { start: 6, end: 9 }
+        );
+        assert_eq!(
+            parse_range("bytes=4-100", 10),
+            ByteRange::Partial { start: 4, end: 9 }
+        );
+        assert_eq!(parse_range("bytes=10-", 10), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), ByteRange::Full);
+        assert_eq!(parse_range("lines=0-1", 10), ByteRange::Full);
+    }
+}
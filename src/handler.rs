@@ -1,48 +1,143 @@
-use std::{future::Future, rc::Rc, sync::Arc};
+use std::{
+    any::{type_name, type_name_of_val},
+    future::Future,
+    rc::Rc,
+    sync::Arc,
+};
 
 use futures_util::{
     future::{BoxFuture, LocalBoxFuture},
     FutureExt,
 };
-use http::{Request, Response};
+use http::{Method, Request, Response};
 
 use crate::{unsync, RouteContext};
 
-type Func<Body, Data, Error> = dyn Fn(Request<Body>, RouteContext<Data>) -> BoxFuture<'static, Result<Response<Body>, Error>>
+type Func<Body, Data, Error, ResBody> = dyn Fn(Request<Body>, RouteContext<Data>) -> BoxFuture<'static, Result<Response<ResBody>, Error>>
     + Sync
     + Send
     + 'static;
 
-#[derive(Clone)]
-pub struct AsyncHandler<Body, Data, Error>(pub Arc<Func<Body, Data, Error>>);
+/// A type-erased handler, along with the type name of the function it was created from.
+pub struct AsyncHandler<Body, Data, Error, ResBody = Body>(
+    pub Arc<Func<Body, Data, Error, ResBody>>,
+    pub &'static str,
+);
+
+impl<Body, Data, Error, ResBody> AsyncHandler<Body, Data, Error, ResBody> {
+    /// Replaces the handler's type name, e.g. with the name of a function it wraps.
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.1 = name;
+        self
+    }
+}
+
+impl<Body, Data, Error, ResBody> Clone for AsyncHandler<Body, Data, Error, ResBody> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), self.1)
+    }
+}
 
-impl<Body, Data, Error, HandlerFn, Fut> From<HandlerFn> for AsyncHandler<Body, Data, Error>
+impl<Body, Data, Error, ResBody, HandlerFn, Fut> From<HandlerFn>
+    for AsyncHandler<Body, Data, Error, ResBody>
 where
     HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
     HandlerFn: Sync + Send + 'static,
-    Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
 {
     fn from(value: HandlerFn) -> Self {
-        Self(Arc::new(move |req, data| Box::pin(value(req, data))))
+        Self(
+            Arc::new(move |req, data| Box::pin(value(req, data))),
+            type_name::<HandlerFn>(),
+        )
+    }
+}
+
+/// A route that can be declared in a `const` route table: its method, its path template and a
+/// plain function handling it, see [`Router::from_const_routes`](crate::Router::from_const_routes).
+///
+/// Every handler in a table has to have the same type, so handlers return boxed futures.
+pub type ConstRoute<Body, Data, Error, ResBody = Body> = (
+    Method,
+    &'static str,
+    fn(Request<Body>, RouteContext<Data>) -> BoxFuture<'static, Result<Response<ResBody>, Error>>,
+);
+
+impl<Body, Data, Error, ResBody> AsyncHandler<Body, Data, Error, ResBody> {
+    pub(crate) fn from_const(handler: ConstRoute<Body, Data, Error, ResBody>) -> Self
+    where
+        Body: 'static,
+        Data: 'static,
+        Error: 'static,
+        ResBody: 'static,
+    {
+        Self(Arc::new(handler.2), type_name_of_val(&handler.2))
     }
 }
 
-type UnsyncFunc<Body, Data, Error> = dyn Fn(
+type UnsyncFunc<Body, Data, Error, ResBody> = dyn Fn(
         Request<Body>,
         unsync::RouteContext<Data>,
-    ) -> LocalBoxFuture<'static, Result<Response<Body>, Error>>
+    ) -> LocalBoxFuture<'static, Result<Response<ResBody>, Error>>
     + 'static;
 
-#[derive(Clone)]
-pub struct AsyncUnsyncHandler<Body, Data, Error>(pub Rc<UnsyncFunc<Body, Data, Error>>);
+/// A type-erased handler, along with the type name of the function it was created from.
+pub struct AsyncUnsyncHandler<Body, Data, Error, ResBody = Body>(
+    pub Rc<UnsyncFunc<Body, Data, Error, ResBody>>,
+    pub &'static str,
+);
+
+impl<Body, Data, Error, ResBody> AsyncUnsyncHandler<Body, Data, Error, ResBody> {
+    /// Replaces the handler's type name, e.g. with the name of a function it wraps.
+    pub fn named(mut self, name: &'static str) -> Self {
+        self.1 = name;
+        self
+    }
+}
+
+impl<Body, Data, Error, ResBody> Clone for AsyncUnsyncHandler<Body, Data, Error, ResBody> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), self.1)
+    }
+}
+
+/// A route that can be declared in a `const` route table: its method, its path template and a
+/// plain function handling it, see
+/// [`Router::from_const_routes`](crate::unsync::Router::from_const_routes).
+///
+/// Every handler in a table has to have the same type, so handlers return boxed futures.
+pub type UnsyncConstRoute<Body, Data, Error, ResBody = Body> = (
+    Method,
+    &'static str,
+    fn(
+        Request<Body>,
+        unsync::RouteContext<Data>,
+    ) -> LocalBoxFuture<'static, Result<Response<ResBody>, Error>>,
+);
+
+impl<Body, Data, Error, ResBody> AsyncUnsyncHandler<Body, Data, Error, ResBody> {
+    pub(crate) fn from_const(handler: UnsyncConstRoute<Body, Data, Error, ResBody>) -> Self
+    where
+        Body: 'static,
+        Data: 'static,
+        Error: 'static,
+        ResBody: 'static,
+    {
+        Self(Rc::new(handler.2), type_name_of_val(&handler.2))
+    }
+}
 
-impl<Body, Data, Error, HandlerFn, Fut> From<HandlerFn> for AsyncUnsyncHandler<Body, Data, Error>
+impl<Body, Data, Error, ResBody, HandlerFn, Fut> From<HandlerFn>
+    for AsyncUnsyncHandler<Body, Data, Error, ResBody>
 where
     HandlerFn: Fn(Request<Body>, unsync::RouteContext<Data>) -> Fut,
     HandlerFn: 'static,
-    Fut: Future<Output = Result<Response<Body>, Error>> + 'static,
+    Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
 {
     fn from(value: HandlerFn) -> Self {
-        Self(Rc::new(move |req, data| value(req, data).boxed_local()))
+        Self(
+            Rc::new(move |req, data| value(req, data).boxed_local()),
+            type_name::<HandlerFn>(),
+        )
     }
 }
@@ -0,0 +1,34 @@
+use bytes::Bytes;
+use http::{header::CONTENT_TYPE, HeaderValue, Response, StatusCode};
+
+/// The result of a check registered with
+/// [`Router::health_endpoint`](crate::Router::health_endpoint) or
+/// [`Router::readiness_endpoint`](crate::Router::readiness_endpoint).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthStatus {
+    /// The service is working, answered with `200 OK`.
+    Healthy,
+    /// The service isn't working for the given reason, answered with
+    /// `503 Service Unavailable`.
+    ///
+    /// The reason is for the application's own use, e.g. logging, and isn't sent to the client.
+    Unhealthy(String),
+}
+
+impl HealthStatus {
+    pub(crate) fn to_response<Body>(&self) -> Response<Body>
+    where
+        Body: From<Bytes>,
+    {
+        let (status, body) = match self {
+            Self::Healthy => (StatusCode::OK, r#"{"status":"healthy"}"#),
+            Self::Unhealthy(_) => (StatusCode::SERVICE_UNAVAILABLE, r#"{"status":"unhealthy"}"#),
+        };
+
+        let mut resp = Response::new(Body::from(Bytes::from_static(body.as_bytes())));
+        *resp.status_mut() = status;
+        resp.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        resp
+    }
+}
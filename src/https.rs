@@ -0,0 +1,61 @@
+use http::{
+    header::{FORWARDED, HOST, LOCATION},
+    HeaderValue, Request, Response, StatusCode,
+};
+
+const X_FORWARDED_PROTO: &str = "x-forwarded-proto";
+
+/// Returns the protocol the client used to reach the proxy in front of the router, if known.
+fn forwarded_proto<Body>(req: &Request<Body>) -> Option<&str> {
+    let headers = req.headers();
+
+    if let Some(proto) = headers.get(X_FORWARDED_PROTO) {
+        return proto.to_str().ok()?.split(',').next().map(str::trim);
+    }
+
+    if let Some(forwarded) = headers.get(FORWARDED) {
+        let first = forwarded.to_str().ok()?.split(',').next()?;
+        return first.split(';').find_map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("proto")
+                .then(|| value.trim().trim_matches('"'))
+        });
+    }
+
+    req.uri().scheme_str()
+}
+
+/// Builds a redirect to the HTTPS version of `req` if it was made over plain HTTP.
+///
+/// Requests without a `Host` header or URI authority can't be redirected and are rejected with
+/// `400 Bad Request`.
+pub(crate) fn https_redirect<Body>(req: &Request<Body>) -> Option<Response<()>> {
+    if !forwarded_proto(req)?.eq_ignore_ascii_case("http") {
+        return None;
+    }
+
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()));
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let location =
+        host.and_then(|host| HeaderValue::try_from(format!("https://{host}{path}")).ok());
+
+    let mut resp = Response::new(());
+    match location {
+        Some(location) => {
+            *resp.status_mut() = StatusCode::MOVED_PERMANENTLY;
+            resp.headers_mut().insert(LOCATION, location);
+        }
+        None => *resp.status_mut() = StatusCode::BAD_REQUEST,
+    }
+
+    Some(resp)
+}
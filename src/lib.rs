@@ -28,29 +28,147 @@
 //! # });
 //! ```
 
+#[cfg(feature = "tracing")]
+mod access_log;
+#[cfg(feature = "actix-web")]
+mod actix;
+mod authority;
+mod body_limit;
+mod cache;
+mod chain;
+mod content_type;
+mod cors;
+#[cfg(feature = "csrf")]
+mod csrf;
+#[cfg(feature = "datadog")]
+mod datadog;
+mod drain;
+mod frozen;
+#[cfg(feature = "fs")]
+mod fs;
+mod group;
 mod handler;
+mod health;
+mod https;
+mod make;
+mod negotiate;
+mod path;
+mod probe;
+#[cfg(feature = "metrics")]
+mod prometheus;
+mod proxy;
+mod query;
+mod rate_limit;
+mod request_context;
+mod response;
+mod response_headers;
+mod route;
+mod route_builder;
+#[cfg(feature = "sentry")]
+mod sentry;
 mod service;
+#[cfg(feature = "sessions")]
+mod session;
+#[cfg(any(feature = "csrf", feature = "sessions"))]
+mod signed_cookie;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod unsync;
+#[cfg(feature = "tokio")]
+mod watch;
+#[cfg(feature = "websocket")]
+mod websocket;
 
+use std::any::{type_name, Any};
+use std::fmt;
 use std::future::Future;
+use std::net::IpAddr;
+#[cfg(feature = "fs")]
+use std::path::Path;
+use std::str::FromStr;
 use std::sync::RwLock;
+use std::time::Duration;
 use std::{collections::HashMap, sync::Arc};
 
-use http::{Method, Request, Response};
-use matchit::Router as MatchRouter;
+use bytes::Bytes;
+use futures_util::future::poll_fn;
+use http::{
+    header::{CONTENT_SECURITY_POLICY, SERVER, STRICT_TRANSPORT_SECURITY, VARY},
+    uri::PathAndQuery,
+    HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+};
+use tower::Service;
 
+use crate::authority::Hosts;
+use crate::body_limit::LimitFn;
+use crate::cache::ResponseCache;
+use crate::content_type::RequiredContentTypes;
+use crate::cors::CorsConfig;
+#[cfg(feature = "csrf")]
+use crate::csrf::{Csrf, IntoBody};
+use crate::drain::DrainState;
 use crate::handler::*;
+#[cfg(feature = "metrics")]
+use crate::prometheus::RouteMetrics;
+use crate::proxy::TrustedProxies;
+use crate::rate_limit::ApiKeyRateLimit;
+#[cfg(feature = "json")]
+use crate::response::json_error;
+use crate::response::{
+    set_content_length, ErrorResponse, PanicHandler, SetContentLength, StaticResponse,
+};
+use crate::response_headers::{ContextHeaders, ResponseHeaders, Scope};
+use crate::route::RouteTable;
+#[cfg(feature = "sentry")]
+use crate::sentry::ReportError;
+#[cfg(feature = "sessions")]
+use crate::session::Sessions;
 
+#[cfg(feature = "tracing")]
+pub use crate::access_log::LogFormat;
+#[cfg(feature = "actix-web")]
+pub use crate::actix::ActixService;
+pub use crate::body_limit::{BodyLimit, BodyTooLarge, LimitBody};
+pub use crate::chain::FallbackChain;
+#[cfg(feature = "datadog")]
+pub use crate::datadog::DatadogTrace;
+#[cfg(feature = "tokio")]
+pub use crate::drain::GracefulShutdown;
+pub use crate::drain::{DrainSignal, DrainWatcher};
+pub use crate::frozen::FrozenRouter;
+pub use crate::group::{Group, Next};
+pub use crate::handler::AsyncHandler as Handler;
+pub use crate::handler::ConstRoute;
+pub use crate::health::HealthStatus;
+pub use crate::make::MakeRouter;
+pub use crate::path::{ParseRoutePathError, RoutePath};
+pub use crate::probe::{Probe, ProbeLayer, WellKnownHandler};
+pub use crate::request_context::{RequestContext, TlsInfo};
+pub use crate::route::RoutingConflictError;
+pub use crate::route_builder::RouteBuilder;
 pub use crate::service::ResponseFuture;
+#[cfg(feature = "sessions")]
+pub use crate::session::{MemorySessionStore, Session, SessionData, SessionStore};
+#[cfg(feature = "tokio")]
+pub use crate::watch::{RouteChangeEvent, RouterWatcher};
+#[cfg(feature = "websocket")]
+pub use crate::websocket::WebSocketStream;
 
-#[derive(Default)]
-struct Route<Body, Data, Error> {
-    handlers: HashMap<Method, AsyncHandler<Body, Data, Error>>,
-    catchall: Option<AsyncHandler<Body, Data, Error>>,
-}
+/// A route table shared by a router and its clones.
+type SharedRoutes<Body, Data, Error, ResBody> =
+    Arc<RwLock<RouteTable<AsyncHandler<Body, Data, Error, ResBody>>>>;
 
 /// A router that can be used as a [`Service`](tower::Service).
 ///
+/// The router is [`Send`] and [`Sync`] as long as its data is, so it can be shared between
+/// threads or wrapped in services like `tower::buffer::Buffer`. Handlers that can't be sent
+/// between threads can use the [`unsync::Router`] instead.
+///
+/// Handlers take a `Request<Body>` and return a `Response<ResBody>`. The response body type is the
+/// same as the request body type unless it's set, e.g. to read streamed request bodies and
+/// answer with buffered responses. Responses the router makes itself, like `404 Not Found`, use
+/// `ResBody::default()` as their body.
+///
 /// # Example
 /// ```
 /// # futures::executor::block_on(async move {
@@ -71,26 +189,142 @@ struct Route<Body, Data, Error> {
 /// # });
 /// ```
 #[derive(Default)]
-pub struct Router<Body, Data: Clone, Error> {
-    inner: Arc<RwLock<MatchRouter<Route<Body, Data, Error>>>>,
-    data: Data,
+pub struct Router<Body, Data, Error, ResBody = Body> {
+    inner: SharedRoutes<Body, Data, Error, ResBody>,
+    data: Arc<Data>,
+    not_found: Option<StaticResponse<ResBody>>,
+    max_params: Option<usize>,
+    fallback: Option<AsyncHandler<Body, Data, Error, ResBody>>,
+    method_not_allowed: Option<AsyncHandler<Body, Data, Error, ResBody>>,
+    hosts: Option<Hosts<Router<Body, Data, Error, ResBody>>>,
+    require_https: bool,
+    any_trailing_slash: bool,
+    auto_head: bool,
+    base_path: Option<Arc<str>>,
+    drain: Option<Arc<DrainState>>,
+    response_headers: ResponseHeaders,
+    cors: Option<Arc<CorsConfig>>,
+    content_types: RequiredContentTypes,
+    content_length: Option<SetContentLength<ResBody>>,
+    request_context: Option<Arc<RequestContext>>,
+    trusted_proxies: Option<TrustedProxies>,
+    max_request_size: Option<(u64, LimitFn<Body>)>,
+    api_key_limit: Option<ApiKeyRateLimit>,
+    request_transform: Option<fn(Request<Body>) -> Request<Body>>,
+    response_transform: Option<fn(Response<ResBody>) -> Response<ResBody>>,
+    error_response: Option<ErrorResponse<ResBody, Error>>,
+    panic_handler: Option<PanicHandler<ResBody>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<RouteMetrics>>,
+    #[cfg(feature = "tracing")]
+    log_format: Option<LogFormat>,
+    #[cfg(feature = "sentry")]
+    sentry: Option<ReportError<Error>>,
+    #[cfg(feature = "datadog")]
+    datadog: Option<Arc<str>>,
+    #[cfg(feature = "tokio")]
+    shadows: Option<SharedRoutes<Body, Data, Error, ResBody>>,
+    #[cfg(feature = "sessions")]
+    sessions: Option<Sessions>,
+    #[cfg(feature = "csrf")]
+    csrf: Option<(Csrf, IntoBody<ResBody>)>,
 }
 
-impl<Body, Error> Router<Body, (), Error> {
+impl<Body, Error, ResBody> Router<Body, (), Error, ResBody> {
     /// Create a new router that doesn't require any data to be passed to handlers.
     pub fn new() -> Self {
         Self {
             inner: Default::default(),
-            data: (),
+            data: Arc::new(()),
+            not_found: None,
+            max_params: None,
+            fallback: None,
+            method_not_allowed: None,
+            hosts: None,
+            require_https: false,
+            any_trailing_slash: false,
+            auto_head: true,
+            base_path: None,
+            drain: None,
+            response_headers: ResponseHeaders::default(),
+            cors: None,
+            content_types: RequiredContentTypes::default(),
+            content_length: None,
+            request_context: None,
+            trusted_proxies: None,
+            max_request_size: None,
+            api_key_limit: None,
+            request_transform: None,
+            response_transform: None,
+            error_response: None,
+            panic_handler: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "tracing")]
+            log_format: None,
+            #[cfg(feature = "sentry")]
+            sentry: None,
+            #[cfg(feature = "datadog")]
+            datadog: None,
+            #[cfg(feature = "tokio")]
+            shadows: Default::default(),
+            #[cfg(feature = "sessions")]
+            sessions: None,
+            #[cfg(feature = "csrf")]
+            csrf: None,
         }
     }
+
+    /// Creates a router from a declarative route table of [`ConstRoute`]s, see
+    /// [`Router::with_const_routes`].
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use futures_util::future::BoxFuture;
+    /// use http::{Method, Request, Response};
+    /// use tower::Service;
+    /// use router_service::{ConstRoute, RouteContext, Router};
+    ///
+    /// type Result = std::result::Result<Response<String>, Infallible>;
+    ///
+    /// fn list_users(_: Request<String>, _: RouteContext<()>) -> BoxFuture<'static, Result> {
+    ///     Box::pin(async move { Ok(Response::new("users".to_string())) })
+    /// }
+    ///
+    /// fn create_user(_: Request<String>, _: RouteContext<()>) -> BoxFuture<'static, Result> {
+    ///     Box::pin(async move { Ok(Response::new("created".to_string())) })
+    /// }
+    ///
+    /// const ROUTES: &[ConstRoute<String, (), Infallible>] = &[
+    ///     (Method::GET, "/users", list_users),
+    ///     (Method::POST, "/users", create_user),
+    /// ];
+    ///
+    /// let mut router = Router::from_const_routes(ROUTES);
+    ///
+    /// let req = Request::post("/users").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "created");
+    /// # });
+    /// ```
+    pub fn from_const_routes(routes: &[ConstRoute<Body, (), Error, ResBody>]) -> Self
+    where
+        Body: 'static,
+        Error: 'static,
+        ResBody: 'static,
+    {
+        Self::new().with_const_routes(routes)
+    }
 }
 
-impl<Body, Data, Error> Router<Body, Data, Error>
+impl<Body, Data, Error, ResBody> Router<Body, Data, Error, ResBody>
 where
     Body: 'static,
-    Data: Clone + 'static,
+    Data: 'static,
     Error: 'static,
+    ResBody: 'static,
 {
     /// Create a new router that requires data to be passed to handlers.
     ///
@@ -115,10 +349,41 @@ where
     /// # });
     /// ```
     pub fn with_data(data: Data) -> Self {
-        Self {
-            inner: Default::default(),
-            data,
-        }
+        Self::with_shared_data(Arc::new(data))
+    }
+
+    /// Create a new router whose handlers share a database connection pool.
+    ///
+    /// This is the same as [`Router::with_data`], but reads better for the common case of the
+    /// data being a pool: handlers get at it through [`RouteContext::db`]. Pools are usually
+    /// cheap to clone handles to shared state, such as `sqlx::PgPool` or `bb8::Pool`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{collections::HashMap, convert::Infallible, sync::Arc};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// // Stands in for a real connection pool.
+    /// #[derive(Clone)]
+    /// struct Pool(Arc<HashMap<&'static str, &'static str>>);
+    ///
+    /// let pool = Pool(Arc::new(HashMap::from([("42", "alice")])));
+    /// let mut router = Router::with_connection_pool(pool).get("/users/:id", |_, ctx| async move {
+    ///     let name = ctx.db().0[ctx.param("id").unwrap()];
+    ///     Ok::<_, Infallible>(Response::new(name.to_string()))
+    /// });
+    ///
+    /// let req = Request::get("/users/42").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.body(), "alice");
+    /// # });
+    /// ```
+    pub fn with_connection_pool(pool: Data) -> Self {
+        Self::with_data(pool)
     }
 
     /// Registers a route requiring the `GET` method.
@@ -126,9 +391,172 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::GET, path, handler)
+    }
+
+    /// Registers a route requiring the `GET` method under `name`, so that its path can be built
+    /// with [`Router::url_for`].
+    ///
+    /// # Panics
+    /// Panics if `name` is already used for a route with a different path.
+    pub fn get_named<HandlerFn, Fut>(
+        self,
+        name: &str,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method_named(Method::GET, name, path, handler)
+    }
+
+    /// Builds the path of the route registered under `name`, e.g. for a `Location` header or a
+    /// link, by substituting `params` for the parameters in its template.
+    ///
+    /// Parameters are looked up by name, without the leading `:` or `*`, and their values are
+    /// inserted as they are, so they need to be percent-encoded already. Paths start with the
+    /// router's base path, see [`Router::with_base_path`].
+    ///
+    /// Returns `None` if no route is registered under `name`, or if one of its parameters is
+    /// missing from `params`.
+    ///
+    /// # Example
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use http::Response;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<String, (), Infallible> = Router::new()
+    ///     .get_named("user", "/users/:id", |_, _| async move { Ok(Response::new(String::new())) })
+    ///     .with_base_path("/api");
+    ///
+    /// assert_eq!(router.url_for("user", &[("id", "42")]).as_deref(), Some("/api/users/42"));
+    /// assert_eq!(router.url_for("user", &[]), None);
+    /// assert_eq!(router.url_for("users", &[("id", "42")]), None);
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
+        let path = self.inner.read().unwrap().url_for(name, params)?;
+        Some(format!(
+            "{}{path}",
+            self.base_path.as_deref().unwrap_or_default()
+        ))
+    }
+
+    /// Registers a route requiring the `GET` method whose successful responses are cached.
+    ///
+    /// Responses are cached per request URI (path and query) for `ttl`, so repeated requests
+    /// within that window are answered without invoking `handler`. Each cached route remembers
+    /// up to 1024 URIs, evicting the least recently used entry when full.
+    ///
+    /// Responses setting cookies or with a `Cache-Control` header marking them `private` or
+    /// `no-store` are meant for a single client and aren't cached. Responses with a `Vary`
+    /// header are only served to requests with the same values for the headers it names as the
+    /// request they were made for, and responses with `Vary: *` aren't cached. The route learns
+    /// which headers a URI varies on from its first response, so that response isn't cached.
+    /// Other responses are served to every client, so handlers shouldn't use e.g. credentials to
+    /// build them.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{convert::Infallible, time::Duration};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new().get_with_cache(
+    ///     "/expensive",
+    ///     |_, _| async move { Ok(Response::new("computed".to_string())) },
+    ///     Duration::from_secs(60),
+    /// );
+    ///
+    /// let req = Request::get("/expensive").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.body(), "computed");
+    /// # });
+    /// ```
+    pub fn get_with_cache<HandlerFn, Fut>(
+        self,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+        ttl: Duration,
+    ) -> Self
+    where
+        ResBody: Clone + Send + Sync,
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        let cache = ResponseCache::new(ttl);
+
+        let cached = AsyncHandler::from(move |req: Request<Body>, ctx| {
+            let key = req.uri().to_string();
+            let lookup = match cache.get(&key, req.headers()) {
+                Ok(resp) => Ok(resp),
+                Err(vary) => Err((handler(req, ctx), vary)),
+            };
+            let cache = cache.clone();
+
+            async move {
+                match lookup {
+                    Ok(resp) => Ok(resp),
+                    Err((fut, vary)) => {
+                        let resp = fut.await?;
+                        cache.insert(key, vary, &resp);
+                        Ok(resp)
+                    }
+                }
+            }
+        });
+
+        self.insert_handler(path, Method::GET, cached.named(type_name::<HandlerFn>()))
+    }
+
+    /// Registers a route requiring the `GET` method, returning an error instead of panicking if
+    /// `path` is malformed or conflicts with a route registered before it.
+    ///
+    /// This is useful for paths that aren't known ahead of time, e.g. read from configuration.
+    ///
+    /// # Example
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use http::Response;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/files/:id/raw", |_, _| async move { Ok(Response::new(())) });
+    ///
+    /// let err = router
+    ///     .try_get("/files/*path", |_, _| async move { Ok(Response::new(())) })
+    ///     .err()
+    ///     .unwrap();
+    /// assert_eq!(err.conflicts_with(), Some("/files/:id/raw"));
+    /// ```
+    pub fn try_get<HandlerFn, Fut>(
+        self,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Result<Self, RoutingConflictError>
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::GET, handler)
+        self.inner.write().unwrap().try_insert_handler(
+            path.as_ref(),
+            Method::GET,
+            handler.into(),
+        )?;
+
+        Ok(self)
     }
 
     /// Registers a route requiring the `POST` method.
@@ -136,9 +564,9 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::POST, handler)
+        self.method(Method::POST, path, handler)
     }
 
     /// Registers a route requiring the `PUT` method.
@@ -146,9 +574,9 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::PUT, handler)
+        self.method(Method::PUT, path, handler)
     }
 
     /// Registers a route requiring the `DELETE` method.
@@ -156,9 +584,9 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::DELETE, handler)
+        self.method(Method::DELETE, path, handler)
     }
 
     /// Registers a route requiring the `HEAD` method.
@@ -166,19 +594,22 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::HEAD, handler)
+        self.method(Method::HEAD, path, handler)
     }
 
     /// Registers a route requiring the `OPTIONS` method.
+    ///
+    /// Without one, `OPTIONS` requests to a route are answered with `200 OK` and an `Allow`
+    /// header listing the route's methods.
     pub fn options<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::DELETE, handler)
+        self.method(Method::OPTIONS, path, handler)
     }
 
     /// Registers a route requiring the `PATCH` method.
@@ -186,167 +617,5664 @@ where
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        self.insert_handler(path, Method::PATCH, handler)
+        self.method(Method::PATCH, path, handler)
     }
 
-    /// Registers a route matching any method.
-    pub fn any<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    /// Registers a route requiring the `TRACE` method.
+    pub fn trace<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
     where
         HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
         HandlerFn: Sync + Send + 'static,
-        Fut: Future<Output = Result<Response<Body>, Error>> + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        let mut inner = self.inner.write().unwrap();
-
-        if let Ok(existing) = inner.at_mut(path.as_ref()) {
-            existing.value.catchall = Some(handler.into());
-        } else {
-            inner
-                .insert(
-                    path.as_ref(),
-                    Route {
-                        handlers: HashMap::new(),
-                        catchall: Some(handler.into()),
-                    },
-                )
-                .expect("unable to add route to router");
-        }
-
-        drop(inner);
+        self.method(Method::TRACE, path, handler)
+    }
 
-        self
+    /// Registers a route requiring the `CONNECT` method, used to open tunnels through proxies.
+    ///
+    /// `CONNECT` requests usually name the host to tunnel to instead of a path, e.g.
+    /// `CONNECT example.com:443`, and are routed as if their path were `/`, leaving the target in
+    /// the request URI's authority.
+    ///
+    /// The request body is passed to the handler like any other, so it can't be used to stream
+    /// data both ways. To tunnel, respond with a `2xx` status and take over the connection once
+    /// the response is sent, e.g. with `hyper::upgrade::on` when serving the router with hyper.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new().connect("/", |req: Request<String>, _| async move {
+    ///     let target = req.uri().authority().unwrap().to_string();
+    ///     Ok::<_, Infallible>(Response::new(format!("tunnel to {target}")))
+    /// });
+    ///
+    /// let req = Request::connect("example.com:443").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "tunnel to example.com:443");
+    /// # });
+    /// ```
+    pub fn connect<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::CONNECT, path, handler)
     }
 
-    fn insert_handler<H>(self, path: impl AsRef<str>, method: Method, handler: H) -> Self
+    /// Registers a route requiring the `LINK` method, used by some storage APIs to link resources.
+    pub fn link<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
     where
-        H: Into<AsyncHandler<Body, Data, Error>>,
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
     {
-        let mut inner = self.inner.write().unwrap();
-        if let Ok(existing) = inner.at_mut(path.as_ref()) {
-            existing.value.handlers.insert(method, handler.into());
-        } else {
-            let mut handlers: HashMap<Method, AsyncHandler<Body, Data, Error>> = HashMap::new();
-            handlers.insert(method, handler.into());
-
-            inner
-                .insert(
-                    path.as_ref(),
-                    Route {
-                        handlers,
-                        catchall: None,
-                    },
-                )
-                .expect("unable to add route to router");
-        }
+        self.method(Method::from_bytes(b"LINK").unwrap(), path, handler)
+    }
 
-        drop(inner);
+    /// Registers a route requiring the `UNLINK` method, the counterpart of `LINK`.
+    pub fn unlink<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"UNLINK").unwrap(), path, handler)
+    }
 
-        self
+    /// Registers a route requiring the `LOCK` method, used by WebDAV to lock a resource.
+    pub fn lock<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"LOCK").unwrap(), path, handler)
     }
-}
 
-impl<Body, Data, Error> Clone for Router<Body, Data, Error>
-where
-    Data: Clone,
-{
-    fn clone(&self) -> Self {
-        Self {
-            inner: self.inner.clone(),
-            data: self.data.clone(),
-        }
+    /// Registers a route requiring the `UNLOCK` method, used by WebDAV to remove a lock.
+    pub fn unlock<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"UNLOCK").unwrap(), path, handler)
     }
-}
 
-/// The context of a matched route.
-#[derive(Debug)]
-pub struct RouteContext<T> {
-    /// Arbitrary data associated with the router that is available to all handlers.
-    pub data: T,
-    params: HashMap<String, String>,
-}
+    /// Registers a route requiring the `MKCOL` method, used by WebDAV to create a collection.
+    pub fn mkcol<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"MKCOL").unwrap(), path, handler)
+    }
 
-impl<T> RouteContext<T> {
-    /// Returns a parameter value from the path by name.
-    pub fn param(&self, name: impl AsRef<str>) -> Option<&str> {
-        self.params.get(name.as_ref()).map(|s| s.as_str())
+    /// Registers a route requiring the `COPY` method, used by WebDAV to copy a resource.
+    pub fn copy<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"COPY").unwrap(), path, handler)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        convert::Infallible,
-        sync::{
-            atomic::{AtomicBool, Ordering},
-            Arc,
-        },
-    };
+    /// Registers a route requiring the `MOVE` method, used by WebDAV to move a resource.
+    ///
+    /// The trailing underscore avoids the `move` keyword.
+    pub fn move_<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"MOVE").unwrap(), path, handler)
+    }
 
-    use http::{Method, Request, Response};
-    use tower::Service;
+    /// Registers a route requiring the `PROPFIND` method, used by WebDAV to read the properties
+    /// of a resource.
+    pub fn propfind<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"PROPFIND").unwrap(), path, handler)
+    }
 
-    use crate::Router;
+    /// Registers a route requiring the `PROPPATCH` method, used by WebDAV to change the properties
+    /// of a resource.
+    pub fn proppatch<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"PROPPATCH").unwrap(), path, handler)
+    }
 
-    #[test]
-    fn not_found() {
-        futures::executor::block_on(async move {
-            let mut router: Router<(), (), Infallible> = Router::new();
-            let req = Request::builder()
-                .uri("/not-found")
-                .method(Method::GET)
-                .body(())
-                .unwrap();
+    /// Registers a route requiring the `REPORT` method, used by CalDAV and CardDAV for queries.
+    pub fn report<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"REPORT").unwrap(), path, handler)
+    }
 
-            let resp = router.call(req).await;
-            let resp = resp.unwrap();
+    /// Registers a route requiring the `SEARCH` method, used by WebDAV search (RFC 5323).
+    pub fn search<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::from_bytes(b"SEARCH").unwrap(), path, handler)
+    }
 
-            assert_eq!(resp.status(), 404);
-        });
+    /// Registers a route requiring `method`, which can be any method, including ones without a
+    /// builder method of their own like the `PURGE` method used by caching proxies.
+    ///
+    /// The builder methods for specific methods, like [`Router::get`], are shorthands for this.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Method, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let purge = Method::from_bytes(b"PURGE").unwrap();
+    /// let mut router = Router::new().method(purge.clone(), "/cache/*key", |_, ctx| async move {
+    ///     let key = ctx.param("key").unwrap().to_string();
+    ///     Ok::<_, Infallible>(Response::new(format!("purged {key}")))
+    /// });
+    ///
+    /// let req = Request::builder()
+    ///     .method(purge)
+    ///     .uri("/cache/logo.png")
+    ///     .body(String::new())
+    ///     .unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "purged logo.png");
+    /// # });
+    /// ```
+    pub fn method<HandlerFn, Fut>(
+        self,
+        method: Method,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.insert_handler(path, method, handler)
     }
 
-    #[test]
-    fn receives_data() {
+    /// Registers a route requiring `method` under `name`, see [`Router::get_named`].
+    ///
+    /// # Panics
+    /// Panics if `name` is already used for a route with a different path.
+    pub fn method_named<HandlerFn, Fut>(
+        self,
+        method: Method,
+        name: &str,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.inner.write().unwrap().name(name, path.as_ref());
+        self.insert_handler(path, method, handler)
+    }
+
+    /// Registers a route requiring one of `methods`, all handled by the same `handler`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Method, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let methods = [Method::GET, Method::POST];
+    /// let mut router = Router::new().on(&methods, "/search", |req, _| async move {
+    ///     Ok::<_, Infallible>(Response::new(format!("{} search", req.method())))
+    /// });
+    ///
+    /// let req = Request::post("/search").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "POST search");
+    /// # });
+    /// ```
+    pub fn on<HandlerFn, Fut>(
+        self,
+        methods: &[Method],
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        let handler: AsyncHandler<Body, Data, Error, ResBody> = handler.into();
+        let mut inner = self.inner.write().unwrap();
+        for method in methods {
+            inner.insert_handler(path.as_ref(), method.clone(), handler.clone());
+        }
+        drop(inner);
+
+        self
+    }
+
+    /// Registers a route matching any method.
+    pub fn any<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.inner
+            .write()
+            .unwrap()
+            .insert_catchall(path.as_ref(), handler.into());
+
+        self
+    }
+
+    /// Registers every route in a declarative route table, in order.
+    ///
+    /// The table can be a `const`, defined next to the handlers, which keeps the routes of an
+    /// application in one place. Registering a route from the table is the same as registering it
+    /// with the builder method for its method, and any method can be used, including extension
+    /// methods. The routes are still inserted into the route tree when this is called. See
+    /// [`Router::from_const_routes`] for an example.
+    pub fn with_const_routes(self, routes: &[ConstRoute<Body, Data, Error, ResBody>]) -> Self {
+        {
+            let mut inner = self.inner.write().unwrap();
+            for route in routes {
+                let (method, path, _) = route;
+                inner.insert_handler(
+                    path,
+                    method.clone(),
+                    AsyncHandler::from_const(route.clone()),
+                );
+            }
+        }
+
+        self
+    }
+
+    /// Registers a shadow route, whose handler is sent a copy of every request matching `path`
+    /// without affecting how the request is answered.
+    ///
+    /// Shadow routes are matched separately from the other routes and for every method, and are
+    /// only sent the requests that are routed to a handler, not those answered with e.g.
+    /// `404 Not Found`. The handler is run to completion in a separate task on the Tokio runtime
+    /// and its response is discarded, so it can be used for auditing or to try out a new
+    /// implementation of a route on live traffic. When the router isn't called from a Tokio
+    /// runtime, the copy is dropped. The copy of the request has the same method, URI, version
+    /// and headers, but an empty body, since bodies can only be read once. Requires the `tokio`
+    /// feature.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tokio::sync::mpsc;
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let (audit, mut audited) = mpsc::unbounded_channel();
+    /// let mut router = Router::new()
+    ///     .get("/users/:id", |_, _| async move { Ok::<_, Infallible>(Response::new(())) })
+    ///     .shadow_route("/*path", move |req: Request<()>, _| {
+    ///         let audit = audit.clone();
+    ///         async move {
+    ///             audit.send(req.uri().to_string()).unwrap();
+    ///             Ok(Response::new(()))
+    ///         }
+    ///     });
+    ///
+    /// let req = Request::get("/users/42").body(()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), 200);
+    /// assert_eq!(audited.recv().await.unwrap(), "/users/42");
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn shadow_route<HandlerFn, Fut>(mut self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.shadows
+            .get_or_insert_with(Default::default)
+            .write()
+            .unwrap()
+            .insert_catchall(path.as_ref(), handler.into());
+
+        self
+    }
+
+    /// Serves the files under `directory` for `GET` requests matching `path`.
+    ///
+    /// `path` must end in a catchall parameter (e.g. `/static/*file`) whose value is resolved
+    /// relative to `directory`; paths that would escape `directory` are answered with
+    /// `404 Not Found`. Responses carry a `Content-Type` guessed from the file extension and an
+    /// `ETag`, and the router honors `If-None-Match` and single-range `Range` request headers.
+    ///
+    /// Files are read with [`tokio::fs`], so this requires a Tokio runtime and the `fs` feature.
+    ///
+    /// # Panics
+    /// Panics if `path` doesn't end in a catchall parameter.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::convert::Infallible;
+    ///
+    /// use hyper::Body;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<Body, (), Infallible> =
+    ///     Router::new().serve_dir("/static/*file", "./public");
+    /// ```
+    #[cfg(feature = "fs")]
+    pub fn serve_dir(self, path: impl AsRef<str>, directory: impl AsRef<Path>) -> Self
+    where
+        ResBody: From<Bytes> + Send + Sync,
+    {
+        let param = crate::fs::catchall_name(path.as_ref())
+            .expect(
+                "serve_dir requires a path ending in a catchall parameter, e.g. `/static/*file`",
+            )
+            .to_owned();
+        let root: Arc<Path> = directory.as_ref().into();
+
+        self.get(path, move |req, ctx| {
+            let root = root.clone();
+            let file = ctx.param(&param).unwrap_or_default().to_owned();
+            let (parts, _) = req.into_parts();
+
+            async move { Ok(crate::fs::serve_file(&root, &file, &parts.headers).await) }
+        })
+    }
+
+    /// Accepts WebSocket connections on `path`, handing each one to `handler` along with the
+    /// context of the request that opened it.
+    ///
+    /// `GET` requests to `path` are checked for a valid opening handshake and answered with
+    /// `101 Switching Protocols`, after which `handler` is spawned as a Tokio task with the
+    /// upgraded connection. Requests that aren't WebSocket handshakes are answered with
+    /// `426 Upgrade Required`, and handshakes made when the router isn't called from a Tokio
+    /// runtime with `500 Internal Server Error`. The connection is taken over through
+    /// [`hyper::upgrade`], so the router has to be served by hyper, with upgrades enabled for
+    /// connections served with `serve_connection`.
+    ///
+    /// This requires the `websocket` feature.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::convert::Infallible;
+    ///
+    /// use futures::{SinkExt, StreamExt};
+    /// use hyper::Body;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<Body, (), Infallible> =
+    ///     Router::new().websocket_upgrade("/echo", |mut ws, _| async move {
+    ///         while let Some(Ok(msg)) = ws.next().await {
+    ///             if ws.send(msg).await.is_err() {
+    ///                 break;
+    ///             }
+    ///         }
+    ///     });
+    /// ```
+    #[cfg(feature = "websocket")]
+    pub fn websocket_upgrade<HandlerFn, Fut>(
+        self,
+        path: impl AsRef<str>,
+        handler: HandlerFn,
+    ) -> Self
+    where
+        ResBody: Default + Send + Sync,
+        Data: Send,
+        HandlerFn: Fn(crate::WebSocketStream, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.get(path, move |mut req, ctx| {
+            let resp = match crate::websocket::accept(&mut req) {
+                Ok((resp, on_upgrade)) => match tokio::runtime::Handle::try_current() {
+                    Ok(runtime) => {
+                        let handler = handler.clone();
+                        runtime.spawn(async move {
+                            if let Some(ws) = crate::websocket::upgraded(on_upgrade).await {
+                                handler(ws, ctx).await;
+                            }
+                        });
+                        resp
+                    }
+                    Err(_) => crate::websocket::cannot_spawn(),
+                },
+                Err(resp) => resp,
+            };
+
+            async move { Ok(resp) }
+        })
+    }
+
+    /// Registers a `GET` route at `path` serving one representation of a resource per media type,
+    /// picking the handler whose type the client prefers according to its `Accept` header.
+    ///
+    /// Each media type gets the `q`-value of the most specific range in `Accept` matching it, and
+    /// ties go to the range listed first, then to the type that sorts first. Requests without an
+    /// `Accept` header accept any type, and requests accepting none of the types are answered with
+    /// `406 Not Acceptable`. Responses carry `Vary: Accept`, so caches keep the representations
+    /// apart.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{collections::HashMap, convert::Infallible};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{Handler, Router};
+    ///
+    /// let handlers: HashMap<_, Handler<String, (), Infallible>> = HashMap::from([
+    ///     (
+    ///         mime::APPLICATION_JSON,
+    ///         Handler::from(|_, _| async move { Ok(Response::new(r#"{"id":1}"#.to_string())) }),
+    ///     ),
+    ///     (
+    ///         mime::TEXT_HTML,
+    ///         Handler::from(|_, _| async move { Ok(Response::new("<p>1</p>".to_string())) }),
+    ///     ),
+    /// ]);
+    /// let mut router = Router::new().content_negotiation_handler("/users/1", handlers);
+    ///
+    /// let req = Request::get("/users/1")
+    ///     .header("accept", "text/html;q=0.9, application/json")
+    ///     .body(String::new())
+    ///     .unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.body(), r#"{"id":1}"#);
+    /// # });
+    /// ```
+    pub fn content_negotiation_handler(
+        self,
+        path: impl AsRef<str>,
+        handlers: HashMap<mime::Mime, Handler<Body, Data, Error, ResBody>>,
+    ) -> Self
+    where
+        ResBody: Default,
+    {
+        let mut handlers: Vec<_> = handlers.into_iter().collect();
+        handlers.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+
+        self.insert_handler(
+            path,
+            Method::GET,
+            AsyncHandler(
+                Arc::new(move |req, ctx| {
+                    let handler = crate::negotiate::negotiate(
+                        req.headers(),
+                        handlers.iter().map(|(mime, _)| mime),
+                    )
+                    .map(|index| (handlers[index].1 .0)(req, ctx));
+
+                    Box::pin(async move {
+                        let mut resp = match handler {
+                            Some(handler) => handler.await?,
+                            None => {
+                                let mut resp = Response::new(ResBody::default());
+                                *resp.status_mut() = StatusCode::NOT_ACCEPTABLE;
+                                resp
+                            }
+                        };
+                        resp.headers_mut()
+                            .append(VARY, HeaderValue::from_static("accept"));
+                        Ok(resp)
+                    })
+                }),
+                "content negotiation",
+            ),
+        )
+    }
+
+    /// Registers a `GET` route at `path` serving one translation of a resource per language,
+    /// picking the handler whose language the client prefers according to its `Accept-Language`
+    /// header.
+    ///
+    /// Language ranges match tags as in the basic filtering of RFC 4647, so `de` matches both `de`
+    /// and `de-AT`, and each language gets the `q`-value of the most specific range matching it.
+    /// Ties go to the range listed first, then to the tag that sorts first. Requests without an
+    /// `Accept-Language` header accept any language, and requests accepting none of them are
+    /// answered with `406 Not Acceptable`. Responses carry `Vary: Accept-Language` and, unless the
+    /// handler sets one, a `Content-Language` header naming the chosen language. Requires the
+    /// `i18n` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{collections::HashMap, convert::Infallible};
+    ///
+    /// use http::{Request, Response};
+    /// use language_tags::LanguageTag;
+    /// use tower::Service;
+    /// use router_service::{Handler, Router};
+    ///
+    /// let handlers: HashMap<_, Handler<String, (), Infallible>> = HashMap::from([
+    ///     (
+    ///         LanguageTag::parse("en").unwrap(),
+    ///         Handler::from(|_, _| async move { Ok(Response::new("Hello".to_string())) }),
+    ///     ),
+    ///     (
+    ///         LanguageTag::parse("de").unwrap(),
+    ///         Handler::from(|_, _| async move { Ok(Response::new("Hallo".to_string())) }),
+    ///     ),
+    /// ]);
+    /// let mut router = Router::new().language_negotiation_handler("/greeting", handlers);
+    ///
+    /// let req = Request::get("/greeting")
+    ///     .header("accept-language", "fr, de;q=0.8, en;q=0.5")
+    ///     .body(String::new())
+    ///     .unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.headers()["content-language"], "de");
+    /// assert_eq!(resp.body(), "Hallo");
+    /// # });
+    /// ```
+    #[cfg(feature = "i18n")]
+    pub fn language_negotiation_handler(
+        self,
+        path: impl AsRef<str>,
+        handlers: HashMap<language_tags::LanguageTag, Handler<Body, Data, Error, ResBody>>,
+    ) -> Self
+    where
+        ResBody: Default,
+    {
+        let mut handlers: Vec<_> = handlers.into_iter().collect();
+        handlers.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+        self.insert_handler(
+            path,
+            Method::GET,
+            AsyncHandler(
+                Arc::new(move |req, ctx| {
+                    let handler = crate::negotiate::negotiate_language(
+                        req.headers(),
+                        handlers.iter().map(|(tag, _)| tag),
+                    )
+                    .map(|index| {
+                        let (tag, handler) = &handlers[index];
+                        let language = HeaderValue::from_str(tag.as_str()).unwrap();
+                        ((handler.0)(req, ctx), language)
+                    });
+
+                    Box::pin(async move {
+                        let mut resp = match handler {
+                            Some((handler, language)) => {
+                                let mut resp = handler.await?;
+                                resp.headers_mut()
+                                    .entry(http::header::CONTENT_LANGUAGE)
+                                    .or_insert(language);
+                                resp
+                            }
+                            None => {
+                                let mut resp = Response::new(ResBody::default());
+                                *resp.status_mut() = StatusCode::NOT_ACCEPTABLE;
+                                resp
+                            }
+                        };
+                        resp.headers_mut()
+                            .append(VARY, HeaderValue::from_static("accept-language"));
+                        Ok(resp)
+                    })
+                }),
+                "language negotiation",
+            ),
+        )
+    }
+
+    /// Responds to requests that don't match any route with a precomputed `404 Not Found`.
+    ///
+    /// The content type header and body are prepared once, so serving a miss only clones `bytes`.
+    ///
+    /// # Panics
+    /// Panics if `content_type` isn't a valid header value.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use bytes::Bytes;
+    /// use http::Request;
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<Bytes, (), Infallible> =
+    ///     Router::new().not_found_bytes("text/html", "<h1>Not Found</h1>");
+    ///
+    /// let req = Request::get("/missing").body(Bytes::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 404);
+    /// assert_eq!(resp.body(), "<h1>Not Found</h1>");
+    /// # });
+    /// ```
+    pub fn not_found_bytes(mut self, content_type: &str, bytes: impl Into<Bytes>) -> Self
+    where
+        ResBody: From<Bytes>,
+    {
+        self.not_found = Some(StaticResponse::new(
+            StatusCode::NOT_FOUND,
+            content_type,
+            bytes.into(),
+        ));
+        self
+    }
+
+    /// Registers a `GET` route at `path` that reports the result of `check`, for use as a
+    /// liveness probe.
+    ///
+    /// `check` runs on every request. [`HealthStatus::Healthy`] is answered with `200 OK` and a
+    /// body of `{"status":"healthy"}`, while [`HealthStatus::Unhealthy`] is answered with
+    /// `503 Service Unavailable` and a body of `{"status":"unhealthy"}`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use bytes::Bytes;
+    /// use http::Request;
+    /// use tower::Service;
+    /// use router_service::{HealthStatus, Router};
+    ///
+    /// let mut router: Router<Bytes, (), Infallible> =
+    ///     Router::new().health_endpoint("/healthz", || HealthStatus::Healthy);
+    ///
+    /// let req = Request::get("/healthz").body(Bytes::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 200);
+    /// assert_eq!(resp.body(), r#"{"status":"healthy"}"#);
+    /// # });
+    /// ```
+    pub fn health_endpoint<F>(self, path: impl AsRef<str>, check: F) -> Self
+    where
+        ResBody: From<Bytes>,
+        F: Fn() -> HealthStatus + Send + Sync + 'static,
+    {
+        self.status_endpoint(path, check)
+    }
+
+    /// Registers a `GET` route at `path` that reports the result of `check`, for use as a
+    /// readiness probe.
+    ///
+    /// Responses are the same as [`Router::health_endpoint`]'s, but orchestrators treat them
+    /// differently: a failing liveness probe gets the process restarted, while a failing readiness
+    /// probe only stops traffic from being sent to it. `check` should report
+    /// [`HealthStatus::Unhealthy`] while the service can't serve requests yet, e.g. while caches
+    /// are warming up during startup, without that being mistaken for the process being stuck.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{
+    ///     convert::Infallible,
+    ///     sync::{
+    ///         atomic::{AtomicBool, Ordering},
+    ///         Arc,
+    ///     },
+    /// };
+    ///
+    /// use bytes::Bytes;
+    /// use http::Request;
+    /// use tower::Service;
+    /// use router_service::{HealthStatus, Router};
+    ///
+    /// let ready = Arc::new(AtomicBool::new(false));
+    /// let check = ready.clone();
+    /// let mut router: Router<Bytes, (), Infallible> = Router::new()
+    ///     .health_endpoint("/healthz", || HealthStatus::Healthy)
+    ///     .readiness_endpoint("/readyz", move || match check.load(Ordering::SeqCst) {
+    ///         true => HealthStatus::Healthy,
+    ///         false => HealthStatus::Unhealthy("warming up".into()),
+    ///     });
+    ///
+    /// let req = Request::get("/readyz").body(Bytes::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), 503);
+    ///
+    /// ready.store(true, Ordering::SeqCst);
+    /// let req = Request::get("/readyz").body(Bytes::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), 200);
+    /// # });
+    /// ```
+    pub fn readiness_endpoint<F>(self, path: impl AsRef<str>, check: F) -> Self
+    where
+        ResBody: From<Bytes>,
+        F: Fn() -> HealthStatus + Send + Sync + 'static,
+    {
+        self.status_endpoint(path, check)
+    }
+
+    fn status_endpoint<F>(self, path: impl AsRef<str>, check: F) -> Self
+    where
+        ResBody: From<Bytes>,
+        F: Fn() -> HealthStatus + Send + Sync + 'static,
+    {
+        self.insert_handler(
+            path,
+            Method::GET,
+            AsyncHandler(
+                Arc::new(move |_, _| {
+                    let status = check();
+                    Box::pin(async move { Ok(status.to_response()) })
+                }),
+                type_name::<F>(),
+            ),
+        )
+    }
+
+    /// Registers a `GET` route at `path` that exposes metrics for every route in the Prometheus
+    /// text format.
+    ///
+    /// Once the endpoint is registered, the router counts the requests each route handles by
+    /// method and response status in `router_requests_total`, and tracks the time spent handling
+    /// them in the `router_request_duration_seconds` summary. Routes are identified by their
+    /// templates, e.g. `/users/:id`, so parameters don't create new series. Requests that don't
+    /// match any route aren't recorded. Requires the `metrics` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use bytes::Bytes;
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<Bytes, (), Infallible> = Router::new()
+    ///     .get("/users/:id", |_, _| async move { Ok(Response::new(Bytes::new())) })
+    ///     .metrics_endpoint("/metrics");
+    ///
+    /// router.call(Request::get("/users/42").body(Bytes::new()).unwrap()).await.unwrap();
+    ///
+    /// let req = Request::get("/metrics").body(Bytes::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// let body = String::from_utf8(resp.into_body().to_vec()).unwrap();
+    /// let sample = r#"router_requests_total{route="/users/:id",method="GET",status="200"} 1"#;
+    /// assert!(body.contains(sample));
+    /// # });
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics_endpoint(mut self, path: impl AsRef<str>) -> Self
+    where
+        ResBody: From<Bytes>,
+    {
+        let metrics = self.metrics.get_or_insert_with(Default::default).clone();
+
+        self.insert_handler(
+            path,
+            Method::GET,
+            AsyncHandler(
+                Arc::new(move |_, _| {
+                    let body = Bytes::from(metrics.encode());
+                    Box::pin(async move {
+                        Ok(Response::builder()
+                            .header(http::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+                            .body(ResBody::from(body))
+                            .unwrap())
+                    })
+                }),
+                type_name::<RouteMetrics>(),
+            ),
+        )
+    }
+
+    /// Trusts `X-Forwarded-For` headers added by the reverse proxies with the given addresses, to
+    /// find the address of the client behind them for [`RouteContext::client_ip`].
+    ///
+    /// The address the request was received from is the remote address of its
+    /// [`RequestContext`], set by [`MakeRouter::request_context`]. If it's one of
+    /// `trusted_proxies`, `X-Forwarded-For` is read from the right, skipping trusted proxies, and
+    /// the first other address is the client's. Addresses to the left of it could have been
+    /// forged by the client and are ignored, as are the headers of requests that didn't come
+    /// from a trusted proxy.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{convert::Infallible, net::SocketAddr};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{MakeRouter, Router};
+    ///
+    /// let router = Router::new()
+    ///     .get("/", |_, ctx| async move {
+    ///         Ok::<_, Infallible>(Response::new(ctx.client_ip().unwrap().to_string()))
+    ///     })
+    ///     .with_proxy_headers(vec!["10.0.0.1".parse().unwrap()]);
+    /// let mut make_router = MakeRouter::new(router, |_, _: &SocketAddr| ())
+    ///     .request_context(|ctx, addr| ctx.with_remote_addr(*addr));
+    ///
+    /// let load_balancer = SocketAddr::from(([10, 0, 0, 1], 50000));
+    /// let mut router = make_router.call(&load_balancer).await.unwrap();
+    ///
+    /// let req = Request::get("/")
+    ///     .header("x-forwarded-for", "198.51.100.4, 203.0.113.7")
+    ///     .body(String::new())
+    ///     .unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "203.0.113.7");
+    /// # });
+    /// ```
+    pub fn with_proxy_headers(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = Some(TrustedProxies::new(trusted_proxies));
+        self
+    }
+
+    /// Answers `HEAD` requests to routes without a `HEAD` handler with their `GET` handler,
+    /// with the body of its response dropped but its status and headers kept. Enabled by
+    /// default.
+    ///
+    /// Routes with a handler registered with [`Router::head`] or [`Router::any`] are always
+    /// answered by it.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let router = Router::new().get("/", |_, _| async move {
+    ///     Ok::<_, Infallible>(Response::new("hello".to_string()))
+    /// });
+    ///
+    /// let req = Request::head("/").body(String::new()).unwrap();
+    /// let resp = router.clone().call(req).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::OK);
+    /// assert_eq!(resp.body(), "");
+    ///
+    /// let req = Request::head("/").body(String::new()).unwrap();
+    /// let resp = router.auto_head(false).call(req).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// # });
+    /// ```
+    pub fn auto_head(mut self, enabled: bool) -> Self {
+        self.auto_head = enabled;
+        self
+    }
+
+    /// Serves requests to the hosts in `hosts` with their own routers, picked by the `Host`
+    /// header, or the URI for HTTP/2 requests. Requests to other hosts are served by this router.
+    ///
+    /// Hosts are compared case-insensitively and can include a port, e.g. `localhost:8080`, in
+    /// which case they're preferred over an entry for just the host. The host routers are used as
+    /// they are, without this router's settings, but share its connection's [`RequestContext`]
+    /// if they don't have their own.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{collections::HashMap, convert::Infallible};
+    ///
+    /// use http::{header::HOST, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let api = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new("api".to_string())) });
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new("www".to_string())) })
+    ///     .with_authority_routing(HashMap::from([("api.example.com".to_string(), api)]));
+    ///
+    /// let req = Request::get("/").header(HOST, "api.example.com").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "api");
+    ///
+    /// let req = Request::get("/").header(HOST, "www.example.com").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "www");
+    /// # });
+    /// ```
+    pub fn with_authority_routing(
+        mut self,
+        hosts: HashMap<String, Router<Body, Data, Error, ResBody>>,
+    ) -> Self {
+        self.hosts = Some(Hosts::new(hosts));
+        self
+    }
+
+    /// Treats `/path` and `/path/` as the same route.
+    ///
+    /// Trailing slashes are removed from request paths before they're routed, so handlers see the
+    /// canonical form of the path, without a trailing slash. Routes registered with a trailing
+    /// slash still match requests without one. The root path `/` is left alone.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/users", |req: Request<String>, _| async move {
+    ///         Ok::<_, Infallible>(Response::new(req.uri().to_string()))
+    ///     })
+    ///     .any_trailing_slash(true);
+    ///
+    /// let req = Request::get("/users/?page=2").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "/users?page=2");
+    /// # });
+    /// ```
+    pub fn any_trailing_slash(mut self, enabled: bool) -> Self {
+        self.any_trailing_slash = enabled;
+        self
+    }
+
+    /// Answers requests with `method` that don't declare the media type `mime` in their
+    /// `Content-Type` with `415 Unsupported Media Type`, before they're routed.
+    ///
+    /// Parameters, like `charset`, aren't compared, and requests without a `Content-Type` are
+    /// rejected. Calling this again for the same method allows the other media type as well.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::CONTENT_TYPE, Method, Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .post("/users", |_, _| async move { Ok::<_, Infallible>(Response::new(())) })
+    ///     .require_content_type(Method::POST, mime::APPLICATION_JSON);
+    ///
+    /// let req = Request::post("/users")
+    ///     .header(CONTENT_TYPE, "application/json; charset=utf-8")
+    ///     .body(())
+    ///     .unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+    ///
+    /// let req = Request::post("/users").header(CONTENT_TYPE, "text/plain").body(()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    /// # });
+    /// ```
+    pub fn require_content_type(mut self, method: Method, mime: mime::Mime) -> Self {
+        self.content_types.require(method, mime);
+        self
+    }
+
+    /// Answers requests whose body is over `bytes` bytes with `413 Payload Too Large`.
+    ///
+    /// Requests declaring a longer body in their `Content-Length` are answered before they're
+    /// routed. Other bodies are limited with [`LimitBody`](crate::LimitBody), so a handler reading
+    /// a body past the limit gets a [`BodyTooLarge`](crate::BodyTooLarge) error, and the request
+    /// is answered with `413 Payload Too Large` whatever the handler responds. Bodies that are
+    /// already in memory, like `String` and `Bytes`, are checked before they're routed, and
+    /// `hyper::Body` counts its bytes as they're read, with the `hyper` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::CONTENT_LENGTH, Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new()
+    ///     .post("/upload", |_, _| async move { Ok(Response::new(String::new())) })
+    ///     .with_max_request_size(1024);
+    ///
+    /// let req = Request::post("/upload").body("hello".to_string()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+    ///
+    /// let req = Request::post("/upload").header(CONTENT_LENGTH, "4096").body(String::new());
+    /// let resp = router.call(req.unwrap()).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    ///
+    /// let req = Request::post("/upload").body("a".repeat(4096)).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    /// # });
+    /// ```
+    pub fn with_max_request_size(mut self, bytes: u64) -> Self
+    where
+        Body: LimitBody,
+    {
+        self.max_request_size = Some((bytes, Body::limit));
+        self
+    }
+
+    /// Limits every API key to `requests_per_minute` requests, answering requests over the limit
+    /// with `429 Too Many Requests` before they're routed.
+    ///
+    /// Keys are read from the `X-Api-Key` header, and requests without one are answered with
+    /// `401 Unauthorized`. Each key gets a token bucket holding `requests_per_minute` requests,
+    /// refilled evenly over the minute, so clients can burst up to the limit. Rejected requests
+    /// carry a `Retry-After` header with the seconds until the key can make another request.
+    /// Clones of the router share the buckets, but they're kept in memory, so limits aren't
+    /// shared between processes. At most 65,536 keys are tracked at once, and while that many
+    /// keys have made requests in the last minute, requests with new keys are answered with
+    /// `429 Too Many Requests` too.
+    ///
+    /// # Panics
+    /// Panics if `requests_per_minute` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/reports", |_, _| async move { Ok::<_, Infallible>(Response::new(())) })
+    ///     .rate_limit_by_api_key(1);
+    ///
+    /// let req = || Request::get("/reports").header("x-api-key", "k-123").body(()).unwrap();
+    /// assert_eq!(router.call(req()).await.unwrap().status(), StatusCode::OK);
+    /// assert_eq!(router.call(req()).await.unwrap().status(), StatusCode::TOO_MANY_REQUESTS);
+    ///
+    /// let req = Request::get("/reports").body(()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), StatusCode::UNAUTHORIZED);
+    /// # });
+    /// ```
+    pub fn rate_limit_by_api_key(mut self, requests_per_minute: u32) -> Self {
+        self.api_key_limit = Some(ApiKeyRateLimit::new(requests_per_minute));
+        self
+    }
+
+    /// Gives every request a server-side session, kept in `store` and identified by a cookie
+    /// signed with `key`, that handlers get at through [`RouteContext::session`].
+    ///
+    /// The `session` cookie holds a random session ID and its HMAC-SHA256 signature, so clients
+    /// can't pick or guess the IDs of other sessions. Requests with a cookie that isn't signed
+    /// with `key` get a new session. See [`Session`](crate::Session) for when sessions are
+    /// loaded, saved and created.
+    ///
+    /// # Panics
+    /// Panics if `key` is shorter than 32 bytes.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::SET_COOKIE, Request, Response};
+    /// use tower::Service;
+    /// use router_service::MemorySessionStore;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new()
+    ///     .post("/login", |_, ctx| async move {
+    ///         ctx.session().set("user", "alice").unwrap();
+    ///         Ok(Response::new(String::new()))
+    ///     })
+    ///     .get("/me", |_, ctx| async move {
+    ///         let user: Option<String> = ctx.session().get("user");
+    ///         Ok(Response::new(user.unwrap_or_default()))
+    ///     })
+    ///     .with_session(MemorySessionStore::new(), [7; 32]);
+    ///
+    /// let req = Request::post("/login").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// let cookie = resp.headers()[SET_COOKIE].to_str().unwrap();
+    /// let session = cookie.split(';').next().unwrap();
+    ///
+    /// let req = Request::get("/me").header("cookie", session).body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "alice");
+    /// # });
+    /// ```
+    #[cfg(feature = "sessions")]
+    pub fn with_session(mut self, store: impl SessionStore, key: impl AsRef<[u8]>) -> Self {
+        self.sessions = Some(Sessions::new(store, key.as_ref()));
+        self
+    }
+
+    /// Requires requests with the `POST`, `PUT`, `PATCH` and `DELETE` methods to carry a CSRF
+    /// token signed with `secret`, answering them with `403 Forbidden` otherwise, and answers
+    /// `GET` requests to `/csrf-token` with a token.
+    ///
+    /// The token endpoint is answered before the router looks for a route, so it takes precedence
+    /// over routes matching `/csrf-token`, e.g. a catchall route. It answers with
+    /// `{"token": "..."}` and sets an `HttpOnly` `csrf_token` cookie holding the same token,
+    /// reusing the token of the request's cookie if it has a valid one. Requests changing state
+    /// have to send the token back in an `X-CSRF-Token` header, along with the cookie. Another
+    /// site can make a browser send the cookie, but it can't read the token to set the header.
+    /// Tokens are only read from the header, as the router doesn't read request bodies, so HTML
+    /// forms need a script to submit them. Requires the `csrf` feature.
+    ///
+    /// # Panics
+    /// Panics if `secret` is shorter than 32 bytes.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use bytes::Bytes;
+    /// use http::{header::SET_COOKIE, Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<Bytes, (), Infallible> = Router::new()
+    ///     .post("/transfers", |_, _| async move { Ok(Response::new(Bytes::new())) })
+    ///     .csrf_protection(&[7; 32]);
+    ///
+    /// let req = Request::post("/transfers").body(Bytes::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), StatusCode::FORBIDDEN);
+    ///
+    /// let req = Request::get("/csrf-token").body(Bytes::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// let cookie = resp.headers()[SET_COOKIE].to_str().unwrap().split(';').next().unwrap();
+    /// let token = cookie.strip_prefix("csrf_token=").unwrap();
+    ///
+    /// let req = Request::post("/transfers")
+    ///     .header("cookie", cookie)
+    ///     .header("x-csrf-token", token)
+    ///     .body(Bytes::new())
+    ///     .unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+    /// # });
+    /// ```
+    #[cfg(feature = "csrf")]
+    pub fn csrf_protection(mut self, secret: &[u8]) -> Self
+    where
+        ResBody: From<Bytes>,
+    {
+        self.csrf = Some((Csrf::new(secret), ResBody::from));
+        self
+    }
+
+    /// Passes every request through `transform` before the router looks at it, as a lighter
+    /// alternative to wrapping the router in a [`tower::Layer`], e.g. to normalize URIs or add
+    /// default headers.
+    ///
+    /// Everything the router does with the request, from redirects and CORS to finding its
+    /// route, sees the transformed request. Calling this again replaces the previous transform.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, Uri};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// fn lowercase_path(mut req: Request<String>) -> Request<String> {
+    ///     let path = req.uri().path().to_lowercase();
+    ///     *req.uri_mut() = Uri::try_from(path).unwrap();
+    ///     req
+    /// }
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/users", |_, _| async move {
+    ///         Ok::<_, Infallible>(Response::new("users".to_string()))
+    ///     })
+    ///     .with_request_transform(lowercase_path);
+    ///
+    /// let req = Request::get("/Users").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "users");
+    /// # });
+    /// ```
+    pub fn with_request_transform(mut self, transform: fn(Request<Body>) -> Request<Body>) -> Self {
+        self.request_transform = Some(transform);
+        self
+    }
+
+    /// Passes every response the router gives, including the ones it answers on its own like
+    /// `404 Not Found`, through `transform`, as a lighter alternative to wrapping the router in
+    /// a [`tower::Layer`].
+    ///
+    /// The transform runs before the router-wide response headers and `Content-Length` are
+    /// added, and replaces any transform set before.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::CACHE_CONTROL, HeaderValue, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// fn no_store(mut resp: Response<()>) -> Response<()> {
+    ///     resp.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    ///     resp
+    /// }
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new(())) })
+    ///     .with_response_transform(no_store);
+    ///
+    /// for path in ["/", "/missing"] {
+    ///     let req = Request::get(path).body(()).unwrap();
+    ///     let resp = router.call(req).await.unwrap();
+    ///     assert_eq!(resp.headers()[CACHE_CONTROL], "no-store");
+    /// }
+    /// # });
+    /// ```
+    pub fn with_response_transform(
+        mut self,
+        transform: fn(Response<ResBody>) -> Response<ResBody>,
+    ) -> Self {
+        self.response_transform = Some(transform);
+        self
+    }
+
+    /// Sets `Content-Length` on responses that don't have it, from the size of their body.
+    ///
+    /// Without `Content-Length`, clients have to fall back to chunked transfer encoding or read
+    /// until the connection closes. Responses that already have `Content-Length` or
+    /// `Transfer-Encoding`, can't have a body (`1xx`, `204 No Content` and `304 Not Modified`),
+    /// or answer `HEAD` requests are left alone. Only routers whose body type exposes its bytes,
+    /// such as `Bytes`, `String`, or `Vec<u8>`, know the size of their bodies.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use bytes::Bytes;
+    /// use http::{header::CONTENT_LENGTH, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move {
+    ///         Ok::<_, Infallible>(Response::new(Bytes::from_static(b"hello")))
+    ///     })
+    ///     .auto_content_length(true);
+    ///
+    /// let resp = router.call(Request::get("/").body(Bytes::new()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.headers()[CONTENT_LENGTH], "5");
+    /// # });
+    /// ```
+    pub fn auto_content_length(mut self, enabled: bool) -> Self
+    where
+        ResBody: AsRef<[u8]>,
+    {
+        self.content_length = enabled.then_some(set_content_length as SetContentLength<ResBody>);
+        self
+    }
+
+    /// Answers requests whose handler fails with `500 Internal Server Error` and the error
+    /// serialized as JSON, instead of failing the service call with it.
+    ///
+    /// This requires the `json` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use http::{Request, Response};
+    /// use serde::Serialize;
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// #[derive(Serialize)]
+    /// struct ApiError {
+    ///     code: &'static str,
+    /// }
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move {
+    ///         Err::<Response<String>, _>(ApiError { code: "database_unavailable" })
+    ///     })
+    ///     .auto_json_error(true);
+    ///
+    /// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await;
+    /// let resp = resp.ok().unwrap();
+    /// assert_eq!(resp.status(), 500);
+    /// assert_eq!(resp.body(), r#"{"code":"database_unavailable"}"#);
+    /// # });
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn auto_json_error(mut self, enabled: bool) -> Self
+    where
+        ResBody: Default + From<String>,
+        Error: serde::Serialize,
+    {
+        self.error_response = enabled.then_some(json_error as ErrorResponse<ResBody, Error>);
+        self
+    }
+
+    /// Answers requests whose handler panics with the response `panic_handler` makes from the
+    /// panic's payload, instead of unwinding through the service call.
+    ///
+    /// Panics are caught both when the handler is called and while its future runs, without
+    /// requiring the handler to be [`UnwindSafe`](std::panic::UnwindSafe). The panic is still
+    /// reported by the panic hook, which prints it to stderr by default, and with the `tracing`
+    /// feature it's logged as an error too. State the handler shared with other requests may be
+    /// left inconsistent by the panic, e.g. a poisoned `Mutex`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move { panic!("database exploded") })
+    ///     .with_panic_handler(|panic| {
+    ///         let message = panic.downcast_ref::<&str>().copied().unwrap_or("unknown");
+    ///         Response::builder()
+    ///             .status(StatusCode::INTERNAL_SERVER_ERROR)
+    ///             .body(format!("panicked: {message}"))
+    ///             .unwrap()
+    ///     });
+    ///
+    /// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    /// assert_eq!(resp.body(), "panicked: database exploded");
+    /// # });
+    /// ```
+    pub fn with_panic_handler(
+        mut self,
+        panic_handler: fn(Box<dyn Any + Send>) -> Response<ResBody>,
+    ) -> Self {
+        self.panic_handler = Some(panic_handler);
+        self
+    }
+
+    /// Logs every request the router answers in `format` once it completes.
+    ///
+    /// Each request produces one `INFO` event with the `router_service::access` target, carrying
+    /// the method, path and query, HTTP version, response status, `Content-Length`, time taken,
+    /// `Referer`, and `User-Agent`. Requests whose handler fails are logged without a status.
+    /// Install a `tracing` subscriber to write the lines somewhere. Requires the `tracing` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{LogFormat, Router};
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new(String::new())) })
+    ///     .with_logging_format(LogFormat::Combined);
+    ///
+    /// // Logs `- - - [10/Oct/2023:13:55:36 +0000] "GET / HTTP/1.1" 200 - "-" "-"`.
+    /// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.status(), 200);
+    /// # });
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn with_logging_format(mut self, format: LogFormat) -> Self {
+        self.log_format = Some(format);
+        self
+    }
+
+    /// Reports errors returned by handlers to Sentry.
+    ///
+    /// Every request is handled in a Sentry hub of its own, forked from the current one, so
+    /// breadcrumbs and scope changes made while handling it don't leak into other requests. When
+    /// a request matches a route, a breadcrumb with the method and route template is added and
+    /// the scope's transaction is named after the template. Errors returned by the handler are
+    /// captured as events on the request's hub. Requires the `sentry` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::io;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/users/:id", |_, _| async move {
+    ///         Err::<Response<()>, _>(io::Error::other("database unavailable"))
+    ///     })
+    ///     .with_sentry_integration();
+    ///
+    /// // Reported with the `/users/:id` transaction once the application has set up Sentry,
+    /// // e.g. with `sentry::init`.
+    /// let req = Request::get("/users/42").body(()).unwrap();
+    /// assert!(router.call(req).await.is_err());
+    /// # });
+    /// ```
+    #[cfg(feature = "sentry")]
+    pub fn with_sentry_integration(mut self) -> Self
+    where
+        Error: std::error::Error,
+    {
+        self.sentry = Some(|hub, err| {
+            hub.capture_error(err);
+        });
+        self
+    }
+
+    /// Traces requests that match a route with Datadog APM, as part of the service with the given
+    /// name.
+    ///
+    /// Every request that matches a route gets a [`DatadogTrace`] in its extensions, continuing
+    /// the trace from the request's `X-Datadog-Trace-Id` and `X-Datadog-Parent-Id` headers or
+    /// starting a new one. Its resource name is the request's method and the route's template,
+    /// e.g. `GET /users/:id`, so requests to the same route are grouped. The handler runs in a
+    /// `tracing` span carrying the trace's `dd.trace_id`, `dd.span_id`, `service.name`, and
+    /// `resource.name`, for a subscriber that exports spans to the Datadog agent. Handlers pass
+    /// the trace on to other services with [`DatadogTrace::inject`]. Requires the `datadog`
+    /// feature.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{DatadogTrace, Router};
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/users/:id", |req: Request<String>, _| async move {
+    ///         let trace = req.extensions().get::<DatadogTrace>().unwrap();
+    ///         Ok::<_, Infallible>(Response::new(trace.resource().to_string()))
+    ///     })
+    ///     .with_datadog_apm("users-api");
+    ///
+    /// let req = Request::get("/users/42")
+    ///     .header("x-datadog-trace-id", "5904164351390152736")
+    ///     .header("x-datadog-parent-id", "2034521329318203943")
+    ///     .body(String::new())
+    ///     .unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.body(), "GET /users/:id");
+    /// # });
+    /// ```
+    #[cfg(feature = "datadog")]
+    pub fn with_datadog_apm(mut self, service_name: &str) -> Self {
+        self.datadog = Some(service_name.into());
+        self
+    }
+
+    /// Answers requests whose path doesn't match any route with `handler` instead of responding
+    /// with `404 Not Found`, e.g. to render a custom error page or serve the index of a
+    /// single-page app.
+    ///
+    /// The handler gets a [`RouteContext`] without any parameters, but with the prefix set with
+    /// [`Router::with_base_path`], and its response is returned as it is. This replaces the
+    /// service set with [`Router::not_found_passthrough`], and vice versa.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new(String::new())) })
+    ///     .fallback(|req: Request<String>, _| async move {
+    ///         let resp = Response::builder()
+    ///             .status(StatusCode::NOT_FOUND)
+    ///             .body(format!("nothing at {}", req.uri().path()))
+    ///             .unwrap();
+    ///         Ok(resp)
+    ///     });
+    ///
+    /// let req = Request::get("/missing").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "nothing at /missing");
+    /// # });
+    /// ```
+    pub fn fallback<HandlerFn, Fut>(mut self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.fallback = Some(handler.into());
+        self
+    }
+
+    /// Answers requests whose path matches a route, but whose method it has no handler for, with
+    /// `handler` instead of an empty `405 Method Not Allowed`, e.g. to return a JSON error body.
+    ///
+    /// The handler gets the methods the route does allow from
+    /// [`RouteContext::allowed_methods`], and its response gets an `Allow` header listing them
+    /// unless it sets one itself. `OPTIONS` requests are still answered by the router.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/users", |_, _| async move { Ok::<_, Infallible>(Response::new(String::new())) })
+    ///     .method_not_allowed(|req: Request<String>, ctx| async move {
+    ///         let allowed: Vec<_> = ctx.allowed_methods().iter().map(|m| m.as_str()).collect();
+    ///         let resp = Response::builder()
+    ///             .status(StatusCode::METHOD_NOT_ALLOWED)
+    ///             .body(format!("{} isn't one of {}", req.method(), allowed.join(", ")))
+    ///             .unwrap();
+    ///         Ok(resp)
+    ///     });
+    ///
+    /// let req = Request::delete("/users").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 405);
+    /// assert_eq!(resp.headers()["allow"], "GET, HEAD, OPTIONS");
+    /// assert_eq!(resp.body(), "DELETE isn't one of GET, HEAD, OPTIONS");
+    /// # });
+    /// ```
+    pub fn method_not_allowed<HandlerFn, Fut>(mut self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method_not_allowed = Some(handler.into());
+        self
+    }
+
+    /// Delegates requests that don't match any route to `inner` instead of responding with
+    /// `404 Not Found`.
+    ///
+    /// This lets the router sit in front of an existing service and only intercept the routes it
+    /// knows about. Requests to the path of a route with a method it has no handler for are still
+    /// answered with `405 Method Not Allowed`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let legacy: Router<(), (), Infallible> = Router::new()
+    ///     .get("/legacy", |_, _| async move { Ok(Response::new(())) });
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new(())) })
+    ///     .not_found_passthrough(legacy);
+    ///
+    /// let req = Request::get("/legacy").body(()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 200);
+    /// # });
+    /// ```
+    pub fn not_found_passthrough<S>(mut self, inner: S) -> Self
+    where
+        Body: Send,
+        S: Service<Request<Body>, Response = Response<ResBody>, Error = Error>,
+        S: Clone + Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        self.fallback = Some(AsyncHandler(
+            Arc::new(move |req, _| {
+                let mut inner = inner.clone();
+                Box::pin(async move {
+                    poll_fn(|cx| inner.poll_ready(cx)).await?;
+                    inner.call(req).await
+                })
+            }),
+            type_name::<S>(),
+        ));
+        self
+    }
+
+    /// Redirects requests made over plain HTTP to HTTPS before any route matching occurs.
+    ///
+    /// The protocol is taken from the `X-Forwarded-Proto` or `Forwarded` header set by a reverse
+    /// proxy terminating TLS, falling back to the request URI's scheme. Plain HTTP requests are
+    /// answered with `301 Moved Permanently` pointing at the same host, path, and query over
+    /// HTTPS, or with `400 Bad Request` if they don't specify a host.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::LOCATION, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new(())) })
+    ///     .require_https(true);
+    ///
+    /// let req = Request::get("/?page=2")
+    ///     .header("host", "example.com")
+    ///     .header("x-forwarded-proto", "http")
+    ///     .body(())
+    ///     .unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 301);
+    /// assert_eq!(resp.headers()[LOCATION], "https://example.com/?page=2");
+    /// # });
+    /// ```
+    pub fn require_https(mut self, enabled: bool) -> Self {
+        self.require_https = enabled;
+        self
+    }
+
+    /// Adds a `Content-Security-Policy` header with `policy` to every `text/html` response.
+    ///
+    /// Responses with other content types are left untouched; use
+    /// [`Router::with_content_security_policy_for_all`] to send the policy on every response.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` isn't a valid header value.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE}, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move {
+    ///         Ok(Response::builder().header(CONTENT_TYPE, "text/html").body(()).unwrap())
+    ///     })
+    ///     .with_content_security_policy("default-src 'self'");
+    ///
+    /// let resp = router.call(Request::get("/").body(()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.headers()[CONTENT_SECURITY_POLICY], "default-src 'self'");
+    /// # });
+    /// ```
+    pub fn with_content_security_policy(self, policy: &str) -> Self {
+        self.content_security_policy(policy, Scope::Html)
+    }
+
+    /// Adds a `Content-Security-Policy` header with `policy` to every response, regardless of its
+    /// content type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` isn't a valid header value.
+    pub fn with_content_security_policy_for_all(self, policy: &str) -> Self {
+        self.content_security_policy(policy, Scope::All)
+    }
+
+    fn content_security_policy(mut self, policy: &str, scope: Scope) -> Self {
+        let policy = HeaderValue::from_str(policy).expect("invalid content security policy");
+        self.response_headers
+            .insert(CONTENT_SECURITY_POLICY, policy, scope);
+        self
+    }
+
+    /// Allows cross-origin requests from `origins`, e.g. `https://example.com`.
+    ///
+    /// Responses to requests from an allowed origin get an `Access-Control-Allow-Origin` header,
+    /// and CORS preflight requests from those origins are answered with `204 No Content` before
+    /// reaching any route. Requests from other origins are routed as usual, without CORS headers,
+    /// so browsers will block them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any origin isn't a valid header value.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::{ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN}, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new(())) })
+    ///     .cors_allow_origin(&["https://example.com"]);
+    ///
+    /// let req = Request::get("/")
+    ///     .header(ORIGIN, "https://example.com")
+    ///     .body(())
+    ///     .unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "https://example.com");
+    /// # });
+    /// ```
+    pub fn cors_allow_origin(mut self, origins: &[&str]) -> Self {
+        self.cors = Some(Arc::new(CorsConfig::origins(origins)));
+        self
+    }
+
+    /// Allows cross-origin requests from any origin.
+    ///
+    /// This is convenient during development, but production deployments should usually list
+    /// their origins with [`Router::cors_allow_origin`] instead.
+    pub fn cors_allow_all(mut self) -> Self {
+        self.cors = Some(Arc::new(CorsConfig::Any));
+        self
+    }
+
+    /// Adds a `Strict-Transport-Security` header with the given `max-age` to every response.
+    ///
+    /// This is shorthand for [`Router::with_hsts`] without `includeSubDomains` or `preload`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::STRICT_TRANSPORT_SECURITY, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new(())) })
+    ///     .with_hsts_max_age(31536000);
+    ///
+    /// let resp = router.call(Request::get("/").body(()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.headers()[STRICT_TRANSPORT_SECURITY], "max-age=31536000");
+    /// # });
+    /// ```
+    pub fn with_hsts_max_age(self, seconds: u64) -> Self {
+        self.with_hsts(false, false, seconds)
+    }
+
+    /// Adds a `Strict-Transport-Security` header to every response.
+    ///
+    /// Browsers only honor the header on responses served over HTTPS, so this is usually combined
+    /// with [`Router::require_https`].
+    pub fn with_hsts(mut self, include_subdomains: bool, preload: bool, max_age: u64) -> Self {
+        let mut value = format!("max-age={max_age}");
+        if include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if preload {
+            value.push_str("; preload");
+        }
+
+        self.response_headers.insert(
+            STRICT_TRANSPORT_SECURITY,
+            HeaderValue::try_from(value).unwrap(),
+            Scope::All,
+        );
+        self
+    }
+
+    /// Sets the `Server` header on every response to `value`, replacing any value set by a
+    /// handler.
+    ///
+    /// Passing an empty string removes the header from every response instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` isn't a valid header value.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::SERVER, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/", |_, _| async move { Ok(Response::new(())) })
+    ///     .with_server_header("my-app");
+    ///
+    /// let resp = router.call(Request::get("/").body(()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.headers()[SERVER], "my-app");
+    /// # });
+    /// ```
+    pub fn with_server_header(mut self, value: &str) -> Self {
+        if value.is_empty() {
+            self.response_headers.remove(SERVER);
+        } else {
+            let value = HeaderValue::from_str(value).expect("invalid server header");
+            self.response_headers.insert(SERVER, value, Scope::All);
+        }
+        self
+    }
+
+    /// Sets the path prefix the router is mounted under, e.g. `/api`.
+    ///
+    /// The router still matches requests against its own routes, but handlers can read the prefix
+    /// through [`RouteContext::base_path`] to report full paths. This includes the handler set
+    /// with [`Router::fallback`], so the `404` bodies it generates can name the path the client
+    /// requested.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new()
+    ///     .get("/users", |_, _| async move { Ok(Response::new(String::new())) })
+    ///     .fallback(|req, ctx| async move {
+    ///         let body = format!("path `{}{}` not found", ctx.base_path(), req.uri().path());
+    ///         Ok(Response::builder().status(StatusCode::NOT_FOUND).body(body).unwrap())
+    ///     })
+    ///     .with_base_path("/api");
+    ///
+    /// let req = Request::get("/nonexistent").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.body(), "path `/api/nonexistent` not found");
+    /// # });
+    /// ```
+    pub fn with_base_path(mut self, prefix: impl AsRef<str>) -> Self {
+        let prefix = prefix.as_ref().trim_end_matches('/');
+        self.base_path = (!prefix.is_empty()).then(|| prefix.into());
+        self
+    }
+
+    /// Tracks in-flight requests so the router can be drained during a graceful shutdown.
+    ///
+    /// Returns the router along with a [`DrainSignal`], which stops the router from accepting new
+    /// requests, and a [`DrainWatcher`], which resolves once every in-flight request's response
+    /// future has completed or been dropped. Once draining, new requests are answered with
+    /// `503 Service Unavailable`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tokio::runtime::Builder;
+    /// # let rt  = Builder::new_multi_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// use std::convert::Infallible;
+    ///
+    /// use hyper::{Body, Response, Server};
+    /// use tower::make::Shared;
+    /// use router_service::Router;
+    ///
+    /// let (router, signal, watcher) = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new(Body::empty())) })
+    ///     .with_graceful_drain();
+    ///
+    /// let addr = ([127, 0, 0, 1], 3030).into();
+    /// let server = Server::bind(&addr).serve(Shared::new(router));
+    /// let shutdown = async move {
+    ///     tokio::signal::ctrl_c().await.unwrap();
+    ///     signal.drain();
+    ///     watcher.await;
+    /// };
+    ///
+    /// server.with_graceful_shutdown(shutdown).await.unwrap();
+    /// # });
+    /// ```
+    pub fn with_graceful_drain(mut self) -> (Self, DrainSignal, DrainWatcher) {
+        let state = Arc::new(DrainState::default());
+        self.drain = Some(state.clone());
+
+        (self, DrainSignal(state.clone()), DrainWatcher(state))
+    }
+
+    /// Tracks in-flight requests so the router can be shut down with a deadline.
+    ///
+    /// This behaves like [`Router::with_graceful_drain`], except that the returned
+    /// [`GracefulShutdown`] gives in-flight requests at most `timeout` to complete once shutdown
+    /// starts. Handlers that are still running after that are dropped and their requests are
+    /// answered with `503 Service Unavailable`. Requires the `tokio` feature.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tokio::runtime::Builder;
+    /// # let rt  = Builder::new_multi_thread().enable_all().build().unwrap();
+    /// # rt.block_on(async {
+    /// use std::{convert::Infallible, time::Duration};
+    ///
+    /// use hyper::{Body, Response, Server};
+    /// use tower::make::Shared;
+    /// use router_service::Router;
+    ///
+    /// let (router, shutdown) = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new(Body::empty())) })
+    ///     .graceful_timeout(Duration::from_secs(30));
+    ///
+    /// let addr = ([127, 0, 0, 1], 3030).into();
+    /// Server::bind(&addr)
+    ///     .serve(Shared::new(router))
+    ///     .with_graceful_shutdown(async move {
+    ///         tokio::signal::ctrl_c().await.unwrap();
+    ///         shutdown.shutdown().await;
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    /// # });
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn graceful_timeout(self, timeout: Duration) -> (Self, GracefulShutdown) {
+        let (router, signal, watcher) = self.with_graceful_drain();
+
+        (
+            router,
+            GracefulShutdown {
+                signal,
+                watcher,
+                timeout,
+            },
+        )
+    }
+
+    /// Limits the number of path parameters that may be extracted for a single request.
+    ///
+    /// Requests matching a route with more than `max` parameters are answered with
+    /// `400 Bad Request` without invoking the handler.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<(), (), Infallible> = Router::new()
+    ///     .get("/:a/:b", |_, _| async move { Ok(Response::new(())) })
+    ///     .with_max_params(1);
+    ///
+    /// let req = Request::get("/1/2").body(()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.status(), 400);
+    /// # });
+    /// ```
+    pub fn with_max_params(mut self, max: usize) -> Self {
+        self.max_params = Some(max);
+        self
+    }
+
+    /// Registers every route of `other` under the path `prefix`, e.g. to compose an application
+    /// from routers built separately.
+    ///
+    /// Parameters in `prefix`, like `:tenant` in `/tenants/:tenant`, are available to the nested
+    /// routes' handlers along with their own. Only the routes are taken from `other`; its handlers
+    /// are called with this router's data, and its other settings, like its fallback, are
+    /// ignored. Routes named in `other` keep their names, see [`Router::url_for`].
+    ///
+    /// # Panics
+    /// Panics if a route of `other` has a handler for a method this router already handles at the
+    /// same path, if one of its routes conflicts with a route of this router, if `other` names a
+    /// route with a name this router uses for another route, or if `other` shares this router's
+    /// routes, e.g. because it's a clone of it.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let api = Router::new().get("/users/:id", |_, ctx| async move {
+    ///     let (id, tenant) = (ctx.param("id").unwrap(), ctx.param("tenant").unwrap());
+    ///     Ok::<_, Infallible>(Response::new(format!("user {id} of {tenant}")))
+    /// });
+    /// let mut router = Router::new().nest("/tenants/:tenant/api", api);
+    ///
+    /// let req = Request::get("/tenants/acme/api/users/42").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "user 42 of acme");
+    /// # });
+    /// ```
+    pub fn nest(self, prefix: impl AsRef<str>, other: Router<Body, Data, Error, ResBody>) -> Self {
+        assert!(
+            !Arc::ptr_eq(&self.inner, &other.inner),
+            "unable to nest a router in a router sharing its routes"
+        );
+
+        let other = other.inner.read().unwrap();
+        self.inner.write().unwrap().nest(prefix.as_ref(), &other);
+
+        self
+    }
+
+    /// Registers the routes `routes` adds to an empty router under the path `prefix`, like
+    /// [`Router::nest`] without naming the router to nest.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as [`Router::nest`].
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new().scope("/admin", |admin| {
+    ///     admin
+    ///         .get("/users", |_, _| async move { Ok(Response::new("users".to_string())) })
+    ///         .delete("/users/:id", |_, ctx| async move {
+    ///             Ok(Response::new(format!("deleted {}", ctx.param("id").unwrap())))
+    ///         })
+    /// });
+    ///
+    /// let req = Request::delete("/admin/users/42").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "deleted 42");
+    /// # });
+    /// ```
+    pub fn scope(self, prefix: impl AsRef<str>, routes: impl FnOnce(Self) -> Self) -> Self {
+        let scoped = routes(Self::with_shared_data(self.data.clone()));
+        self.nest(prefix, scoped)
+    }
+
+    /// Registers every route of `other` in this router, e.g. to combine routers built in separate
+    /// modules.
+    ///
+    /// Routes with the same path are combined, so `other` can add methods to a path this router
+    /// already has a route for. Like with [`Router::nest`], only the routes are taken from
+    /// `other`.
+    ///
+    /// # Panics
+    /// Panics if `other` has a handler for a method this router already handles at the same
+    /// path, if one of its routes conflicts with a route of this router, or if `other` shares
+    /// this router's routes. [`Router::try_merge`] reports the first two instead.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let users = Router::new().get("/users", |_, _| async move {
+    ///     Ok::<_, Infallible>(Response::new("list".to_string()))
+    /// });
+    /// let admin = Router::new()
+    ///     .post("/users", |_, _| async move { Ok(Response::new("created".to_string())) });
+    /// let mut router = users.merge(admin);
+    ///
+    /// let req = Request::post("/users").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "created");
+    /// # });
+    /// ```
+    pub fn merge(self, other: Router<Body, Data, Error, ResBody>) -> Self {
+        self.nest("", other)
+    }
+
+    /// Registers every route of `other` in this router like [`Router::merge`], unless a route of
+    /// `other` has a handler for a method this router already handles at the same path or
+    /// conflicts with a route of this router.
+    ///
+    /// On failure the templates of those routes are returned and no route is registered.
+    ///
+    /// # Panics
+    /// Panics if `other` shares this router's routes, e.g. because it's a clone of it.
+    pub fn try_merge(self, other: Router<Body, Data, Error, ResBody>) -> Result<Self, Vec<String>> {
+        assert!(
+            !Arc::ptr_eq(&self.inner, &other.inner),
+            "unable to merge a router into a router sharing its routes"
+        );
+
+        let other = other.inner.read().unwrap();
+        let mut inner = self.inner.write().unwrap();
+        let conflicts = inner.nest_conflicts("", &other);
+        if !conflicts.is_empty() {
+            return Err(conflicts);
+        }
+        inner.nest("", &other);
+        drop(inner);
+
+        Ok(self)
+    }
+
+    /// Lists the routes of the router, e.g. to log them at startup or to document an API.
+    ///
+    /// Every path template is listed once, in the order it was first registered in, along with
+    /// the methods it has handlers for in alphabetical order. Routes with a handler for every
+    /// method, registered with [`Router::any`], list `None` after their methods, like a
+    /// [`RouteChangeEvent`](crate::RouteChangeEvent) does. Routes of nested routers are listed
+    /// with their full template.
+    ///
+    /// # Example
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Method, Response};
+    /// use router_service::Router;
+    ///
+    /// let router: Router<(), (), Infallible> = Router::new()
+    ///     .post("/users", |_, _| async move { Ok(Response::new(())) })
+    ///     .get("/users", |_, _| async move { Ok(Response::new(())) })
+    ///     .any("/static/*path", |_, _| async move { Ok(Response::new(())) });
+    ///
+    /// assert_eq!(
+    ///     router.routes(),
+    ///     vec![
+    ///         ("/users".to_string(), vec![Some(Method::GET), Some(Method::POST)]),
+    ///         ("/static/*path".to_string(), vec![None]),
+    ///     ]
+    /// );
+    /// ```
+    pub fn routes(&self) -> Vec<(String, Vec<Option<Method>>)> {
+        self.inner.read().unwrap().routes()
+    }
+
+    /// Checks the router for registrations that can never be reached.
+    ///
+    /// A registration is unreachable when its path was folded into a previously registered route
+    /// with a different template (e.g. registering `/foo` after the catchall `/*rest`), or when a
+    /// later registration for the same path and method replaced it. On failure the templates of
+    /// the unreachable registrations are returned in registration order.
+    ///
+    /// # Example
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use http::Response;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<(), (), Infallible> = Router::new()
+    ///     .any("/*rest", |_, _| async move { Ok(Response::new(())) })
+    ///     .get("/foo", |_, _| async move { Ok(Response::new(())) });
+    ///
+    /// assert_eq!(router.validate(), Err(vec!["/foo".to_string()]));
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let inner = self.inner.read().unwrap();
+        let shadowed = inner.shadowed();
+
+        if shadowed.is_empty() {
+            Ok(())
+        } else {
+            Err(shadowed.to_vec())
+        }
+    }
+
+    /// Checks the router with [`Router::validate`] and, if every registration can be reached,
+    /// returns a [`FrozenRouter`] that serves its routes but can't register any more.
+    ///
+    /// This marks the point where all routes are registered and the router is ready to serve.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new()
+    ///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new("home".to_string())) })
+    ///     .build_and_check()
+    ///     .unwrap();
+    ///
+    /// let req = Request::get("/").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "home");
+    /// # });
+    /// ```
+    pub fn build_and_check(self) -> Result<FrozenRouter<Body, Data, Error, ResBody>, Vec<String>> {
+        self.validate()?;
+        Ok(FrozenRouter::new(self))
+    }
+
+    /// Describes the handler that serves `method` requests to `path`, if any.
+    ///
+    /// The description is the type name of the handler function, which makes it possible to tell
+    /// which of several registrations for the same path and method is the one in effect. `path`
+    /// can be a registered template or a concrete path. Handlers registered with [`Router::any`]
+    /// are reported for every method without a more specific handler. The format of the
+    /// description isn't stable and should only be used for debugging.
+    ///
+    /// # Example
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Method, Request, Response};
+    /// use router_service::{RouteContext, Router};
+    ///
+    /// async fn list_users(
+    ///     _: Request<()>,
+    ///     _: RouteContext<()>,
+    /// ) -> Result<Response<()>, Infallible> {
+    ///     Ok(Response::new(()))
+    /// }
+    ///
+    /// let router = Router::new().get("/users", list_users);
+    ///
+    /// let description = router.debug_handler("/users", Method::GET).unwrap();
+    /// assert!(description.ends_with("list_users"));
+    /// assert_eq!(router.debug_handler("/users", Method::POST), None);
+    /// ```
+    pub fn debug_handler(&self, path: impl AsRef<str>, method: Method) -> Option<String> {
+        let inner = self.inner.read().unwrap();
+        let route = inner.at(path.as_ref())?.value;
+
+        route
+            .handlers
+            .get(&method)
+            .or(route.catchall.as_ref())
+            .map(|handler| handler.1.to_owned())
+    }
+
+    /// Returns a watcher that's notified whenever a route is added to or removed from the router.
+    ///
+    /// The watcher observes the route table shared by the router and all of its clones, so
+    /// routes registered on any of them are reported. Requires the `tokio` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{Method, Response};
+    /// use router_service::{RouteChangeEvent, Router};
+    ///
+    /// let router: Router<(), (), Infallible> = Router::new();
+    /// let mut watcher = router.watcher();
+    ///
+    /// let router = router.get("/", |_, _| async move { Ok(Response::new(())) });
+    ///
+    /// let changes = watcher.changed().await.unwrap();
+    /// assert_eq!(changes, vec![RouteChangeEvent::Added("/".into(), Some(Method::GET))]);
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn watcher(&self) -> RouterWatcher {
+        self.inner.write().unwrap().watch()
+    }
+
+    /// Adapts the router into an actix-web service, so it can serve part of an actix-web `App`.
+    ///
+    /// This lets an actix-web application adopt the router incrementally, e.g. by mounting it as
+    /// the app's default service so it handles every request actix-web's own routes don't. Request
+    /// bodies are read into memory before the router is called. Requires the `actix-web` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # actix_web::rt::System::new().block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use actix_web::{test, web, App, HttpResponse};
+    /// use bytes::Bytes;
+    /// use http::Response;
+    /// use router_service::Router;
+    ///
+    /// let router: Router<Bytes, (), Infallible> = Router::new()
+    ///     .get("/users", |_, _| async move { Ok(Response::new(Bytes::from("router"))) });
+    ///
+    /// let app = App::new()
+    ///     .route("/health", web::get().to(HttpResponse::Ok))
+    ///     .default_service(router.into_actix_service());
+    /// let app = test::init_service(app).await;
+    ///
+    /// let req = test::TestRequest::get().uri("/users").to_request();
+    /// assert_eq!(test::call_and_read_body(&app, req).await, "router");
+    /// # });
+    /// ```
+    #[cfg(feature = "actix-web")]
+    pub fn into_actix_service(self) -> ActixService<Self, Body> {
+        ActixService::new(self)
+    }
+
+    /// Creates a router without routes whose handlers get `data`.
+    fn with_shared_data(data: Arc<Data>) -> Self {
+        Self {
+            inner: Default::default(),
+            data,
+            not_found: None,
+            max_params: None,
+            fallback: None,
+            method_not_allowed: None,
+            hosts: None,
+            require_https: false,
+            any_trailing_slash: false,
+            auto_head: true,
+            base_path: None,
+            drain: None,
+            response_headers: ResponseHeaders::default(),
+            cors: None,
+            content_types: RequiredContentTypes::default(),
+            content_length: None,
+            request_context: None,
+            trusted_proxies: None,
+            max_request_size: None,
+            api_key_limit: None,
+            request_transform: None,
+            response_transform: None,
+            error_response: None,
+            panic_handler: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "tracing")]
+            log_format: None,
+            #[cfg(feature = "sentry")]
+            sentry: None,
+            #[cfg(feature = "datadog")]
+            datadog: None,
+            #[cfg(feature = "tokio")]
+            shadows: Default::default(),
+            #[cfg(feature = "sessions")]
+            sessions: None,
+            #[cfg(feature = "csrf")]
+            csrf: None,
+        }
+    }
+
+    fn insert_handler<H>(self, path: impl AsRef<str>, method: Method, handler: H) -> Self
+    where
+        H: Into<AsyncHandler<Body, Data, Error, ResBody>>,
+    {
+        self.inner
+            .write()
+            .unwrap()
+            .insert_handler(path.as_ref(), method, handler.into());
+
+        self
+    }
+}
+
+impl<Body, Data, Error, ResBody> Clone for Router<Body, Data, Error, ResBody> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            data: self.data.clone(),
+            not_found: self.not_found.clone(),
+            max_params: self.max_params,
+            fallback: self.fallback.clone(),
+            method_not_allowed: self.method_not_allowed.clone(),
+            hosts: self.hosts.clone(),
+            require_https: self.require_https,
+            any_trailing_slash: self.any_trailing_slash,
+            auto_head: self.auto_head,
+            base_path: self.base_path.clone(),
+            drain: self.drain.clone(),
+            response_headers: self.response_headers.clone(),
+            cors: self.cors.clone(),
+            content_types: self.content_types.clone(),
+            content_length: self.content_length,
+            request_context: self.request_context.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+            max_request_size: self.max_request_size,
+            api_key_limit: self.api_key_limit.clone(),
+            request_transform: self.request_transform,
+            response_transform: self.response_transform,
+            error_response: self.error_response,
+            panic_handler: self.panic_handler,
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics.clone(),
+            #[cfg(feature = "tracing")]
+            log_format: self.log_format,
+            #[cfg(feature = "sentry")]
+            sentry: self.sentry,
+            #[cfg(feature = "datadog")]
+            datadog: self.datadog.clone(),
+            #[cfg(feature = "tokio")]
+            shadows: self.shadows.clone(),
+            #[cfg(feature = "sessions")]
+            sessions: self.sessions.clone(),
+            #[cfg(feature = "csrf")]
+            csrf: self.csrf.clone(),
+        }
+    }
+}
+
+/// The context of a matched route.
+///
+/// Typed request headers, such as `headers::Authorization<Bearer>`, can be decoded from the
+/// request with `headers::HeaderMapExt::typed_get`.
+#[derive(Debug)]
+pub struct RouteContext<T> {
+    /// Arbitrary data associated with the router that is available to all handlers.
+    pub data: T,
+    params: HashMap<String, String>,
+    base_path: Option<Arc<str>>,
+    template: Option<Arc<str>>,
+    request_context: Option<Arc<RequestContext>>,
+    client_ip: Option<IpAddr>,
+    path_and_query: Option<PathAndQuery>,
+    query: Vec<(String, String)>,
+    response_headers: ContextHeaders,
+    allowed_methods: Vec<Method>,
+    #[cfg(feature = "sessions")]
+    session: Option<Session>,
+}
+
+impl<T> RouteContext<T> {
+    /// Returns a parameter value from the path by name.
+    pub fn param(&self, name: impl AsRef<str>) -> Option<&str> {
+        self.params.get(name.as_ref()).map(|s| s.as_str())
+    }
+
+    /// Returns every parameter captured from the path as `(name, value)` pairs, in no particular
+    /// order, e.g. to record them on a tracing span.
+    pub fn params(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns a parameter value from the path by name, parsed as a `V`.
+    ///
+    /// This is `None` if the route has no such parameter, and the parse error if the value isn't
+    /// a valid `V`.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use http::{Request, Response, StatusCode};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router = Router::new().get("/users/:id", |_, ctx| async move {
+    ///     let resp = match ctx.param_as::<u64>("id") {
+    ///         Some(Ok(id)) => Response::new(format!("user {id}")),
+    ///         _ => Response::builder()
+    ///             .status(StatusCode::BAD_REQUEST)
+    ///             .body(String::new())
+    ///             .unwrap(),
+    ///     };
+    ///     Ok::<_, std::convert::Infallible>(resp)
+    /// });
+    ///
+    /// let req = Request::get("/users/42").body(String::new()).unwrap();
+    /// assert_eq!(router.call(req).await.unwrap().body(), "user 42");
+    /// # });
+    /// ```
+    pub fn param_as<V: FromStr>(&self, name: impl AsRef<str>) -> Option<Result<V, V::Err>> {
+        self.param(name).map(str::parse)
+    }
+
+    /// Sets a header to add to the response to the request, e.g. from middleware that assigns
+    /// a correlation ID or refreshes a session cookie before the handler runs.
+    ///
+    /// The header is added once the handler returns, alongside any values the handler sets for
+    /// the same name. Setting the same header again replaces the value set before.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::convert::Infallible;
+    ///
+    /// use http::{header::HeaderName, HeaderValue, Request, Response};
+    /// use tower::Service;
+    /// use router_service::Router;
+    ///
+    /// let mut router: Router<String, (), Infallible> = Router::new()
+    ///     .group("/api")
+    ///     .middleware(|req, ctx, next| {
+    ///         ctx.set_response_header(
+    ///             HeaderName::from_static("x-correlation-id"),
+    ///             HeaderValue::from_static("abc123"),
+    ///         );
+    ///         next.run(req, ctx)
+    ///     })
+    ///     .get("/users", |_, _| async move { Ok(Response::new(String::new())) })
+    ///     .finish();
+    ///
+    /// let req = Request::get("/api/users").body(String::new()).unwrap();
+    /// let resp = router.call(req).await.unwrap();
+    /// assert_eq!(resp.headers()["x-correlation-id"], "abc123");
+    /// # });
+    /// ```
+    pub fn set_response_header(&self, name: HeaderName, value: HeaderValue) {
+        self.response_headers.insert(name, value);
+    }
+
+    /// Returns the path and query of the request URI the route was matched with, e.g.
+    /// `/users/42?expand=posts`, such as for forwarding the request to an upstream server.
+    pub fn path_and_query(&self) -> &str {
+        // URIs in authority form, like that of a `CONNECT` request, have no path.
+        match &self.path_and_query {
+            Some(path_and_query) => path_and_query.as_str(),
+            None => "",
+        }
+    }
+
+    /// Returns the first value of a query string parameter by name, percent-decoded and with `+`
+    /// decoded as a space, e.g. `rust router` for `q` in `/search?q=rust+router`.
+    ///
+    /// Parameters without a value, like `flag` in `?flag`, have an empty value.
+    pub fn query(&self, name: impl AsRef<str>) -> Option<&str> {
+        let name = name.as_ref();
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns every value of a query string parameter by name, in order, e.g. `["a", "b"]` for
+    /// `tag` in `/posts?tag=a&tag=b`.
+    pub fn query_all(&self, name: impl AsRef<str>) -> Vec<&str> {
+        let name = name.as_ref();
+        self.query
+            .iter()
+            .filter(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Returns the methods the matched route has handlers for, in alphabetical order, when called
+    /// from the handler set with [`Router::method_not_allowed`]. Elsewhere, this is empty.
+    pub fn allowed_methods(&self) -> &[Method] {
+        &self.allowed_methods
+    }
+
+    /// Returns the prefix set with [`Router::with_base_path`], or an empty string if none was set.
+    pub fn base_path(&self) -> &str {
+        self.base_path.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the connection pool passed to [`Router::with_connection_pool`].
+    ///
+    /// This is the same as [`RouteContext::data`], named for routers whose data is a pool.
+    pub fn db(&self) -> &T {
+        &self.data
+    }
+
+    /// Returns the metadata of the connection the request arrived on.
+    ///
+    /// Routers created by a [`MakeRouter`] get a context for every connection. Other routers
+    /// return an empty context with the connection ID 0.
+    pub fn request_context(&self) -> &RequestContext {
+        static EMPTY: RequestContext = RequestContext::EMPTY;
+        self.request_context.as_deref().unwrap_or(&EMPTY)
+    }
+
+    /// Returns the address of the client that made the request, if known.
+    ///
+    /// This is the remote address of the [`RequestContext`], or the client's address taken from
+    /// `X-Forwarded-For` if the request came from a proxy trusted with
+    /// [`Router::with_proxy_headers`].
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// Returns the server-side session of the request, creating it once a value is set.
+    ///
+    /// # Panics
+    /// Panics if the router wasn't given a session store with [`Router::with_session`].
+    #[cfg(feature = "sessions")]
+    pub fn session(&self) -> Session {
+        self.session
+            .clone()
+            .expect("the router has no session store, see `Router::with_session`")
+    }
+}
+
+/// Summarizes the context for logging: the matched route's template (including the base path),
+/// its parameters sorted by name, and the router's data.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{Request, Response};
+/// use tower::Service;
+/// use router_service::Router;
+///
+/// let mut router = Router::with_data("app")
+///     .get("/users/:id/posts/:post", |_, ctx| async move {
+///         Ok::<_, Infallible>(Response::new(ctx.to_string()))
+///     })
+///     .with_base_path("/api");
+///
+/// let req = Request::get("/users/42/posts/7").body(String::new()).unwrap();
+/// let resp = router.call(req).await.unwrap();
+/// assert_eq!(resp.body(), "route=/api/users/:id/posts/:post params={id=42, post=7} data=app");
+/// # });
+/// ```
+impl<T: fmt::Display> fmt::Display for RouteContext<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(template) = &self.template {
+            write!(f, "route={}{template} ", self.base_path())?;
+        }
+
+        let mut params: Vec<_> = self.params.iter().collect();
+        params.sort();
+
+        f.write_str("params={")?;
+        for (i, (name, value)) in params.into_iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{name}={value}")?;
+        }
+        write!(f, "}} data={}", self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use http::{
+        header::{
+            AUTHORIZATION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, FORWARDED, HOST, LOCATION,
+            SET_COOKIE, VARY,
+        },
+        Method, Request, Response, StatusCode,
+    };
+    use tower::Service;
+
+    use crate::{Handler, RouteContext, Router};
+
+    #[test]
+    fn not_found() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new();
+            let req = Request::builder()
+                .uri("/not-found")
+                .method(Method::GET)
+                .body(())
+                .unwrap();
+
+            let resp = router.call(req).await;
+            let resp = resp.unwrap();
+
+            assert_eq!(resp.status(), 404);
+        });
+    }
+
+    #[test]
+    fn receives_data() {
+        futures::executor::block_on(async move {
+            let data = Arc::new(AtomicBool::new(false));
+            let mut router: Router<(), Arc<AtomicBool>, Infallible> =
+                Router::with_data(data.clone()).get("/example", |_req, ctx| async move {
+                    ctx.data.store(true, Ordering::SeqCst);
+                    Ok(Response::builder().body(()).unwrap())
+                });
+
+            let req = Request::builder()
+                .uri("/example")
+                .method(Method::GET)
+                .body(())
+                .unwrap();
+
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+            assert!(data.load(Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn receives_data_async() {
+        futures::executor::block_on(async move {
+            let data = Arc::new(AtomicBool::new(false));
+            let mut router: Router<_, _, Infallible> =
+                Router::with_data(data.clone()).get("/example", |_req, ctx| async move {
+                    ctx.data.store(true, Ordering::SeqCst);
+                    Ok(Response::builder().body(()).unwrap())
+                });
+
+            let req = Request::builder()
+                .uri("/example")
+                .method(Method::GET)
+                .body(())
+                .unwrap();
+
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+            assert!(data.load(Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn validate_reports_shadowed_routes() {
+        let router: Router<(), (), Infallible> = Router::new()
+            .get("/", |_, _| async move { Ok(Response::new(())) })
+            .any("/*rest", |_, _| async move { Ok(Response::new(())) })
+            .get("/shadowed", |_, _| async move { Ok(Response::new(())) })
+            .post("/", |_, _| async move { Ok(Response::new(())) });
+
+        assert_eq!(router.validate(), Err(vec!["/shadowed".to_string()]));
+    }
+
+    #[test]
+    fn validate_accepts_distinct_routes() {
+        let router: Router<(), (), Infallible> = Router::new()
+            .get("/foo", |_, _| async move { Ok(Response::new(())) })
+            .any("/*rest", |_, _| async move { Ok(Response::new(())) })
+            .post("/foo", |_, _| async move { Ok(Response::new(())) });
+
+        assert_eq!(router.validate(), Ok(()));
+    }
+
+    #[test]
+    fn clone_without_cloneable_data() {
+        struct NotClone;
+
+        let router: Router<(), NotClone, Infallible> = Router::with_data(NotClone);
+        let cloned = router.clone();
+
+        assert!(Arc::ptr_eq(&router.data, &cloned.data));
+    }
+
+    #[test]
+    fn not_found_bytes() {
+        futures::executor::block_on(async move {
+            let mut router: Router<Bytes, (), Infallible> = Router::new()
+                .get("/", |_, _| async move { Ok(Response::new(Bytes::new())) })
+                .not_found_bytes("text/plain", "nothing here");
+
+            let req = Request::builder()
+                .uri("/not-found")
+                .method(Method::GET)
+                .body(Bytes::new())
+                .unwrap();
+
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 404);
+            assert_eq!(resp.headers()[CONTENT_TYPE], "text/plain");
+            assert_eq!(resp.body(), "nothing here");
+        });
+    }
+
+    #[test]
+    fn max_params() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/:a", |_, _| async move { Ok(Response::new(())) })
+                .get("/:a/:b", |_, _| async move { Ok(Response::new(())) })
+                .with_max_params(1);
+
+            let req = Request::builder().uri("/1").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+
+            let req = Request::builder().uri("/1/2").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 400);
+        });
+    }
+
+    #[test]
+    fn get_with_cache() {
+        futures::executor::block_on(async move {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = calls.clone();
+            let mut router: Router<String, (), Infallible> = Router::new().get_with_cache(
+                "/cached",
+                move |_, _| {
+                    let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { Ok(Response::new(count.to_string())) }
+                },
+                Duration::from_secs(60),
+            );
+
+            for _ in 0..2 {
+                let req = Request::get("/cached").body(String::new()).unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.body(), "1");
+            }
+
+            let req = Request::get("/cached?page=2").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "2");
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn get_with_cache_expires() {
+        futures::executor::block_on(async move {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let counter = calls.clone();
+            let mut router: Router<String, (), Infallible> = Router::new().get_with_cache(
+                "/cached",
+                move |_, _| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    async move { Ok(Response::new(String::new())) }
+                },
+                Duration::ZERO,
+            );
+
+            for _ in 0..2 {
+                let req = Request::get("/cached").body(String::new()).unwrap();
+                router.call(req).await.unwrap();
+            }
+
+            assert_eq!(calls.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn get_with_cache_per_client() {
+        futures::executor::block_on(async move {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let user = {
+                let calls = calls.clone();
+                move |req: &Request<String>| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    let user = req.headers()["x-user"].to_str().unwrap();
+                    user.to_string()
+                }
+            };
+            let login = user.clone();
+            let mut router: Router<String, (), Infallible> = Router::new()
+                .get_with_cache(
+                    "/login",
+                    move |req, _| {
+                        let user = login(&req);
+                        async move {
+                            let resp = Response::builder()
+                                .header(SET_COOKIE, format!("user={user}"))
+                                .body(user)
+                                .unwrap();
+                            Ok(resp)
+                        }
+                    },
+                    Duration::from_secs(60),
+                )
+                .get_with_cache(
+                    "/greeting",
+                    move |req, _| {
+                        let user = user(&req);
+                        async move {
+                            let resp = Response::builder()
+                                .header(VARY, "x-user")
+                                .body(user)
+                                .unwrap();
+                            Ok(resp)
+                        }
+                    },
+                    Duration::from_secs(60),
+                );
+
+            // Responses setting cookies aren't cached, and responses varying on a header are only
+            // replayed to requests with the same value for it.
+            for (path, expected_calls) in [("/login", 4), ("/greeting", 3)] {
+                calls.store(0, Ordering::SeqCst);
+                for user in ["alice", "alice", "alice", "bob"] {
+                    let req = Request::get(path)
+                        .header("x-user", user)
+                        .body(String::new())
+                        .unwrap();
+                    let resp = router.call(req).await.unwrap();
+                    assert_eq!(resp.body(), user);
+                }
+                assert_eq!(calls.load(Ordering::SeqCst), expected_calls);
+            }
+        });
+    }
+
+    #[test]
+    fn group_guards_prefixed_routes() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/public", |_, _| async move { Ok(Response::new(())) })
+                .group("/admin")
+                .guard(|req| match req.headers().contains_key(AUTHORIZATION) {
+                    true => Ok(()),
+                    false => Err(StatusCode::UNAUTHORIZED),
+                })
+                .get("/users", |_, _| async move { Ok(Response::new(())) })
+                .post("/settings", |_, _| async move { Ok(Response::new(())) })
+                .finish();
+
+            for (method, path) in [
+                (Method::GET, "/admin/users"),
+                (Method::POST, "/admin/settings"),
+            ] {
+                let req = Request::builder()
+                    .uri(path)
+                    .method(method.clone())
+                    .body(())
+                    .unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.status(), 401);
+
+                let req = Request::builder()
+                    .uri(path)
+                    .method(method)
+                    .header(AUTHORIZATION, "Bearer token")
+                    .body(())
+                    .unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.status(), 200);
+            }
+
+            let req = Request::get("/public").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+        });
+    }
+
+    #[test]
+    fn group_middleware_and_data() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), &'static str, Infallible> = Router::with_data("outer")
+                .get("/", |_, ctx| async move {
+                    Ok(Response::builder()
+                        .header("x-data", ctx.data)
+                        .body(())
+                        .unwrap())
+                })
+                .group("/inner")
+                .with_data("inner")
+                .middleware(|req, ctx, next| async move {
+                    let mut resp = next.run(req, ctx).await?;
+                    resp.headers_mut()
+                        .insert("x-order", "outer".parse().unwrap());
+                    Ok(resp)
+                })
+                .middleware(|req, ctx, next| async move {
+                    let mut resp = next.run(req, ctx).await?;
+                    resp.headers_mut()
+                        .insert("x-order", "inner".parse().unwrap());
+                    Ok(resp)
+                })
+                .get("/", |_, ctx| async move {
+                    Ok(Response::builder()
+                        .header("x-data", ctx.data)
+                        .body(())
+                        .unwrap())
+                })
+                .finish();
+
+            let req = Request::get("/inner").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()["x-data"], "inner");
+            assert_eq!(resp.headers()["x-order"], "outer");
+
+            let req = Request::get("/").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()["x-data"], "outer");
+            assert!(!resp.headers().contains_key("x-order"));
+        });
+    }
+
+    #[test]
+    fn not_found_passthrough() {
+        futures::executor::block_on(async move {
+            let legacy: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move {
+                    Ok(Response::builder()
+                        .header("x-legacy", "1")
+                        .body(())
+                        .unwrap())
+                })
+                .get("/legacy", |_, _| async move { Ok(Response::new(())) });
+
+            let mut router = Router::new()
+                .get("/", |_, _| async move { Ok(Response::new(())) })
+                .not_found_passthrough(legacy);
+
+            let req = Request::get("/").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert!(!resp.headers().contains_key("x-legacy"));
+
+            let req = Request::get("/legacy").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+
+            let req = Request::get("/missing").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 404);
+        });
+    }
+
+    #[test]
+    fn require_https() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move { Ok(Response::new(())) })
+                .require_https(true);
+
+            let req = Request::get("/")
+                .header(HOST, "example.com")
+                .header(FORWARDED, "for=192.0.2.60;proto=http")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 301);
+            assert_eq!(resp.headers()[LOCATION], "https://example.com/");
+
+            let req = Request::get("/")
+                .header(HOST, "example.com")
+                .header("x-forwarded-proto", "https")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+
+            let req = Request::get("/")
+                .header("x-forwarded-proto", "http")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 400);
+        });
+    }
+
+    #[test]
+    fn content_security_policy() {
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move {
+                    Ok(Response::builder()
+                        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+                        .body(())
+                        .unwrap())
+                })
+                .get("/api", |_, _| async move {
+                    Ok(Response::builder()
+                        .header(CONTENT_TYPE, "application/json")
+                        .body(())
+                        .unwrap())
+                })
+                .with_content_security_policy("default-src 'self'");
+
+            let resp = router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                resp.headers()[CONTENT_SECURITY_POLICY],
+                "default-src 'self'"
+            );
+
+            let resp = router
+                .call(Request::get("/api").body(()).unwrap())
+                .await
+                .unwrap();
+            assert!(!resp.headers().contains_key(CONTENT_SECURITY_POLICY));
+
+            let mut router = router.with_content_security_policy_for_all("default-src 'none'");
+            let resp = router
+                .call(Request::get("/api").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                resp.headers()[CONTENT_SECURITY_POLICY],
+                "default-src 'none'"
+            );
+        });
+    }
+
+    #[test]
+    fn cors() {
+        use http::header::{
+            ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+        };
+
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move { Ok(Response::new(())) })
+                .cors_allow_origin(&["https://example.com"]);
+
+            let req = Request::get("/")
+                .header(ORIGIN, "https://example.com")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(
+                resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+                "https://example.com"
+            );
+            assert_eq!(resp.headers()[VARY], "origin");
+
+            let req = Request::get("/")
+                .header(ORIGIN, "https://evil.example")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 200);
+            assert!(!resp.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+
+            let req = Request::options("/")
+                .header(ORIGIN, "https://example.com")
+                .header(ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 204);
+            assert_eq!(
+                resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+                "https://example.com"
+            );
+
+            let mut router = router.cors_allow_all();
+            let req = Request::get("/")
+                .header(ORIGIN, "https://evil.example")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        });
+    }
+
+    #[test]
+    fn hsts() {
+        use http::header::STRICT_TRANSPORT_SECURITY;
+
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move { Ok(Response::new(())) })
+                .with_hsts_max_age(300);
+
+            let resp = router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.headers()[STRICT_TRANSPORT_SECURITY], "max-age=300");
+
+            let mut router = router.with_hsts(true, true, 63072000);
+            let resp = router
+                .call(Request::get("/missing").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 404);
+            assert_eq!(
+                resp.headers()[STRICT_TRANSPORT_SECURITY],
+                "max-age=63072000; includeSubDomains; preload"
+            );
+        });
+    }
+
+    #[test]
+    fn server_header() {
+        use http::header::SERVER;
+
+        futures::executor::block_on(async move {
+            let mut router: Router<(), (), Infallible> = Router::new()
+                .get("/", |_, _| async move {
+                    Ok(Response::builder()
+                        .header(SERVER, "hyper")
+                        .body(())
+                        .unwrap())
+                })
+                .with_server_header("my-app");
+
+            let resp = router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.headers()[SERVER], "my-app");
+
+            let mut router = router.with_server_header("");
+            let resp = router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert!(!resp.headers().contains_key(SERVER));
+        });
+    }
+
+    #[test]
+    fn probe_layer() {
+        use tower::Layer;
+
+        use crate::ProbeLayer;
+
+        futures::executor::block_on(async move {
+            let router: Router<String, (), Infallible> = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("home".into()))
+                })
+                .get("/.well-known/change-password", |_, _| async move {
+                    Ok(Response::new("router".into()))
+                });
+            let mut service = ProbeLayer::new()
+                .register("openid-configuration", |_| async move {
+                    Ok::<_, Infallible>(Response::new("oidc".to_string()))
+                })
+                .register("acme-challenge", |req: Request<String>| async move {
+                    Ok(Response::new(req.uri().path().to_string()))
+                })
+                .layer(router);
+
+            let mut get =
+                |path: &str| service.call(Request::get(path).body(String::new()).unwrap());
+
+            assert_eq!(
+                get("/.well-known/openid-configuration")
+                    .await
+                    .unwrap()
+                    .body(),
+                "oidc"
+            );
+            let resp = get("/.well-known/acme-challenge/token").await.unwrap();
+            assert_eq!(resp.body(), "/.well-known/acme-challenge/token");
+            assert_eq!(
+                get("/.well-known/change-password").await.unwrap().body(),
+                "router"
+            );
+            assert_eq!(get("/").await.unwrap().body(), "home");
+            let resp = get("/.well-known/webfinger").await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn fallback_chain() {
+        use crate::FallbackChain;
+
+        futures::executor::block_on(async move {
+            let v1: Router<String, (), Infallible> = Router::new()
+                .get("/users", |_, _| async move {
+                    Ok(Response::new("v1 users".into()))
+                })
+                .get("/posts", |_, _| async move {
+                    Ok(Response::new("v1 posts".into()))
+                });
+            let v2 = Router::new()
+                .get("/users", |_, _| async move {
+                    Ok(Response::new("v2 users".into()))
+                })
+                .get("/posts", |_, _| async move {
+                    Ok(Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body("v2 posts".into())
+                        .unwrap())
+                });
+            let mut chain = FallbackChain::new(vec![v2, v1]);
+
+            let req = Request::get("/users").body(String::new()).unwrap();
+            let resp = chain.call(req).await.unwrap();
+            assert_eq!(resp.body(), "v2 users");
+
+            let req = Request::get("/posts").body(String::new()).unwrap();
+            let resp = chain.call(req).await.unwrap();
+            assert_eq!(resp.body(), "v1 posts");
+
+            let req = Request::get("/missing").body(String::new()).unwrap();
+            let resp = chain.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn make_router() {
+        use crate::MakeRouter;
+
+        futures::executor::block_on(async move {
+            let router = Router::with_data(("router", 0)).get("/", |_, ctx| async move {
+                let (name, conn) = ctx.data;
+                Ok::<_, Infallible>(Response::new(format!("{name} {conn}")))
+            });
+            let mut make_router = MakeRouter::new(router, |data, conn: &u32| (data.0, *conn));
+
+            let mut first = make_router.call(&1).await.unwrap();
+            let mut second = make_router.call(&2).await.unwrap();
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            assert_eq!(first.call(req).await.unwrap().body(), "router 1");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            assert_eq!(second.call(req).await.unwrap().body(), "router 2");
+        });
+    }
+
+    #[test]
+    fn request_context() {
+        use std::net::SocketAddr;
+
+        use crate::{MakeRouter, RequestContext, TlsInfo};
+
+        futures::executor::block_on(async move {
+            let router = Router::new().get("/", |req: Request<String>, ctx| async move {
+                let conn = ctx.request_context();
+                let attached = req.extensions().get::<RequestContext>() == Some(conn);
+                let sni = conn.tls().and_then(TlsInfo::server_name).unwrap_or("-");
+                let id = conn.connection_id();
+                let body = format!("{id} {:?} {sni} {attached}", conn.remote_addr());
+                Ok::<_, Infallible>(Response::new(body))
+            });
+            let mut make_router = MakeRouter::new(router.clone(), |_, _: &SocketAddr| ())
+                .request_context(|ctx, addr| {
+                    ctx.with_remote_addr(*addr)
+                        .with_tls(TlsInfo::new().with_server_name("example.com"))
+                });
+
+            let addr = SocketAddr::from(([127, 0, 0, 1], 50000));
+            let mut first = make_router.call(&addr).await.unwrap();
+            let mut second = make_router.call(&addr).await.unwrap();
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = first.call(req).await.unwrap();
+            assert_eq!(resp.body(), "1 Some(127.0.0.1:50000) example.com true");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = second.call(req).await.unwrap();
+            assert_eq!(resp.body(), "2 Some(127.0.0.1:50000) example.com true");
+
+            let mut router = router;
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "0 None - false");
+        });
+    }
+
+    #[test]
+    fn with_proxy_headers() {
+        futures::executor::block_on(async move {
+            use std::net::SocketAddr;
+
+            use crate::MakeRouter;
+
+            let router = Router::new().get("/", |_: Request<String>, ctx| async move {
+                let ip = ctx.client_ip().map(|ip| ip.to_string());
+                Ok::<_, Infallible>(Response::new(ip.unwrap_or_default()))
+            });
+            let forwarded = || {
+                Request::get("/")
+                    .header("x-forwarded-for", "198.51.100.4, 203.0.113.7")
+                    .body(String::new())
+                    .unwrap()
+            };
+
+            let proxy = SocketAddr::from(([10, 0, 0, 1], 50000));
+            let mut make_router = MakeRouter::new(router.clone(), |_, _: &SocketAddr| ())
+                .request_context(|ctx, addr| ctx.with_remote_addr(*addr));
+            let mut untrusted = make_router.call(&proxy).await.unwrap();
+            let resp = untrusted.call(forwarded()).await.unwrap();
+            assert_eq!(resp.body(), "10.0.0.1");
+
+            let trusted = router.with_proxy_headers(vec![proxy.ip()]);
+            let mut make_router = MakeRouter::new(trusted.clone(), |_, _: &SocketAddr| ())
+                .request_context(|ctx, addr| ctx.with_remote_addr(*addr));
+            let mut router = make_router.call(&proxy).await.unwrap();
+            let resp = router.call(forwarded()).await.unwrap();
+            assert_eq!(resp.body(), "203.0.113.7");
+
+            let client = SocketAddr::from(([192, 0, 2, 1], 50000));
+            let mut router = make_router.call(&client).await.unwrap();
+            let resp = router.call(forwarded()).await.unwrap();
+            assert_eq!(resp.body(), "192.0.2.1");
+
+            let mut router = trusted;
+            let resp = router.call(forwarded()).await.unwrap();
+            assert_eq!(resp.body(), "");
+        });
+    }
+
+    #[test]
+    fn with_max_request_size() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .post("/upload", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(()))
+                })
+                .with_max_request_size(4);
+            let upload = |len: &str| {
+                Request::post("/upload")
+                    .header(http::header::CONTENT_LENGTH, len)
+                    .body(())
+                    .unwrap()
+            };
+
+            let resp = router.call(upload("4")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let resp = router.call(upload("5")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+            // Rejected before routing, so missing routes don't turn into 404s.
+            let req = Request::post("/missing")
+                .header(http::header::CONTENT_LENGTH, "5")
+                .body(())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        });
+    }
+
+    #[cfg(feature = "hyper")]
+    #[tokio::test]
+    async fn with_max_request_size_streamed() {
+        let mut router = Router::new()
+            .post("/upload", |req: Request<hyper::Body>, _| async move {
+                // Handlers may ignore the error, the router still answers with `413`.
+                let read = hyper::body::to_bytes(req.into_body()).await;
+                Ok::<_, Infallible>(Response::new(hyper::Body::from(format!("{read:?}"))))
+            })
+            .with_max_request_size(4);
+        let upload = |chunks: &'static [&'static str]| {
+            let chunks = chunks.iter().map(|chunk| Ok::<_, Infallible>(*chunk));
+            let body = hyper::Body::wrap_stream(futures_util::stream::iter(chunks));
+            Request::post("/upload").body(body).unwrap()
+        };
+
+        let resp = router.call(upload(&["ab", "cd"])).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, r#"Ok(b"abcd")"#);
+
+        let resp = router.call(upload(&["ab", "cd", "e"])).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn shadow_route() {
+        use tokio::sync::mpsc;
+
+        let (shadowed, mut received) = mpsc::unbounded_channel();
+        let mut router = Router::new()
+            .post("/users/:id", |_, _| async move {
+                Ok::<_, Infallible>(Response::new("primary".to_string()))
+            })
+            .shadow_route("/users/:user", move |req: Request<String>, ctx| {
+                let shadowed = shadowed.clone();
+                async move {
+                    let copy = format!(
+                        "{} {} {:?}",
+                        req.method(),
+                        ctx.param("user").unwrap(),
+                        req.body()
+                    );
+                    shadowed.send(copy).unwrap();
+                    Ok(Response::new("shadow".to_string()))
+                }
+            });
+
+        let req = Request::post("/users/42")
+            .body("name=alice".to_string())
+            .unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.body(), "primary");
+        assert_eq!(received.recv().await.unwrap(), "POST 42 \"\"");
+
+        // Requests the primary routes don't answer aren't shadowed.
+        let req = Request::get("/users/7").body(String::new()).unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let req = Request::get("/posts").body(String::new()).unwrap();
+        router.call(req).await.unwrap();
+
+        // Without a Tokio runtime, the copy is dropped instead of spawned.
+        let mut blocking = router.clone();
+        std::thread::spawn(move || {
+            let req = Request::post("/users/1").body(String::new()).unwrap();
+            let resp = futures::executor::block_on(blocking.call(req)).unwrap();
+            assert_eq!(resp.body(), "primary");
+        })
+        .join()
+        .unwrap();
+        drop(router);
+        assert_eq!(received.recv().await, None);
+    }
+
+    #[test]
+    fn with_request_transform() {
+        futures::executor::block_on(async move {
+            fn default_language(mut req: Request<String>) -> Request<String> {
+                let headers = req.headers_mut();
+                if !headers.contains_key(http::header::ACCEPT_LANGUAGE) {
+                    headers.insert(
+                        http::header::ACCEPT_LANGUAGE,
+                        http::HeaderValue::from_static("en"),
+                    );
+                }
+                req
+            }
+
+            let mut router = Router::new()
+                .get("/", |req: Request<String>, _| async move {
+                    let language = req.headers()[http::header::ACCEPT_LANGUAGE].clone();
+                    Ok::<_, Infallible>(Response::new(language.to_str().unwrap().to_string()))
+                })
+                .with_request_transform(default_language);
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "en");
+
+            let req = Request::get("/")
+                .header(http::header::ACCEPT_LANGUAGE, "fr")
+                .body(String::new())
+                .unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "fr");
+        });
+    }
+
+    #[test]
+    fn with_response_transform() {
+        futures::executor::block_on(async move {
+            fn shout(resp: Response<String>) -> Response<String> {
+                let status = resp.status();
+                resp.map(|body| format!("{} {}", status.as_u16(), body.to_uppercase()))
+            }
+
+            let mut router = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("hello".to_string()))
+                })
+                .with_server_header("router")
+                .with_response_transform(shout);
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "200 HELLO");
+            assert_eq!(resp.headers()[http::header::SERVER], "router");
+
+            let req = Request::get("/missing").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "404 ");
+        });
+    }
+
+    #[test]
+    fn const_routes() {
+        use futures_util::future::BoxFuture;
+
+        use crate::ConstRoute;
+
+        type Result = std::result::Result<Response<String>, Infallible>;
+
+        fn greet(
+            _: Request<String>,
+            ctx: RouteContext<&'static str>,
+        ) -> BoxFuture<'static, Result> {
+            let body = format!("{}, {}", ctx.data, ctx.param("name").unwrap());
+            Box::pin(async move { Ok(Response::new(body)) })
+        }
+
+        fn forget(_: Request<String>, _: RouteContext<&'static str>) -> BoxFuture<'static, Result> {
+            Box::pin(async move { Ok(Response::new("forgotten".to_string())) })
+        }
+
+        const ROUTES: &[ConstRoute<String, &'static str, Infallible>] = &[
+            (Method::GET, "/greet/:name", greet),
+            (Method::DELETE, "/greet/:name", forget),
+        ];
+
+        futures::executor::block_on(async move {
+            let mut router = Router::with_data("hello").with_const_routes(ROUTES);
+
+            let req = Request::get("/greet/alice").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "hello, alice");
+
+            let req = Request::delete("/greet/alice").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "forgotten");
+
+            let req = Request::post("/greet/alice").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn link_and_unlink() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .link("/resource", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.method().to_string()))
+                })
+                .unlink("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                });
+
+            for method in ["LINK", "UNLINK"] {
+                let req = Request::builder()
+                    .method(Method::from_bytes(method.as_bytes()).unwrap())
+                    .uri("/resource")
+                    .body(String::new())
+                    .unwrap();
+                assert_eq!(router.call(req).await.unwrap().body(), method);
+            }
+
+            let req = Request::get("/resource").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn webdav_methods() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .lock("/resource", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.method().to_string()))
+                })
+                .unlock("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                })
+                .mkcol("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                })
+                .copy("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                })
+                .move_("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                })
+                .propfind("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                })
+                .proppatch("/resource", |req, _| async move {
+                    Ok(Response::new(req.method().to_string()))
+                });
+
+            for method in [
+                "LOCK",
+                "UNLOCK",
+                "MKCOL",
+                "COPY",
+                "MOVE",
+                "PROPFIND",
+                "PROPPATCH",
+            ] {
+                let req = Request::builder()
+                    .method(Method::from_bytes(method.as_bytes()).unwrap())
+                    .uri("/resource")
+                    .body(String::new())
+                    .unwrap();
+                assert_eq!(router.call(req).await.unwrap().body(), method);
+            }
+
+            let req = Request::get("/resource").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn report_method() {
+        futures::executor::block_on(async move {
+            let mut router =
+                Router::new().report("/resource", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.method().to_string()))
+                });
+
+            let req = Request::builder()
+                .method(Method::from_bytes(b"REPORT").unwrap())
+                .uri("/resource")
+                .body(String::new())
+                .unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "REPORT");
+
+            let req = Request::get("/resource").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn search_method() {
+        futures::executor::block_on(async move {
+            let mut router =
+                Router::new().search("/resource", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.method().to_string()))
+                });
+
+            let req = Request::builder()
+                .method(Method::from_bytes(b"SEARCH").unwrap())
+                .uri("/resource")
+                .body(String::new())
+                .unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "SEARCH");
+
+            let req = Request::get("/resource").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn method_not_allowed() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(()))
+                })
+                .put("/items", |_, _| async move { Ok(Response::new(())) })
+                .get("/items", |_, _| async move { Ok(Response::new(())) })
+                .delete("/items", |_, _| async move { Ok(Response::new(())) });
+
+            let req = Request::post("/users").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(resp.headers()[http::header::ALLOW], "GET, HEAD, OPTIONS");
+
+            let req = Request::post("/items").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(
+                resp.headers()[http::header::ALLOW],
+                "DELETE, GET, HEAD, OPTIONS, PUT"
+            );
+
+            let req = Request::post("/posts").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert!(resp.headers().get(http::header::ALLOW).is_none());
+        });
+    }
+
+    #[test]
+    fn options_and_delete_on_same_path() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .delete("/foo", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("delete".to_string()))
+                })
+                .options("/foo", |_, _| async move {
+                    Ok(Response::new("options".to_string()))
+                });
+
+            let req = Request::delete("/foo").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "delete");
+
+            let req = Request::options("/foo").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "options");
+        });
+    }
+
+    #[test]
+    fn options_does_not_answer_delete() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().options("/foo", |_, _| async move {
+                Ok::<_, Infallible>(Response::new(()))
+            });
+
+            let req = Request::options("/foo").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let req = Request::delete("/foo").body(()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn custom_method() {
+        futures::executor::block_on(async move {
+            let purge = Method::from_bytes(b"PURGE").unwrap();
+            let mut router = Router::new()
+                .get("/cache", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("get".to_string()))
+                })
+                .method(purge.clone(), "/cache", |_, _| async move {
+                    Ok(Response::new("purge".to_string()))
+                })
+                .method(Method::PUT, "/cache", |_, _| async move {
+                    Ok(Response::new("put".to_string()))
+                });
+
+            for (method, body) in [(purge, "purge"), (Method::PUT, "put"), (Method::GET, "get")] {
+                let req = Request::builder()
+                    .method(method)
+                    .uri("/cache")
+                    .body(String::new())
+                    .unwrap();
+                assert_eq!(router.call(req).await.unwrap().body(), body);
+            }
+        });
+    }
+
+    #[test]
+    fn query() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().get("/search", |_, ctx| async move {
+                let body = format!(
+                    "{:?} {:?} {:?}",
+                    ctx.query("q"),
+                    ctx.query_all("tag"),
+                    ctx.query("page")
+                );
+                Ok::<_, Infallible>(Response::new(body))
+            });
+
+            let req = Request::get("/search?q=caf%C3%A9+au+lait&tag=a&tag=b%26c")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), r#"Some("café au lait") ["a", "b&c"] None"#);
+
+            let req = Request::get("/search").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "None [] None");
+        });
+    }
+
+    #[test]
+    fn path_and_query() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users/:id", |_, ctx| async move {
+                    Ok::<_, Infallible>(Response::new(ctx.path_and_query().to_string()))
+                })
+                .any_trailing_slash(true);
+
+            let req = Request::get("http://example.com/users/42?expand=posts")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "/users/42?expand=posts");
+
+            let req = Request::get("/users/42/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "/users/42");
+        });
+    }
+
+    #[test]
+    fn auto_options() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/items", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("items".to_string()))
+                })
+                .post(
+                    "/items",
+                    |_, _| async move { Ok(Response::new(String::new())) },
+                )
+                .get(
+                    "/custom",
+                    |_, _| async move { Ok(Response::new(String::new())) },
+                )
+                .options("/custom", |_, _| async move {
+                    Ok(Response::new("custom".to_string()))
+                });
+
+            let req = Request::options("/items").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(
+                resp.headers()[http::header::ALLOW],
+                "GET, HEAD, OPTIONS, POST"
+            );
+            assert_eq!(resp.headers()[http::header::CONTENT_LENGTH], "0");
+            assert_eq!(resp.body(), "");
+
+            let req = Request::options("/custom").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "custom");
+
+            let req = Request::options("/missing").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[test]
+    fn auto_head() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/", |_, _| async move {
+                    let resp = Response::builder()
+                        .header("x-greeting", "hello")
+                        .body("hello".to_string())
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                })
+                .get("/explicit", |_, _| async move {
+                    Ok(Response::new("get".to_string()))
+                })
+                .head("/explicit", |_, _| async move {
+                    Ok(Response::new("head".to_string()))
+                });
+
+            let req = Request::head("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()["x-greeting"], "hello");
+            assert_eq!(resp.body(), "");
+
+            let req = Request::head("/explicit").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "head");
+
+            let mut router = router.auto_head(false);
+            let req = Request::head("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(resp.headers()[http::header::ALLOW], "GET, OPTIONS");
+        });
+    }
+
+    #[test]
+    fn auto_head_params() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().get("/users/:id", |_, ctx| async move {
+                let id = ctx.param("id").unwrap().to_string();
+                let resp = Response::builder()
+                    .header("x-user", id)
+                    .body("user".to_string());
+                Ok::<_, Infallible>(resp.unwrap())
+            });
+
+            let req = Request::head("/users/42").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()["x-user"], "42");
+            assert_eq!(resp.body(), "");
+        });
+    }
+
+    #[test]
+    fn with_authority_routing() {
+        futures::executor::block_on(async move {
+            use std::collections::HashMap;
+
+            let host_router = |name: &'static str| {
+                Router::new()
+                    .get("/", move |_, _| async move {
+                        Ok::<_, Infallible>(Response::new(name.to_string()))
+                    })
+                    .with_server_header(name)
+            };
+            let mut router = host_router("default").with_authority_routing(HashMap::from([
+                ("api.example.com".to_string(), host_router("api")),
+                ("localhost:8080".to_string(), host_router("dev")),
+            ]));
+            let mut call = |uri: &str, host: Option<&str>| {
+                let mut req = Request::get(uri);
+                if let Some(host) = host {
+                    req = req.header(http::header::HOST, host);
+                }
+                router.call(req.body(String::new()).unwrap())
+            };
+
+            let resp = call("/", Some("API.example.com")).await.unwrap();
+            assert_eq!(resp.body(), "api");
+            assert_eq!(resp.headers()[http::header::SERVER], "api");
+
+            let resp = call("https://api.example.com/", None).await.unwrap();
+            assert_eq!(resp.body(), "api");
+
+            let resp = call("/", Some("localhost:8080")).await.unwrap();
+            assert_eq!(resp.body(), "dev");
+
+            let resp = call("/", Some("localhost:3000")).await.unwrap();
+            assert_eq!(resp.body(), "default");
+
+            let resp = call("/", None).await.unwrap();
+            assert_eq!(resp.body(), "default");
+        });
+    }
+
+    #[test]
+    fn fallback() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                })
+                .fallback(|req: Request<String>, ctx| async move {
+                    assert_eq!(ctx.param("path"), None);
+                    let resp = Response::builder()
+                        .status(StatusCode::IM_A_TEAPOT)
+                        .body(req.uri().path().to_string())
+                        .unwrap();
+                    Ok(resp)
+                });
+
+            let req = Request::get("/teapot").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::IM_A_TEAPOT);
+            assert_eq!(resp.body(), "/teapot");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        });
+    }
+
+    #[cfg(feature = "websocket")]
+    #[tokio::test]
+    async fn websocket_upgrade() {
+        use futures::{SinkExt, StreamExt};
+        use hyper::{Body, Server};
+        use tokio::net::TcpStream;
+        use tokio_tungstenite::tungstenite::Message;
+        use tower::make::Shared;
+
+        let mut router: Router<Body, (), Infallible> =
+            Router::new().websocket_upgrade("/echo/:name", |mut ws, ctx| async move {
+                let name = ctx.param("name").unwrap().to_string();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    ws.send(Message::Text(format!("{name}: {text}")))
+                        .await
+                        .unwrap();
+                }
+            });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(Shared::new(router.clone()));
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let url = format!("ws://{addr}/echo/alice");
+        let (mut ws, resp) = tokio_tungstenite::client_async(url, stream).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        ws.send(Message::Text("hello".to_string())).await.unwrap();
+        let reply = ws.next().await.unwrap().unwrap();
+        assert_eq!(reply, Message::Text("alice: hello".to_string()));
+
+        let req = Request::get("/echo/alice").body(Body::empty()).unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UPGRADE_REQUIRED);
+
+        // Without a Tokio runtime, the connection's handler can't be spawned.
+        std::thread::spawn(move || {
+            let req = Request::get("/echo/alice")
+                .header("connection", "upgrade")
+                .header("upgrade", "websocket")
+                .header("sec-websocket-version", "13")
+                .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+                .body(Body::empty())
+                .unwrap();
+            let resp = futures::executor::block_on(router.call(req)).unwrap();
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn trace() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().trace("/echo", |req: Request<String>, _| async move {
+                let resp = Response::builder()
+                    .header("content-type", "message/http")
+                    .body(format!(
+                        "{} {} {:?}",
+                        req.method(),
+                        req.uri(),
+                        req.version()
+                    ))
+                    .unwrap();
+                Ok::<_, Infallible>(resp)
+            });
+
+            let req = Request::trace("/echo").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.body(), "TRACE /echo HTTP/1.1");
+
+            let req = Request::get("/echo").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn params() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().get("/users/:id/posts/:post", |_, ctx| async move {
+                let mut params: Vec<_> = ctx.params().collect();
+                params.sort_unstable();
+                Ok::<_, Infallible>(Response::new(format!("{params:?}")))
+            });
+
+            let req = Request::get("/users/42/posts/7")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), r#"[("id", "42"), ("post", "7")]"#);
+        });
+    }
+
+    #[test]
+    fn param_as() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new().get("/users/:id", |_, ctx| async move {
+                let body = match ctx.param_as::<u32>("id") {
+                    Some(Ok(id)) => format!("id {id}"),
+                    Some(Err(err)) => format!("invalid id: {err}"),
+                    None => unreachable!(),
+                };
+                assert!(ctx.param_as::<u32>("name").is_none());
+                Ok::<_, Infallible>(Response::new(body))
+            });
+
+            let req = Request::get("/users/42").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "id 42");
+
+            let req = Request::get("/users/alice").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "invalid id: invalid digit found in string");
+        });
+    }
+
+    #[test]
+    fn connect() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .connect("/", |req: Request<String>, _| async move {
+                    let target = req.uri().authority().unwrap().to_string();
+                    Ok::<_, Infallible>(Response::new(target))
+                })
+                .connect("/tunnels/:id", |_, ctx| async move {
+                    Ok(Response::new(ctx.param("id").unwrap().to_string()))
+                });
+
+            let req = Request::connect("example.com:443")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.body(), "example.com:443");
+
+            let req = Request::connect("/tunnels/7").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "7");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        });
+    }
+
+    #[test]
+    fn build_and_check() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users/:id", |_, ctx| async move {
+                    Ok::<_, Infallible>(Response::new(ctx.param("id").unwrap().to_string()))
+                })
+                .build_and_check()
+                .unwrap();
+
+            let req = Request::get("/users/42").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "42");
+
+            let errors = Router::new()
+                .any("/*rest", |_: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                })
+                .get(
+                    "/users",
+                    |_, _| async move { Ok(Response::new(String::new())) },
+                )
+                .build_and_check()
+                .err()
+                .unwrap();
+            assert_eq!(errors, vec!["/users".to_string()]);
+        });
+    }
+
+    #[test]
+    fn try_get() {
+        futures::executor::block_on(async move {
+            let router = Router::new()
+                .try_get("/files/:id/raw", |_, ctx| async move {
+                    Ok::<_, Infallible>(Response::new(ctx.param("id").unwrap().to_string()))
+                })
+                .unwrap();
+
+            let err = router
+                .clone()
+                .try_get("/files/*path", |_, _| async move {
+                    Ok(Response::new(String::new()))
+                })
+                .err()
+                .unwrap();
+            assert_eq!(err.template(), "/files/*path");
+            assert_eq!(err.conflicts_with(), Some("/files/:id/raw"));
+
+            let err = router
+                .clone()
+                .try_get("/files/:", |_, _| async move {
+                    Ok(Response::new(String::new()))
+                })
+                .err()
+                .unwrap();
+            assert_eq!(err.conflicts_with(), None);
+
+            let mut router = router;
+            let req = Request::get("/files/7/raw").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "7");
+        });
+    }
+
+    #[test]
+    fn nest() {
+        futures::executor::block_on(async move {
+            let api = Router::new()
+                .get("/users", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("users".to_string()))
+                })
+                .post("/users/:id", |_, ctx| async move {
+                    Ok(Response::new(format!("user {}", ctx.param("id").unwrap())))
+                });
+            let mut router = Router::new()
+                .get(
+                    "/",
+                    |_, _| async move { Ok(Response::new("home".to_string())) },
+                )
+                .get("/api/status", |_, _| async move {
+                    Ok(Response::new("ok".to_string()))
+                })
+                .nest("/api", api.clone())
+                .nest("/tenants/:tenant", api);
+
+            let req = Request::get("/api/users").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "users");
+
+            let req = Request::post("/tenants/acme/users/42")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "user 42");
+
+            let req = Request::get("/api/status").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "ok");
+        });
+    }
+
+    #[test]
+    fn nest_wildcard() {
+        futures::executor::block_on(async move {
+            let assets = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("index".to_string()))
+                })
+                .get("/*path", |_, ctx| async move {
+                    Ok(Response::new(ctx.param("path").unwrap().to_string()))
+                });
+            let mut router: Router<String, (), Infallible> = Router::new().nest("/static/", assets);
+
+            let req = Request::get("/static/css/site.css")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "css/site.css");
+
+            let req = Request::get("/static").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "index");
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "already has a GET handler for `/api/users`")]
+    fn nest_collision() {
+        let api = Router::new().get("/users", |_, _| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+
+        let _: Router<String, (), Infallible> = Router::new()
+            .get("/api/users", |_, _| async move {
+                Ok(Response::new(String::new()))
+            })
+            .nest("/api", api);
+    }
+
+    #[test]
+    #[should_panic(expected = "unable to add route `/users/:id` to router")]
+    fn merge_collision() {
+        let users = Router::new().get("/users/:id", |_, _| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+
+        let _: Router<String, (), Infallible> = Router::new()
+            .get("/users/:id", |_, _| async move {
+                Ok(Response::new(String::new()))
+            })
+            .merge(users);
+    }
+
+    #[test]
+    fn set_response_header() {
+        futures::executor::block_on(async move {
+            use http::{
+                header::{HeaderName, SET_COOKIE},
+                HeaderValue,
+            };
+
+            let mut router = Router::new()
+                .get("/", |_, ctx| async move {
+                    ctx.set_response_header(SET_COOKIE, HeaderValue::from_static("session=abc"));
+                    let resp = Response::builder()
+                        .header(SET_COOKIE, "theme=dark")
+                        .body(String::new())
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                })
+                .fallback(|_, ctx| async move {
+                    ctx.set_response_header(
+                        HeaderName::from_static("x-fallback"),
+                        HeaderValue::from_static("yes"),
+                    );
+                    Ok(Response::new(String::new()))
+                });
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            let cookies: Vec<_> = resp.headers().get_all(SET_COOKIE).iter().collect();
+            assert_eq!(cookies, ["theme=dark", "session=abc"]);
+
+            let req = Request::get("/missing").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()["x-fallback"], "yes");
+        });
+    }
+
+    #[test]
+    fn on() {
+        futures::executor::block_on(async move {
+            let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let counter = calls.clone();
+            let mut router = Router::new().on(
+                &[Method::GET, Method::POST],
+                "/search",
+                move |req: Request<String>, _| {
+                    counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    async move { Ok::<_, Infallible>(Response::new(req.method().to_string())) }
+                },
+            );
+
+            for method in [Method::GET, Method::POST] {
+                let req = Request::builder()
+                    .method(method.clone())
+                    .uri("/search")
+                    .body(String::new())
+                    .unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.body(), method.as_str());
+            }
+            assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+            let req = Request::put("/search").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(
+                resp.headers()[http::header::ALLOW],
+                "GET, HEAD, OPTIONS, POST"
+            );
+        });
+    }
+
+    #[test]
+    fn merge() {
+        futures::executor::block_on(async move {
+            let a = Router::new().get("/a", |_, _| async move {
+                Ok::<_, Infallible>(Response::new("a".to_string()))
+            });
+            let b = Router::new()
+                .post(
+                    "/b",
+                    |_, _| async move { Ok(Response::new("b".to_string())) },
+                )
+                .post("/a", |_, _| async move {
+                    Ok(Response::new("post a".to_string()))
+                });
+            let mut router = a.merge(b);
+
+            let req = Request::get("/a").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "a");
+            let req = Request::post("/a").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "post a");
+            let req = Request::post("/b").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "b");
+        });
+    }
+
+    #[test]
+    fn try_merge() {
+        futures::executor::block_on(async move {
+            let handler = |_, _| async move { Ok::<_, Infallible>(Response::new(String::new())) };
+            let router = || {
+                Router::new()
+                    .get("/a", handler)
+                    .get("/files/:id/raw", handler)
+            };
+
+            let other = Router::new()
+                .get("/a", handler)
+                .post("/b", handler)
+                .get("/files/*path", handler);
+            let conflicts = router().try_merge(other).err().unwrap();
+            assert_eq!(conflicts, ["/a", "/files/*path"]);
+
+            let other = Router::new().post("/a", handler).get("/b", handler);
+            let mut router = router().try_merge(other).unwrap();
+            let req = Request::post("/a").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn auto_json_error() {
+        futures::executor::block_on(async move {
+            #[derive(Debug, serde::Serialize)]
+            struct ApiError {
+                code: &'static str,
+                retry: bool,
+            }
+
+            let router = Router::new()
+                .get("/", |_, _| async move {
+                    Err::<Response<String>, _>(ApiError {
+                        code: "unavailable",
+                        retry: true,
+                    })
+                })
+                .get(
+                    "/ok",
+                    |_, _| async move { Ok(Response::new("ok".to_string())) },
+                );
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let err = router.clone().call(req).await.unwrap_err();
+            assert_eq!(err.code, "unavailable");
+
+            let mut router = router.auto_json_error(true);
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(
+                resp.headers()[http::header::CONTENT_TYPE],
+                "application/json"
+            );
+            assert_eq!(resp.body(), r#"{"code":"unavailable","retry":true}"#);
+
+            let req = Request::get("/ok").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "ok");
+        });
+    }
+
+    #[test]
+    fn route_builder() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .route("/items/:id")
+                .get(|_, ctx| async move {
+                    let id = ctx.param("id").unwrap();
+                    Ok::<_, Infallible>(Response::new(format!("item {id}")))
+                })
+                .delete(|_, ctx| async move {
+                    let id = ctx.param("id").unwrap();
+                    Ok(Response::new(format!("deleted {id}")))
+                })
+                .finish();
+
+            let req = Request::get("/items/1").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "item 1");
+
+            let req = Request::delete("/items/2").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "deleted 2");
+
+            let req = Request::put("/items/3").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(
+                resp.headers()[http::header::ALLOW],
+                "DELETE, GET, HEAD, OPTIONS"
+            );
+        });
+    }
+
+    #[test]
+    fn fallback_data() {
+        futures::executor::block_on(async move {
+            let mut router = Router::with_data("acme")
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                })
+                .fallback(|_, ctx| async move {
+                    assert_eq!(ctx.params().count(), 0);
+                    let resp = Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .header("content-type", "application/json")
+                        .body(format!(
+                            r#"{{"error":"not found","tenant":"{}"}}"#,
+                            ctx.data
+                        ))
+                        .unwrap();
+                    Ok(resp)
+                });
+
+            let req = Request::get("/missing/page").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(resp.body(), r#"{"error":"not found","tenant":"acme"}"#);
+        });
+    }
+
+    #[test]
+    fn content_negotiation() {
+        futures::executor::block_on(async move {
+            let handlers: HashMap<_, Handler<String, (), Infallible>> = HashMap::from([
+                (
+                    mime::APPLICATION_JSON,
+                    Handler::from(|_, ctx: RouteContext<()>| async move {
+                        let id = ctx.param("id").unwrap_or_default().to_owned();
+                        Ok(Response::new(format!(r#"{{"id":{id}}}"#)))
+                    }),
+                ),
+                (
+                    "application/xml".parse().unwrap(),
+                    Handler::from(|_, _| async move { Ok(Response::new("<user/>".to_string())) }),
+                ),
+            ]);
+            let mut router = Router::new().content_negotiation_handler("/users/:id", handlers);
+
+            let mut call = |accept: Option<&str>| {
+                let mut req = Request::get("/users/7");
+                if let Some(accept) = accept {
+                    req = req.header("accept", accept);
+                }
+                router.call(req.body(String::new()).unwrap())
+            };
+
+            let resp = call(Some("application/xml, application/json;q=0.5"))
+                .await
+                .unwrap();
+            assert_eq!(resp.body(), "<user/>");
+            assert_eq!(resp.headers()["vary"], "accept");
+
+            let resp = call(Some("text/html;q=0.9, */*;q=0.1")).await.unwrap();
+            assert_eq!(resp.body(), r#"{"id":7}"#);
+
+            let resp = call(None).await.unwrap();
+            assert_eq!(resp.body(), r#"{"id":7}"#);
+
+            let resp = call(Some("text/html")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+            assert_eq!(resp.headers()["vary"], "accept");
+        });
+    }
+
+    #[test]
+    fn method_not_allowed_handler() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                })
+                .post(
+                    "/users",
+                    |_, _| async move { Ok(Response::new(String::new())) },
+                )
+                .method_not_allowed(|_, ctx: RouteContext<()>| async move {
+                    let allowed: Vec<_> =
+                        ctx.allowed_methods().iter().map(Method::as_str).collect();
+                    let resp = Response::builder()
+                        .status(StatusCode::METHOD_NOT_ALLOWED)
+                        .header("content-type", "application/json")
+                        .body(format!(r#"{{"allowed":"{}"}}"#, allowed.join(",")))
+                        .unwrap();
+                    Ok(resp)
+                });
+
+            let req = Request::delete("/users").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(
+                resp.headers()[http::header::ALLOW],
+                "GET, HEAD, OPTIONS, POST"
+            );
+            assert_eq!(resp.body(), r#"{"allowed":"GET,HEAD,OPTIONS,POST"}"#);
+
+            // `OPTIONS` is still answered by the router, and unknown paths are still 404s.
+            let req = Request::options("/users").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert!(resp.body().is_empty());
+
+            let req = Request::delete("/posts").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+            // Routes that do handle the method don't see the allowed methods.
+            let mut router = Router::new().get("/", |_, ctx: RouteContext<()>| async move {
+                assert!(ctx.allowed_methods().is_empty());
+                Ok::<_, Infallible>(Response::new(String::new()))
+            });
+            let req = Request::get("/").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "i18n")]
+    fn language_negotiation() {
+        use language_tags::LanguageTag;
+
+        futures::executor::block_on(async move {
+            let handlers: HashMap<_, Handler<String, (), Infallible>> = HashMap::from([
+                (
+                    LanguageTag::parse("en").unwrap(),
+                    Handler::from(|_, _| async move { Ok(Response::new("Hello".to_string())) }),
+                ),
+                (
+                    LanguageTag::parse("pt-BR").unwrap(),
+                    Handler::from(|_, _| async move {
+                        let resp = Response::builder()
+                            .header("content-language", "pt")
+                            .body("Olá".to_string())
+                            .unwrap();
+                        Ok(resp)
+                    }),
+                ),
+            ]);
+            let mut router = Router::new().language_negotiation_handler("/greeting", handlers);
+
+            let mut call = |accept: Option<&str>| {
+                let mut req = Request::get("/greeting");
+                if let Some(accept) = accept {
+                    req = req.header("accept-language", accept);
+                }
+                router.call(req.body(String::new()).unwrap())
+            };
+
+            let resp = call(Some("en-US, en;q=0.9")).await.unwrap();
+            assert_eq!(resp.body(), "Hello");
+            assert_eq!(resp.headers()["content-language"], "en");
+            assert_eq!(resp.headers()["vary"], "accept-language");
+
+            // The handler's own `Content-Language` is kept.
+            let resp = call(Some("pt-br")).await.unwrap();
+            assert_eq!(resp.body(), "Olá");
+            assert_eq!(resp.headers()["content-language"], "pt");
+
+            let resp = call(None).await.unwrap();
+            assert_eq!(resp.body(), "Hello");
+
+            let resp = call(Some("fr, pt-PT")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_ACCEPTABLE);
+            assert_eq!(resp.headers()["vary"], "accept-language");
+        });
+    }
+
+    #[test]
+    fn rate_limit_by_api_key() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/reports", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                })
+                .rate_limit_by_api_key(2)
+                .cors_allow_origin(&["https://example.com"]);
+            let mut other = router.clone();
+
+            let req = |key: Option<&str>| {
+                let mut req = Request::get("/reports");
+                if let Some(key) = key {
+                    req = req.header("x-api-key", key);
+                }
+                req.body(String::new()).unwrap()
+            };
+
+            let resp = router.call(req(Some("alpha"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            // Clones of the router share the budget.
+            let resp = other.call(req(Some("alpha"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let resp = router.call(req(Some("alpha"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert!(resp.headers().contains_key(http::header::RETRY_AFTER));
+
+            let resp = router.call(req(Some("beta"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let resp = router.call(req(None)).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+            // CORS preflights don't carry credentials, so they aren't limited.
+            let preflight = Request::options("/reports")
+                .header("origin", "https://example.com")
+                .header("access-control-request-method", "GET")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(preflight).await.unwrap();
+            assert!(resp.status().is_success());
+        });
+    }
+
+    #[test]
+    fn separate_response_body() {
+        futures::executor::block_on(async move {
+            let mut router: Router<Bytes, (), Infallible, String> =
+                Router::new().post("/echo", |req: Request<Bytes>, _| async move {
+                    Ok(Response::new(
+                        String::from_utf8_lossy(req.body()).into_owned(),
+                    ))
+                });
+
+            let req = Request::post("/echo").body(Bytes::from("hello")).unwrap();
+            let resp: Response<String> = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "hello");
+
+            let req = Request::get("/missing").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+            assert_eq!(resp.body(), "");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "sessions")]
+    fn session() {
+        use http::header::{COOKIE, SET_COOKIE};
+
+        use crate::MemorySessionStore;
+
         futures::executor::block_on(async move {
-            let data = Arc::new(AtomicBool::new(false));
-            let mut router: Router<(), Arc<AtomicBool>, Infallible> =
-                Router::with_data(data.clone()).get("/example", |_req, ctx| async move {
-                    ctx.data.store(true, Ordering::SeqCst);
-                    Ok(Response::builder().body(()).unwrap())
+            let mut router: Router<String, (), Infallible> = Router::new()
+                .post("/login", |_, ctx| async move {
+                    ctx.session().set("user", 42).unwrap();
+                    Ok(Response::new(String::new()))
+                })
+                .get("/me", |_, ctx| async move {
+                    let user: Option<u32> = ctx.session().get("user");
+                    Ok(Response::new(format!("{user:?}")))
+                })
+                .post("/logout", |_, ctx| async move {
+                    ctx.session().destroy();
+                    Ok(Response::new(String::new()))
+                })
+                .with_session(MemorySessionStore::new(), [7; 32]);
+
+            let req = Request::get("/me").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "None");
+            assert!(!resp.headers().contains_key(SET_COOKIE));
+
+            let req = Request::post("/login").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            let cookie = resp.headers()[SET_COOKIE].to_str().unwrap();
+            let session = cookie.split(';').next().unwrap().to_owned();
+
+            let req = Request::get("/me")
+                .header(COOKIE, &session)
+                .body(String::new())
+                .unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "Some(42)");
+
+            let req = Request::post("/logout")
+                .header(COOKIE, &session)
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert!(resp.headers()[SET_COOKIE]
+                .to_str()
+                .unwrap()
+                .contains("Max-Age=0"));
+
+            let req = Request::get("/me")
+                .header(COOKIE, &session)
+                .body(String::new())
+                .unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "None");
+        });
+    }
+
+    #[test]
+    fn url_for() {
+        let handler = |_, _| async move { Ok(Response::new(String::new())) };
+        let files = Router::new().get_named("file", "/*path", handler);
+        let router: Router<String, (), Infallible> = Router::new()
+            .get_named("user", "/users/:id", handler)
+            .method_named(Method::PUT, "post", "/users/:id/posts/:post", handler)
+            .nest("/files/:owner", files);
+
+        assert_eq!(
+            router.url_for("user", &[("id", "42")]).as_deref(),
+            Some("/users/42")
+        );
+        assert_eq!(
+            router
+                .url_for("post", &[("post", "7"), ("id", "42"), ("extra", "1")])
+                .as_deref(),
+            Some("/users/42/posts/7")
+        );
+        assert_eq!(
+            router
+                .url_for("file", &[("owner", "alice"), ("path", "docs/a.txt")])
+                .as_deref(),
+            Some("/files/alice/docs/a.txt")
+        );
+        assert_eq!(router.url_for("post", &[("id", "42")]), None);
+        assert_eq!(router.url_for("missing", &[]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the route `/users/:id` already has that name")]
+    fn url_for_duplicate_name() {
+        let _: Router<String, (), Infallible> = Router::new()
+            .get_named("user", "/users/:id", |_, _| async move {
+                Ok(Response::new(String::new()))
+            })
+            .get_named("user", "/accounts/:id", |_, _| async move {
+                Ok(Response::new(String::new()))
+            });
+    }
+
+    #[test]
+    fn scope() {
+        futures::executor::block_on(async move {
+            let mut router = Router::with_data("acme")
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("home".to_string()))
+                })
+                .scope("/admin", |admin| {
+                    admin
+                        .get("/users", |_, ctx| async move {
+                            Ok(Response::new(format!("users of {}", ctx.data)))
+                        })
+                        .scope("/users/:id", |user| {
+                            user.delete("/", |_, ctx| async move {
+                                Ok(Response::new(format!(
+                                    "deleted {}",
+                                    ctx.param("id").unwrap()
+                                )))
+                            })
+                        })
                 });
 
-            let req = Request::builder()
-                .uri("/example")
-                .method(Method::GET)
-                .body(())
+            let req = Request::get("/admin/users").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "users of acme");
+
+            let req = Request::delete("/admin/users/42")
+                .body(String::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "deleted 42");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "home");
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "csrf")]
+    fn csrf_protection() {
+        futures::executor::block_on(async move {
+            let mut router: Router<Bytes, (), Infallible> = Router::new()
+                .get(
+                    "/form",
+                    |_, _| async move { Ok(Response::new(Bytes::new())) },
+                )
+                .delete("/posts/:id", |_, _| async move {
+                    Ok(Response::new(Bytes::from("deleted")))
+                })
+                .csrf_protection(&[7; 32]);
+
+            let req = Request::get("/form").body(Bytes::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+
+            let req = Request::delete("/posts/1").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+            let req = Request::get("/csrf-token").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()["content-type"], "application/json");
+            let cookie = resp.headers()["set-cookie"].to_str().unwrap();
+            let cookie = cookie.split(';').next().unwrap().to_owned();
+            let token = cookie.strip_prefix("csrf_token=").unwrap();
+
+            let req = Request::delete("/posts/1")
+                .header("cookie", &cookie)
+                .header("x-csrf-token", "forged")
+                .body(Bytes::new())
+                .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+            let req = Request::delete("/posts/1")
+                .header("cookie", &cookie)
+                .header("x-csrf-token", token)
+                .body(Bytes::new())
                 .unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "deleted");
+        });
+    }
 
+    #[test]
+    #[cfg(feature = "csrf")]
+    fn csrf_protection_with_catchall() {
+        futures::executor::block_on(async move {
+            let mut router: Router<Bytes, (), Infallible> = Router::new()
+                .get("/*rest", |_, _| async move {
+                    Ok(Response::new(Bytes::from("app")))
+                })
+                .csrf_protection(&[7; 32]);
+
+            let req = Request::get("/csrf-token").body(Bytes::new()).unwrap();
             let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()["content-type"], "application/json");
+
+            // The catchall route still answers every other path.
+            for path in ["/login", "/users/42"] {
+                let req = Request::get(path).body(Bytes::new()).unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.body(), "app");
+            }
+        });
+    }
+
+    #[test]
+    fn routes() {
+        let api = Router::new().put("/users/:id", |_, _| async move {
+            Ok::<_, Infallible>(Response::new(String::new()))
+        });
+        let router: Router<String, (), Infallible> = Router::new()
+            .get("/", |_, _| async move { Ok(Response::new(String::new())) })
+            .get(
+                "/users",
+                |_, _| async move { Ok(Response::new(String::new())) },
+            )
+            .delete(
+                "/users",
+                |_, _| async move { Ok(Response::new(String::new())) },
+            )
+            .any("/static/*path", |_, _| async move {
+                Ok(Response::new(String::new()))
+            })
+            .get("/static/*path", |_, _| async move {
+                Ok(Response::new(String::new()))
+            })
+            .nest("/api", api);
+
+        assert_eq!(
+            router.routes(),
+            vec![
+                ("/".to_string(), vec![Some(Method::GET)]),
+                (
+                    "/users".to_string(),
+                    vec![Some(Method::DELETE), Some(Method::GET)]
+                ),
+                ("/static/*path".to_string(), vec![Some(Method::GET), None]),
+                ("/api/users/:id".to_string(), vec![Some(Method::PUT)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn panic_handler() {
+        futures::executor::block_on(async move {
+            let mut router: Router<String, (), Infallible> = Router::new()
+                .get("/async", |_, _| async move { panic!("async panic") })
+                .get("/sync", |_, _| -> std::future::Ready<_> {
+                    panic!("sync panic")
+                })
+                .get(
+                    "/ok",
+                    |_, _| async move { Ok(Response::new("ok".to_string())) },
+                )
+                .with_panic_handler(|panic| {
+                    let message = panic.downcast_ref::<&str>().unwrap();
+                    Response::builder()
+                        .status(StatusCode::INTERNAL_SERVER_ERROR)
+                        .body(message.to_string())
+                        .unwrap()
+                });
+
+            for path in ["/async", "/sync"] {
+                let req = Request::get(path).body(String::new()).unwrap();
+                let resp = router.call(req).await.unwrap();
+                assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+                assert_eq!(resp.body(), &format!("{} panic", &path[1..]));
+            }
+
+            let req = Request::get("/ok").body(String::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().body(), "ok");
+        });
+    }
+
+    #[test]
+    fn debug_handler() {
+        async fn create_user(
+            _: Request<()>,
+            _: RouteContext<()>,
+        ) -> Result<Response<()>, Infallible> {
+            Ok(Response::new(()))
+        }
+
+        let router = Router::new()
+            .get("/users/:id", |_, _| async move { Ok(Response::new(())) })
+            .post("/users/:id", create_user)
+            .any("/*rest", create_user);
+
+        let description = router.debug_handler("/users/:id", Method::POST).unwrap();
+        assert!(description.ends_with("create_user"), "{description}");
+        let description = router.debug_handler("/users/42", Method::GET).unwrap();
+        assert!(
+            description.contains("debug_handler::{{closure}}"),
+            "{description}"
+        );
+        assert_eq!(router.debug_handler("/users/42", Method::PUT), None);
+
+        let description = router.debug_handler("/posts", Method::PUT).unwrap();
+        assert!(description.ends_with("create_user"), "{description}");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn watcher() {
+        use crate::RouteChangeEvent::{Added, Removed};
+
+        let router: Router<(), (), Infallible> = Router::new();
+        let mut watcher = router.watcher();
+
+        let router = router
+            .get("/users", |_, _| async move { Ok(Response::new(())) })
+            .any("/users", |_, _| async move { Ok(Response::new(())) });
+        assert_eq!(
+            watcher.changed().await.unwrap(),
+            vec![
+                Added("/users".into(), Some(Method::GET)),
+                Added("/users".into(), None),
+            ]
+        );
+
+        let router = router.get("/users", |_, _| async move { Ok(Response::new(())) });
+        assert_eq!(
+            watcher.changed().await.unwrap(),
+            vec![
+                Removed("/users".into(), Some(Method::GET)),
+                Added("/users".into(), Some(Method::GET)),
+            ]
+        );
+
+        drop(router);
+        assert_eq!(watcher.changed().await, None);
+    }
+
+    #[cfg(feature = "actix-web")]
+    #[test]
+    fn into_actix_service() {
+        use actix_web::{test, App};
+
+        actix_web::rt::System::new().block_on(async move {
+            let router: Router<Bytes, (), Infallible> =
+                Router::new().post("/echo/:name", |req: Request<Bytes>, ctx| async move {
+                    let body = format!(
+                        "{} {}",
+                        ctx.param("name").unwrap(),
+                        String::from_utf8_lossy(req.body())
+                    );
+                    Ok(Response::builder()
+                        .header(CONTENT_TYPE, "text/plain")
+                        .body(Bytes::from(body))
+                        .unwrap())
+                });
+            let app = App::new().default_service(router.into_actix_service());
+            let app = test::init_service(app).await;
+
+            let req = test::TestRequest::post()
+                .uri("/echo/alice")
+                .set_payload("hello")
+                .to_request();
+            let resp = test::call_service(&app, req).await;
             assert_eq!(resp.status(), 200);
-            assert!(data.load(Ordering::SeqCst));
+            assert_eq!(resp.headers().get(CONTENT_TYPE).unwrap(), "text/plain");
+            assert_eq!(test::read_body(resp).await, "alice hello");
+
+            let req = test::TestRequest::get().uri("/missing").to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 404);
         });
     }
 
     #[test]
-    fn receives_data_async() {
+    fn health_endpoint() {
+        use crate::HealthStatus;
+
         futures::executor::block_on(async move {
-            let data = Arc::new(AtomicBool::new(false));
-            let mut router: Router<_, _, Infallible> =
-                Router::with_data(data.clone()).get("/example", |_req, ctx| async move {
-                    ctx.data.store(true, Ordering::SeqCst);
-                    Ok(Response::builder().body(()).unwrap())
+            let healthy = Arc::new(AtomicBool::new(true));
+            let check = healthy.clone();
+            let mut router: Router<Bytes, (), Infallible> =
+                Router::new().health_endpoint("/healthz", move || {
+                    if check.load(Ordering::SeqCst) {
+                        HealthStatus::Healthy
+                    } else {
+                        HealthStatus::Unhealthy("database unreachable".into())
+                    }
                 });
 
-            let req = Request::builder()
-                .uri("/example")
-                .method(Method::GET)
+            let req = Request::get("/healthz").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+            assert_eq!(resp.body(), r#"{"status":"healthy"}"#);
+
+            healthy.store(false, Ordering::SeqCst);
+            let req = Request::get("/healthz").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(resp.body(), r#"{"status":"unhealthy"}"#);
+        });
+    }
+
+    #[test]
+    fn readiness_endpoint() {
+        use crate::HealthStatus;
+
+        futures::executor::block_on(async move {
+            let ready = Arc::new(AtomicBool::new(false));
+            let check = ready.clone();
+            let mut router: Router<Bytes, (), Infallible> = Router::new()
+                .health_endpoint("/healthz", || HealthStatus::Healthy)
+                .readiness_endpoint("/readyz", move || {
+                    if check.load(Ordering::SeqCst) {
+                        HealthStatus::Healthy
+                    } else {
+                        HealthStatus::Unhealthy("warming up".into())
+                    }
+                });
+
+            let req = Request::get("/healthz").body(Bytes::new()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+
+            let req = Request::get("/readyz").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+            assert_eq!(resp.body(), r#"{"status":"unhealthy"}"#);
+
+            ready.store(true, Ordering::SeqCst);
+            let req = Request::get("/readyz").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.body(), r#"{"status":"healthy"}"#);
+        });
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_endpoint() {
+        futures::executor::block_on(async move {
+            let mut router: Router<Bytes, (), Infallible> = Router::new()
+                .metrics_endpoint("/metrics")
+                .get("/users/:id", |_, _| async move {
+                    Ok(Response::new(Bytes::new()))
+                })
+                .any("/*rest", |_, _| async move {
+                    Ok(Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Bytes::new())
+                        .unwrap())
+                });
+
+            for uri in ["/users/1", "/users/2", "/missing"] {
+                let req = Request::get(uri).body(Bytes::new()).unwrap();
+                router.call(req).await.unwrap();
+            }
+            let req = Request::delete("/missing").body(Bytes::new()).unwrap();
+            router.call(req).await.unwrap();
+
+            let req = Request::get("/metrics").body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.headers()[CONTENT_TYPE], "text/plain; version=0.0.4");
+
+            let body = String::from_utf8(resp.into_body().to_vec()).unwrap();
+            for line in [
+                r#"router_requests_total{route="/users/:id",method="GET",status="200"} 2"#,
+                r#"router_requests_total{route="/*rest",method="GET",status="404"} 1"#,
+                r#"router_requests_total{route="/*rest",method="DELETE",status="404"} 1"#,
+                r#"router_request_duration_seconds_count{route="/users/:id",method="GET"} 2"#,
+            ] {
+                assert!(
+                    body.lines().any(|l| l == line),
+                    "missing `{line}` in:\n{body}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn with_connection_pool() {
+        futures::executor::block_on(async move {
+            #[derive(Clone)]
+            struct Pool(Arc<AtomicUsize>);
+
+            let pool = Pool(Arc::new(AtomicUsize::new(0)));
+            let mut router =
+                Router::with_connection_pool(pool.clone()).get("/", |_, ctx| async move {
+                    ctx.db().0.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, Infallible>(Response::new(()))
+                });
+
+            router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(pool.0.load(Ordering::SeqCst), 2);
+        });
+    }
+
+    #[test]
+    fn display_route_context() {
+        futures::executor::block_on(async move {
+            let mut router = Router::with_data(42)
+                .get("/users/:id", |_, ctx| async move {
+                    Ok::<_, Infallible>(Response::new(ctx.to_string()))
+                })
+                .get("/", |_, ctx| async move {
+                    Ok::<_, Infallible>(Response::new(ctx.to_string()))
+                });
+
+            let req = Request::get("/users/7").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "route=/users/:id params={id=7} data=42");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "route=/ params={} data=42");
+        });
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn logging_format() {
+        use crate::LogFormat;
+
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new("hello".to_string()))
+                })
+                .with_logging_format(LogFormat::Json);
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.body(), "hello");
+
+            let req = Request::get("/missing").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        });
+    }
+
+    #[cfg(feature = "sentry")]
+    #[test]
+    fn sentry_integration() {
+        use std::io;
+
+        let events = sentry_core::test::with_captured_events(|| {
+            futures::executor::block_on(async move {
+                let mut router = Router::new()
+                    .get("/users/:id", |_, _| async move {
+                        Err::<Response<()>, _>(io::Error::other("database down"))
+                    })
+                    .get("/", |_, _| async move { Ok(Response::new(())) })
+                    .with_sentry_integration();
+
+                let req = Request::get("/").body(()).unwrap();
+                assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+
+                let req = Request::get("/users/7").body(()).unwrap();
+                assert!(router.call(req).await.is_err());
+            });
+        });
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.transaction.as_deref(), Some("/users/:id"));
+        assert_eq!(
+            event.exception.values[0].value.as_deref(),
+            Some("database down")
+        );
+        let breadcrumbs: Vec<_> = event.breadcrumbs.iter().map(|b| &b.message).collect();
+        assert_eq!(breadcrumbs, [&Some("GET /users/:id".to_string())]);
+    }
+
+    #[cfg(feature = "datadog")]
+    #[test]
+    fn datadog_apm() {
+        use crate::DatadogTrace;
+
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users/:id", |req: Request<()>, _| async move {
+                    let trace = req.extensions().get::<DatadogTrace>().unwrap();
+                    let mut outbound = Request::new(());
+                    trace.inject(outbound.headers_mut());
+
+                    let resp = Response::builder()
+                        .header("trace-id", trace.trace_id())
+                        .header("parent-id", format!("{:?}", trace.parent_id()))
+                        .header("resource", trace.resource())
+                        .header("service", trace.service())
+                        .header(
+                            "outbound-parent-id",
+                            &outbound.headers()["x-datadog-parent-id"],
+                        )
+                        .header("span-id", trace.span_id())
+                        .body(())
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                })
+                .with_datadog_apm("users-api");
+
+            let req = Request::get("/users/7")
+                .header("x-datadog-trace-id", "123")
+                .header("x-datadog-parent-id", "456")
                 .body(())
                 .unwrap();
+            let resp = router.call(req).await.unwrap();
+            let headers = resp.headers();
+            assert_eq!(headers["trace-id"], "123");
+            assert_eq!(headers["parent-id"], "Some(456)");
+            assert_eq!(headers["resource"], "GET /users/:id");
+            assert_eq!(headers["service"], "users-api");
+            assert_eq!(headers["outbound-parent-id"], headers["span-id"]);
+        });
+    }
+
+    #[test]
+    fn auto_content_length() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(Bytes::from_static(b"hello")))
+                })
+                .get("/explicit", |_, _| async move {
+                    let resp = Response::builder()
+                        .header(http::header::CONTENT_LENGTH, "2")
+                        .body(Bytes::from_static(b"hi"))
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                })
+                .get("/empty", |_, _| async move {
+                    let resp = Response::builder()
+                        .status(StatusCode::NO_CONTENT)
+                        .body(Bytes::new())
+                        .unwrap();
+                    Ok::<_, Infallible>(resp)
+                })
+                .head("/", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(Bytes::new()))
+                })
+                .auto_content_length(true);
+
+            let length =
+                |resp: &Response<Bytes>| resp.headers().get(http::header::CONTENT_LENGTH).cloned();
+
+            let resp = router
+                .call(Request::get("/").body(Bytes::new()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(length(&resp).unwrap(), "5");
 
+            let req = Request::get("/explicit").body(Bytes::new()).unwrap();
             let resp = router.call(req).await.unwrap();
-            assert_eq!(resp.status(), 200);
-            assert!(data.load(Ordering::SeqCst));
+            assert_eq!(length(&resp).unwrap(), "2");
+
+            let req = Request::get("/empty").body(Bytes::new()).unwrap();
+            assert_eq!(length(&router.call(req).await.unwrap()), None);
+
+            let req = Request::head("/").body(Bytes::new()).unwrap();
+            assert_eq!(length(&router.call(req).await.unwrap()), None);
+        });
+    }
+
+    #[test]
+    fn require_content_type() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .post("/users", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(()))
+                })
+                .put("/users", |_, _| async move {
+                    Ok::<_, Infallible>(Response::new(()))
+                })
+                .require_content_type(Method::POST, mime::APPLICATION_JSON);
+
+            let post = |content_type: Option<&str>| {
+                let mut req = Request::post("/users");
+                if let Some(content_type) = content_type {
+                    req = req.header(http::header::CONTENT_TYPE, content_type);
+                }
+                req.body(()).unwrap()
+            };
+
+            let resp = router.call(post(Some("application/json"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let resp = router.call(post(Some("text/html"))).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+            let resp = router.call(post(None)).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+            let req = Request::put("/users").body(()).unwrap();
+            assert_eq!(router.call(req).await.unwrap().status(), StatusCode::OK);
+        });
+    }
+
+    #[test]
+    fn any_trailing_slash() {
+        futures::executor::block_on(async move {
+            let mut router = Router::new()
+                .get("/users", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.uri().to_string()))
+                })
+                .get("/posts/", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.uri().to_string()))
+                })
+                .get("/", |req: Request<String>, _| async move {
+                    Ok::<_, Infallible>(Response::new(req.uri().to_string()))
+                });
+
+            let req = Request::get("/users/").body(String::new()).unwrap();
+            assert_eq!(
+                router.call(req).await.unwrap().status(),
+                StatusCode::NOT_FOUND
+            );
+
+            let mut router = router.any_trailing_slash(true);
+            for (uri, expected) in [
+                ("/users", "/users"),
+                ("/users/", "/users"),
+                ("/posts", "/posts"),
+                ("/posts/", "/posts"),
+                ("/", "/"),
+            ] {
+                let req = Request::get(uri).body(String::new()).unwrap();
+                assert_eq!(router.call(req).await.unwrap().body(), expected, "{uri}");
+            }
+        });
+    }
+
+    #[test]
+    fn base_path() {
+        futures::executor::block_on(async move {
+            let mut router: Router<String, (), Infallible> = Router::new()
+                .get("/", |_, ctx| async move {
+                    Ok(Response::new(ctx.base_path().into()))
+                })
+                .fallback(|req, ctx| async move {
+                    let body = format!("path `{}{}` not found", ctx.base_path(), req.uri().path());
+                    Ok(Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(body)
+                        .unwrap())
+                })
+                .with_base_path("/api/");
+
+            let req = Request::get("/").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.body(), "/api");
+
+            let req = Request::get("/nonexistent").body(String::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 404);
+            assert_eq!(resp.body(), "path `/api/nonexistent` not found");
+        });
+    }
+
+    #[cfg(feature = "fs")]
+    #[tokio::test]
+    async fn serve_dir() {
+        use http::header::{CONTENT_RANGE, ETAG, IF_NONE_MATCH, RANGE};
+
+        let root = std::env::temp_dir().join(format!("sync-serve-dir-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("css")).unwrap();
+        std::fs::write(root.join("css/site.css"), "body { color: red; }").unwrap();
+        std::fs::write(root.parent().unwrap().join("secret.txt"), "secret").unwrap();
+
+        let mut router: Router<Bytes, (), Infallible> =
+            Router::new().serve_dir("/static/*file", &root);
+
+        let req = Request::get("/static/css/site.css")
+            .body(Bytes::new())
+            .unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "text/css");
+        assert_eq!(resp.body(), "body { color: red; }");
+        let etag = resp.headers()[ETAG].clone();
+
+        let req = Request::get("/static/css/site.css")
+            .header(IF_NONE_MATCH, etag)
+            .body(Bytes::new())
+            .unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), 304);
+
+        let req = Request::get("/static/css/site.css")
+            .header(RANGE, "bytes=0-3")
+            .body(Bytes::new())
+            .unwrap();
+        let resp = router.call(req).await.unwrap();
+        assert_eq!(resp.status(), 206);
+        assert_eq!(resp.headers()[CONTENT_RANGE], "bytes 0-3/20");
+        assert_eq!(resp.body(), "body");
+
+        for path in [
+            "/static/../secret.txt",
+            "/static/missing.css",
+            "/static/css",
+        ] {
+            let req = Request::get(path).body(Bytes::new()).unwrap();
+            let resp = router.call(req).await.unwrap();
+            assert_eq!(resp.status(), 404);
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn router_is_send_and_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        assert_send::<Router<(), (), Infallible>>();
+        assert_sync::<Router<(), (), Infallible>>();
+        assert_send::<crate::ResponseFuture<(), Infallible>>();
+    }
+
+    #[tokio::test]
+    async fn buffered_router() {
+        use tower::{buffer::Buffer, ServiceExt};
+
+        let router: Router<(), (), Infallible> =
+            Router::new().get("/", |_, _| async move { Ok(Response::new(())) });
+        let buffered = Buffer::new(router, 8);
+
+        let req = Request::get("/").body(()).unwrap();
+        let resp = buffered.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[test]
+    fn graceful_drain() {
+        futures::executor::block_on(async move {
+            let (mut router, signal, mut watcher) = Router::<(), (), Infallible>::new()
+                .get("/", |_, _| async move { Ok(Response::new(())) })
+                .with_graceful_drain();
+
+            let in_flight = router.call(Request::get("/").body(()).unwrap());
+            assert_eq!(signal.in_flight(), 1);
+            assert!(futures::poll!(&mut watcher).is_pending());
+
+            signal.drain();
+            let resp = router
+                .call(Request::get("/").body(()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), 503);
+
+            in_flight.await.unwrap();
+            assert_eq!(signal.in_flight(), 0);
+            watcher.await;
         });
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn graceful_timeout() {
+        let (mut router, shutdown) = Router::<(), (), Infallible>::new()
+            .get("/slow", |_, _| async move {
+                std::future::pending::<()>().await;
+                Ok(Response::new(()))
+            })
+            .graceful_timeout(Duration::from_millis(10));
+
+        let in_flight = router.call(Request::get("/slow").body(()).unwrap());
+        let (resp, ()) = futures::join!(in_flight, shutdown.shutdown());
+        assert_eq!(resp.unwrap().status(), 503);
+
+        let resp = router
+            .call(Request::get("/slow").body(()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 503);
+    }
 }
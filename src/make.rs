@@ -0,0 +1,152 @@
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures_util::future::{ready, Ready};
+use tower::Service;
+
+use crate::{RequestContext, Router};
+
+type AugmentFn<Data, ConnInfo> = dyn Fn(&Data, &ConnInfo) -> Data + Send + Sync;
+type DescribeFn<ConnInfo> = dyn Fn(RequestContext, &ConnInfo) -> RequestContext + Send + Sync;
+
+/// A `tower::MakeService` that creates a router for every connection, with data
+/// derived from the connection.
+///
+/// Wrapping a router in `tower::make::Shared` hands every connection the same data, which leaves
+/// handlers no way to learn about the connection itself, e.g. the client's address. A
+/// [`MakeRouter`] instead calls `augment` with the router's data and the connection's info, and
+/// serves the connection with a router whose handlers see the result. The route table is shared
+/// between every connection's router.
+///
+/// Every connection also gets a [`RequestContext`] with an ID of its own, which can describe
+/// the connection further with [`MakeRouter::request_context`].
+///
+/// With hyper, `ConnInfo` is the connection type, e.g. `hyper::server::conn::AddrStream`.
+///
+/// # Example
+/// ```no_run
+/// # use tokio::runtime::Builder;
+/// # let rt  = Builder::new_multi_thread().enable_all().build().unwrap();
+/// # rt.block_on(async {
+/// use std::{convert::Infallible, net::SocketAddr};
+///
+/// use http::Response;
+/// use hyper::{server::conn::AddrStream, Body, Server};
+/// use router_service::{MakeRouter, Router};
+///
+/// let router = Router::with_data(None::<SocketAddr>).get("/", |_, ctx| async move {
+///     let body = format!("hello, {}", ctx.data.unwrap());
+///     Ok::<_, Infallible>(Response::new(Body::from(body)))
+/// });
+/// let make_router = MakeRouter::new(router, |_, conn: &AddrStream| Some(conn.remote_addr()));
+///
+/// let addr = ([127, 0, 0, 1], 3030).into();
+/// Server::bind(&addr)
+///     .serve(make_router)
+///     .await
+///     .expect("error running server");
+/// # });
+/// ```
+pub struct MakeRouter<Body, Data, Error, ConnInfo, ResBody = Body> {
+    router: Router<Body, Data, Error, ResBody>,
+    augment: Arc<AugmentFn<Data, ConnInfo>>,
+    describe: Option<Arc<DescribeFn<ConnInfo>>>,
+    connections: Arc<AtomicU64>,
+}
+
+impl<Body, Data, Error, ConnInfo, ResBody> MakeRouter<Body, Data, Error, ConnInfo, ResBody> {
+    /// Creates a [`MakeRouter`] whose routers' data is computed by `augment` from `router`'s data
+    /// and each connection's info.
+    pub fn new<F>(router: Router<Body, Data, Error, ResBody>, augment: F) -> Self
+    where
+        F: Fn(&Data, &ConnInfo) -> Data + Send + Sync + 'static,
+    {
+        Self {
+            router,
+            augment: Arc::new(augment),
+            describe: None,
+            connections: Default::default(),
+        }
+    }
+
+    /// Fills in the [`RequestContext`] of every connection with `describe`, called with a context
+    /// holding just the connection's ID and the connection's info.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{convert::Infallible, net::SocketAddr};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::{MakeRouter, Router};
+    ///
+    /// let router = Router::new().get("/", |_, ctx| async move {
+    ///     let conn = ctx.request_context();
+    ///     let body = format!("#{} from {}", conn.connection_id(), conn.remote_addr().unwrap());
+    ///     Ok::<_, Infallible>(Response::new(body))
+    /// });
+    /// let mut make_router = MakeRouter::new(router, |_, _: &SocketAddr| ())
+    ///     .request_context(|ctx, addr| ctx.with_remote_addr(*addr));
+    ///
+    /// let addr = SocketAddr::from(([127, 0, 0, 1], 50000));
+    /// let mut router = make_router.call(&addr).await.unwrap();
+    ///
+    /// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.body(), "#1 from 127.0.0.1:50000");
+    /// # });
+    /// ```
+    pub fn request_context<F>(mut self, describe: F) -> Self
+    where
+        F: Fn(RequestContext, &ConnInfo) -> RequestContext + Send + Sync + 'static,
+    {
+        self.describe = Some(Arc::new(describe));
+        self
+    }
+}
+
+impl<Body, Data, Error, ConnInfo, ResBody> Clone
+    for MakeRouter<Body, Data, Error, ConnInfo, ResBody>
+{
+    fn clone(&self) -> Self {
+        Self {
+            router: self.router.clone(),
+            augment: self.augment.clone(),
+            describe: self.describe.clone(),
+            connections: self.connections.clone(),
+        }
+    }
+}
+
+impl<'a, Body, Data, Error, ConnInfo, ResBody> Service<&'a ConnInfo>
+    for MakeRouter<Body, Data, Error, ConnInfo, ResBody>
+{
+    type Response = Router<Body, Data, Error, ResBody>;
+
+    type Error = Infallible;
+
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, conn: &'a ConnInfo) -> Self::Future {
+        let mut router = self.router.clone();
+        router.data = Arc::new((self.augment)(&self.router.data, conn));
+
+        let ctx = RequestContext::new(self.connections.fetch_add(1, Ordering::Relaxed) + 1);
+        let ctx = match &self.describe {
+            Some(describe) => describe(ctx, conn),
+            None => ctx,
+        };
+        router.request_context = Some(Arc::new(ctx));
+        ready(Ok(router))
+    }
+}
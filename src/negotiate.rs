@@ -0,0 +1,217 @@
+use std::cmp::Reverse;
+
+#[cfg(feature = "i18n")]
+use http::header::ACCEPT_LANGUAGE;
+use http::{header::ACCEPT, HeaderMap, HeaderName};
+#[cfg(feature = "i18n")]
+use language_tags::LanguageTag;
+use mime::Mime;
+
+/// Picks the media type in `available` that the client prefers according to the `Accept`
+/// header in `headers`, for
+/// [`Router::content_negotiation_handler`](crate::Router::content_negotiation_handler), returning
+/// its index, or `None` if the client accepts none of them.
+///
+/// Each available type gets the quality of the most specific media range matching it, so
+/// `text/html;q=0.1, text/*` ranks `text/html` below `text/plain`. Ties go to the type whose
+/// range comes first in the header, then to the first type in `available`. Requests without an
+/// `Accept` header accept any type. Malformed ranges are ignored, as are parameters other than
+/// `q`.
+pub(crate) fn negotiate<'a>(
+    headers: &HeaderMap,
+    available: impl IntoIterator<Item = &'a Mime>,
+) -> Option<usize> {
+    let ranges = ranges(headers, ACCEPT, "*/*", |range| range.parse::<Mime>().ok());
+    best(&ranges, available, specificity)
+}
+
+/// Picks the language in `available` that the client prefers according to the `Accept-Language`
+/// header in `headers`, for
+/// [`Router::language_negotiation_handler`](crate::Router::language_negotiation_handler),
+/// returning its index, or `None` if the client accepts none of them.
+///
+/// Ranges match tags as in the basic filtering of RFC 4647, section 3.3.1: `en` matches `en` and
+/// `en-GB`, but `en-GB` doesn't match `en`. Qualities and ties are handled as in [`negotiate`].
+#[cfg(feature = "i18n")]
+pub(crate) fn negotiate_language<'a>(
+    headers: &HeaderMap,
+    available: impl IntoIterator<Item = &'a LanguageTag>,
+) -> Option<usize> {
+    let ranges = ranges(headers, ACCEPT_LANGUAGE, "*", |range| {
+        Some(range.to_ascii_lowercase())
+    });
+    best(&ranges, available, |range, tag| {
+        language_specificity(range, tag.as_str())
+    })
+}
+
+/// Parses the ranges listed in `header`, along with their quality, or returns `any` if the
+/// request doesn't have the header.
+fn ranges<R>(
+    headers: &HeaderMap,
+    header: HeaderName,
+    any: &str,
+    parse: impl Fn(&str) -> Option<R>,
+) -> Vec<(R, f32)> {
+    if !headers.contains_key(&header) {
+        return parse(any).map(|any| (any, 1.0)).into_iter().collect();
+    }
+
+    let parse_range = |range: &str| {
+        let mut parts = range.split(';').map(str::trim);
+        let range = parse(parts.next()?)?;
+        let quality = match parts.find_map(|param| param.strip_prefix("q=")) {
+            Some(quality) => quality.parse::<f32>().ok()?,
+            None => 1.0,
+        };
+        (0.0..=1.0).contains(&quality).then_some((range, quality))
+    };
+    headers
+        .get_all(header)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_range)
+        .collect()
+}
+
+/// Finds the index of the item in `available` with the highest quality, taken from the most
+/// specific of `ranges` matching it.
+fn best<R, T>(
+    ranges: &[(R, f32)],
+    available: impl IntoIterator<Item = T>,
+    specificity: impl Fn(&R, &T) -> Option<usize>,
+) -> Option<usize> {
+    let mut best: Option<(usize, f32, usize)> = None;
+    for (index, item) in available.into_iter().enumerate() {
+        let matching = ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(position, (range, quality))| {
+                Some((specificity(range, &item)?, position, *quality))
+            })
+            .min_by_key(|(specificity, position, _)| (Reverse(*specificity), *position));
+        let (_, position, quality) = match matching {
+            Some(matching) if matching.2 > 0.0 => matching,
+            _ => continue,
+        };
+
+        let better = match best {
+            Some((_, best_quality, best_position)) => {
+                quality > best_quality || (quality == best_quality && position < best_position)
+            }
+            None => true,
+        };
+        if better {
+            best = Some((index, quality, position));
+        }
+    }
+    best.map(|(index, _, _)| index)
+}
+
+/// How specifically `range` matches `mime`: 2 for the same type and subtype, 1 for `type/*` and 0
+/// for `*/*`, or `None` if it doesn't match.
+fn specificity(range: &Mime, mime: &&Mime) -> Option<usize> {
+    if range.type_() == mime::STAR {
+        return Some(0);
+    }
+    if range.type_() != mime.type_() {
+        return None;
+    }
+    if range.subtype() == mime::STAR {
+        return Some(1);
+    }
+    (range.subtype() == mime.subtype() && range.suffix() == mime.suffix()).then_some(2)
+}
+
+/// How specifically the lowercase `range` matches `tag`: the number of subtags in the range, or 0
+/// for `*`, or `None` if it doesn't match.
+#[cfg(feature = "i18n")]
+fn language_specificity(range: &str, tag: &str) -> Option<usize> {
+    if range == "*" {
+        return Some(0);
+    }
+    let tag = tag.to_ascii_lowercase();
+    let matches = match tag.strip_prefix(range) {
+        Some(rest) => rest.is_empty() || rest.starts_with('-'),
+        None => false,
+    };
+    matches.then(|| range.split('-').count())
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{HeaderMap, HeaderValue};
+    use mime::Mime;
+
+    use super::negotiate;
+    #[cfg(feature = "i18n")]
+    use super::negotiate_language;
+
+    #[test]
+    fn negotiate_accept() {
+        let available: Vec<Mime> = vec![
+            mime::APPLICATION_JSON,
+            "application/xml".parse().unwrap(),
+            mime::TEXT_HTML,
+        ];
+        let pick = |accept: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(accept) = accept {
+                headers.insert("accept", HeaderValue::from_static(accept));
+            }
+            negotiate(&headers, &available).map(|index| available[index].essence_str())
+        };
+
+        assert_eq!(pick(None), Some("application/json"));
+        assert_eq!(pick(Some("*/*")), Some("application/json"));
+        assert_eq!(pick(Some("text/html")), Some("text/html"));
+        assert_eq!(
+            pick(Some("application/xml;q=0.9, text/html;q=0.8")),
+            Some("application/xml")
+        );
+        assert_eq!(pick(Some("text/html, application/json")), Some("text/html"));
+        assert_eq!(
+            pick(Some("text/*;q=0.5, application/json;q=0.4")),
+            Some("text/html")
+        );
+        assert_eq!(
+            pick(Some("application/*, application/json;q=0")),
+            Some("application/xml")
+        );
+        assert_eq!(
+            pick(Some("TEXT/HTML;charset=utf-8;q=0.2, nonsense, image/png")),
+            Some("text/html")
+        );
+        assert_eq!(pick(Some("image/png")), None);
+        assert_eq!(pick(Some("*/*;q=0")), None);
+        assert_eq!(pick(Some("text/html;q=2")), None);
+    }
+
+    #[test]
+    #[cfg(feature = "i18n")]
+    fn negotiate_accept_language() {
+        let available: Vec<_> = ["en", "en-GB", "fr", "zh-Hant"]
+            .into_iter()
+            .map(|tag| language_tags::LanguageTag::parse(tag).unwrap())
+            .collect();
+        let pick = |accept: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(accept) = accept {
+                headers.insert("accept-language", HeaderValue::from_static(accept));
+            }
+            negotiate_language(&headers, &available).map(|index| available[index].as_str())
+        };
+
+        assert_eq!(pick(None), Some("en"));
+        assert_eq!(pick(Some("*")), Some("en"));
+        assert_eq!(pick(Some("fr-CH, fr;q=0.9, en;q=0.8")), Some("fr"));
+        assert_eq!(pick(Some("en-gb")), Some("en-GB"));
+        assert_eq!(pick(Some("en;q=0.5, en-GB;q=0.2")), Some("en"));
+        assert_eq!(pick(Some("zh")), Some("zh-Hant"));
+        assert_eq!(pick(Some("de, *;q=0.1")), Some("en"));
+        assert_eq!(pick(Some("fr;q=0, *")), Some("en"));
+        assert_eq!(pick(Some("de, en-US")), None);
+        assert_eq!(pick(Some("english")), None);
+    }
+}
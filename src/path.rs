@@ -0,0 +1,232 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use http::{uri::PathAndQuery, Request, Uri};
+
+use crate::route::{check_conflicts, check_template, RoutingConflictError};
+
+/// Removes the trailing slashes from the path of `req`'s URI, e.g. turning `/users/?page=2` into
+/// `/users?page=2`. The root path is left alone.
+pub(crate) fn trim_trailing_slash<Body>(mut req: Request<Body>) -> Request<Body> {
+    let path = req.uri().path();
+    if path.len() <= 1 || !path.ends_with('/') {
+        return req;
+    }
+
+    let trimmed = match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    };
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{trimmed}?{query}"),
+        None => trimmed.to_owned(),
+    };
+
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+    if let Ok(uri) = Uri::from_parts(parts) {
+        *req.uri_mut() = uri;
+    }
+    req
+}
+
+/// Returns the path `req` is routed by: the path of its URI, or `/` for URIs without one, like
+/// the authority of a `CONNECT` request.
+pub(crate) fn route_path<Body>(req: &Request<Body>) -> &str {
+    match req.uri().path() {
+        "" => "/",
+        path => path,
+    }
+}
+
+/// A validated route path template, e.g. `/users/:id` or `/static/*file`.
+///
+/// Parsing a [`RoutePath`] checks that the template can be registered with a router, which lets
+/// route tables loaded from configuration files be validated before any routes are inserted.
+///
+/// # Example
+/// ```
+/// use router_service::RoutePath;
+///
+/// let path: RoutePath = "/users/:id".parse().unwrap();
+/// assert_eq!(path.as_str(), "/users/:id");
+///
+/// assert!("users//:id".parse::<RoutePath>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RoutePath(String);
+
+impl RoutePath {
+    /// Returns the path template as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Checks that `paths` can all be registered with the same router, in order.
+    ///
+    /// Registering a template that conflicts with an earlier one, like the catchall
+    /// `/users/*rest` after `/users/:id/posts`, panics. This finds every such registration up
+    /// front, so a route table can be fixed in one go. On failure the errors registering the
+    /// conflicting templates are returned in registration order.
+    ///
+    /// # Example
+    /// ```
+    /// use router_service::RoutePath;
+    ///
+    /// let paths: Vec<RoutePath> = ["/users/:id/posts", "/users/*rest", "/posts", "/posts/:id"]
+    ///     .iter()
+    ///     .map(|path| path.parse().unwrap())
+    ///     .collect();
+    /// assert!(RoutePath::check_conflicts(&paths[2..]).is_ok());
+    ///
+    /// let conflicts = RoutePath::check_conflicts(&paths).unwrap_err();
+    /// assert_eq!(conflicts.len(), 1);
+    /// assert_eq!(conflicts[0].template(), "/users/*rest");
+    /// assert_eq!(conflicts[0].conflicts_with(), Some("/users/:id/posts"));
+    /// ```
+    pub fn check_conflicts(paths: &[RoutePath]) -> Result<(), Vec<RoutingConflictError>> {
+        let conflicts = check_conflicts(paths.iter().map(RoutePath::as_str));
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(conflicts)
+        }
+    }
+}
+
+impl FromStr for RoutePath {
+    type Err = ParseRoutePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with('/') {
+            return Err(ParseRoutePathError::MissingLeadingSlash);
+        }
+
+        if s.contains("//") {
+            return Err(ParseRoutePathError::EmptySegment);
+        }
+
+        check_template(s).map_err(ParseRoutePathError::InvalidSyntax)?;
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl AsRef<str> for RoutePath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RoutePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An error returned when parsing a [`RoutePath`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseRoutePathError {
+    /// The path doesn't start with `/`.
+    MissingLeadingSlash,
+    /// The path contains an empty segment, e.g. `/users//posts`.
+    EmptySegment,
+    /// The path's parameters or catchall are malformed, e.g. an unnamed parameter or a catchall
+    /// that isn't the final segment.
+    InvalidSyntax(String),
+}
+
+impl fmt::Display for ParseRoutePathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingLeadingSlash => f.write_str("route paths must start with `/`"),
+            Self::EmptySegment => f.write_str("route paths must not contain empty segments"),
+            Self::InvalidSyntax(reason) => write!(f, "invalid route path: {reason}"),
+        }
+    }
+}
+
+impl Error for ParseRoutePathError {}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::{trim_trailing_slash, ParseRoutePathError, RoutePath};
+
+    #[test]
+    fn parses_valid_paths() {
+        for path in ["/", "/users/:id", "/users/:id/posts", "/static/*file"] {
+            let parsed: RoutePath = path.parse().unwrap();
+            assert_eq!(parsed.as_str(), path);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_paths() {
+        assert_eq!(
+            "users".parse::<RoutePath>(),
+            Err(ParseRoutePathError::MissingLeadingSlash)
+        );
+        assert_eq!(
+            "/users//posts".parse::<RoutePath>(),
+            Err(ParseRoutePathError::EmptySegment)
+        );
+        assert!(matches!(
+            "/users/:".parse::<RoutePath>(),
+            Err(ParseRoutePathError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            "/static/*file/more".parse::<RoutePath>(),
+            Err(ParseRoutePathError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn check_conflicts() {
+        let paths = |paths: &[&str]| -> Vec<RoutePath> {
+            paths.iter().map(|path| path.parse().unwrap()).collect()
+        };
+
+        // Paths matched by an earlier route are folded into it rather than conflicting.
+        let folded = paths(&["/users/:id", "/users/:name", "/*rest", "/users"]);
+        assert!(RoutePath::check_conflicts(&folded).is_ok());
+
+        let conflicting = paths(&[
+            "/users/:id/posts",
+            "/users/*rest",
+            "/files/:id/raw",
+            "/files/*path",
+            "/health",
+        ]);
+        let conflicts = RoutePath::check_conflicts(&conflicting).unwrap_err();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].template(), "/users/*rest");
+        assert_eq!(conflicts[0].conflicts_with(), Some("/users/:id/posts"));
+        assert_eq!(conflicts[1].template(), "/files/*path");
+        assert_eq!(conflicts[1].conflicts_with(), Some("/files/:id/raw"));
+
+        let message = conflicts[0].to_string();
+        assert!(
+            message.starts_with("route `/users/*rest` conflicts with the route `/users/:id/posts`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn trims_trailing_slash() {
+        let trim = |uri: &str| {
+            let req = Request::get(uri).body(()).unwrap();
+            trim_trailing_slash(req).uri().to_string()
+        };
+
+        assert_eq!(trim("/"), "/");
+        assert_eq!(trim("/users"), "/users");
+        assert_eq!(trim("/users/"), "/users");
+        assert_eq!(trim("/users//?page=2"), "/users?page=2");
+        assert_eq!(
+            trim("http://example.com/users/"),
+            "http://example.com/users"
+        );
+    }
+}
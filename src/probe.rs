@@ -0,0 +1,179 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::future::BoxFuture;
+use http::{Request, Response};
+use tower::{Layer, Service};
+
+type ProbeFn<Body, Error> =
+    dyn Fn(Request<Body>) -> BoxFuture<'static, Result<Response<Body>, Error>> + Send + Sync;
+
+/// A handler for requests to a `/.well-known/` path, registered with a [`ProbeLayer`].
+pub struct WellKnownHandler<Body, Error>(Arc<ProbeFn<Body, Error>>);
+
+impl<Body, Error> WellKnownHandler<Body, Error> {
+    /// Creates a handler that answers requests with `handler`.
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(Request<Body>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, Error>> + Send + 'static,
+    {
+        Self(Arc::new(move |req| Box::pin(handler(req))))
+    }
+}
+
+impl<Body, Error> Clone for WellKnownHandler<Body, Error> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+/// A [`Layer`] that answers requests to `/.well-known/*probe_name` paths, used for protocol
+/// discovery by OIDC, ACME, WebFinger, and others, from a registry of handlers.
+///
+/// A request is handled by the handler registered for the whole name after `/.well-known/`, or
+/// for its first segment, so a handler registered for `acme-challenge` answers
+/// `/.well-known/acme-challenge/<token>`. Requests to other paths, including `/.well-known/`
+/// paths without a handler, are passed on to the wrapped service.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{Request, Response};
+/// use tower::{Layer, Service};
+/// use router_service::{ProbeLayer, Router};
+///
+/// let router = Router::new()
+///     .get("/", |_, _| async move { Ok::<_, Infallible>(Response::new("home".to_string())) });
+/// let probes = ProbeLayer::new()
+///     .register("security.txt", |_| async move {
+///         Ok(Response::new("Contact: mailto:security@example.com".to_string()))
+///     })
+///     .register("acme-challenge", |req: Request<String>| async move {
+///         let token = req.uri().path().rsplit('/').next().unwrap().to_string();
+///         Ok(Response::new(token))
+///     });
+/// let mut service = probes.layer(router);
+///
+/// let req = Request::get("/.well-known/acme-challenge/abc").body(String::new()).unwrap();
+/// assert_eq!(service.call(req).await.unwrap().body(), "abc");
+///
+/// let req = Request::get("/").body(String::new()).unwrap();
+/// assert_eq!(service.call(req).await.unwrap().body(), "home");
+/// # });
+/// ```
+pub struct ProbeLayer<Body, Error> {
+    handlers: Arc<HashMap<String, WellKnownHandler<Body, Error>>>,
+}
+
+impl<Body, Error> ProbeLayer<Body, Error> {
+    /// Creates a layer without any handlers.
+    pub fn new() -> Self {
+        Self {
+            handlers: Default::default(),
+        }
+    }
+
+    /// Registers `handler` for requests to `/.well-known/{name}` and the paths below it,
+    /// replacing the handler previously registered for `name`, if any.
+    pub fn register<F, Fut>(self, name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(Request<Body>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response<Body>, Error>> + Send + 'static,
+    {
+        self.register_handler(name, WellKnownHandler::new(handler))
+    }
+
+    /// Registers a [`WellKnownHandler`] for requests to `/.well-known/{name}` and the paths below
+    /// it, e.g. one shared between several layers.
+    pub fn register_handler(
+        mut self,
+        name: impl Into<String>,
+        handler: WellKnownHandler<Body, Error>,
+    ) -> Self {
+        Arc::make_mut(&mut self.handlers).insert(name.into(), handler);
+        self
+    }
+}
+
+impl<Body, Error> Default for ProbeLayer<Body, Error> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Body, Error> Clone for ProbeLayer<Body, Error> {
+    fn clone(&self) -> Self {
+        Self {
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<S, Body, Error> Layer<S> for ProbeLayer<Body, Error> {
+    type Service = Probe<S, Body, Error>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Probe {
+            inner,
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+/// A service that answers requests to `/.well-known/` paths with the handlers registered with a
+/// [`ProbeLayer`], passing every other request on to the wrapped service.
+pub struct Probe<S, Body, Error> {
+    inner: S,
+    handlers: Arc<HashMap<String, WellKnownHandler<Body, Error>>>,
+}
+
+impl<S, Body, Error> Clone for Probe<S, Body, Error>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            handlers: self.handlers.clone(),
+        }
+    }
+}
+
+impl<S, Body, Error> Service<Request<Body>> for Probe<S, Body, Error>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = Error>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+
+    type Error = Error;
+
+    type Future = BoxFuture<'static, Result<Response<Body>, Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        match find_probe(&self.handlers, req.uri().path()) {
+            Some(handler) => (handler.0)(req),
+            None => Box::pin(self.inner.call(req)),
+        }
+    }
+}
+
+/// Finds the handler for `path` if it's a `/.well-known/` path, by the whole name after the
+/// prefix or by its first segment.
+pub(crate) fn find_probe<'a, H>(handlers: &'a HashMap<String, H>, path: &str) -> Option<&'a H> {
+    let name = path.strip_prefix("/.well-known/")?;
+    handlers
+        .get(name)
+        .or_else(|| handlers.get(name.split('/').next()?))
+}
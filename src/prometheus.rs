@@ -0,0 +1,194 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Duration};
+
+use http::{Method, StatusCode};
+use metrics::{Key, Label};
+
+/// Request counts and timings for every route, recorded by routers with a metrics endpoint.
+#[derive(Default)]
+pub(crate) struct RouteMetrics(Mutex<BTreeMap<(String, String), RouteStats>>);
+
+#[derive(Default)]
+struct RouteStats {
+    responses: BTreeMap<u16, u64>,
+    duration_sum: Duration,
+    duration_count: u64,
+}
+
+impl RouteMetrics {
+    /// Records a request to the route with the given template that completed after `elapsed`,
+    /// with `status` if it produced a response.
+    pub(crate) fn record(
+        &self,
+        template: &str,
+        method: &Method,
+        status: Option<StatusCode>,
+        elapsed: Duration,
+    ) {
+        let mut routes = self.0.lock().unwrap();
+        let stats = routes
+            .entry((template.to_owned(), method.to_string()))
+            .or_default();
+
+        if let Some(status) = status {
+            *stats.responses.entry(status.as_u16()).or_default() += 1;
+        }
+        stats.duration_sum += elapsed;
+        stats.duration_count += 1;
+    }
+
+    /// Renders the recorded metrics in the Prometheus text exposition format.
+    pub(crate) fn encode(&self) -> String {
+        let routes = self.0.lock().unwrap();
+        let mut encoder = PrometheusEncoder::default();
+
+        encoder.family(
+            "router_requests_total",
+            "counter",
+            "Requests handled by each route, by response status.",
+        );
+        for ((template, method), stats) in routes.iter() {
+            for (status, count) in &stats.responses {
+                let key = Key::from_parts(
+                    "router_requests_total",
+                    vec![
+                        Label::new("route", template.clone()),
+                        Label::new("method", method.clone()),
+                        Label::new("status", status.to_string()),
+                    ],
+                );
+                encoder.sample(&key, *count as f64);
+            }
+        }
+
+        encoder.family(
+            "router_request_duration_seconds",
+            "summary",
+            "Time spent handling requests to each route.",
+        );
+        for ((template, method), stats) in routes.iter() {
+            let labels = vec![
+                Label::new("route", template.clone()),
+                Label::new("method", method.clone()),
+            ];
+            let sum = Key::from_parts("router_request_duration_seconds_sum", labels.clone());
+            let count = Key::from_parts("router_request_duration_seconds_count", labels);
+            encoder.sample(&sum, stats.duration_sum.as_secs_f64());
+            encoder.sample(&count, stats.duration_count as f64);
+        }
+
+        encoder.finish()
+    }
+}
+
+/// Formats metric samples in the Prometheus text exposition format, version 0.0.4.
+#[derive(Default)]
+pub(crate) struct PrometheusEncoder {
+    output: String,
+}
+
+impl PrometheusEncoder {
+    /// Starts a metric family, writing its `HELP` and `TYPE` lines.
+    pub(crate) fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let name = sanitize_name(name);
+        let help = help.replace('\\', r"\\").replace('\n', r"\n");
+        let _ = writeln!(self.output, "# HELP {name} {help}");
+        let _ = writeln!(self.output, "# TYPE {name} {kind}");
+    }
+
+    /// Writes a single sample of the series identified by `key`.
+    pub(crate) fn sample(&mut self, key: &Key, value: f64) {
+        self.output.push_str(&sanitize_name(key.name()));
+
+        let mut labels = key.labels().peekable();
+        if labels.peek().is_some() {
+            self.output.push('{');
+            for (i, label) in labels.enumerate() {
+                if i > 0 {
+                    self.output.push(',');
+                }
+                let _ = write!(
+                    self.output,
+                    "{}=\"{}\"",
+                    sanitize_name(label.key()),
+                    escape_label_value(label.value())
+                );
+            }
+            self.output.push('}');
+        }
+
+        let _ = writeln!(self.output, " {value}");
+    }
+
+    pub(crate) fn finish(self) -> String {
+        self.output
+    }
+}
+
+/// Replaces characters that aren't allowed in metric and label names with underscores.
+fn sanitize_name(name: &str) -> String {
+    name.char_indices()
+        .map(|(i, c)| match c {
+            'a'..='z' | 'A'..='Z' | '_' | ':' => c,
+            '0'..='9' if i > 0 => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use http::{Method, StatusCode};
+    use metrics::{Key, Label};
+
+    use super::{PrometheusEncoder, RouteMetrics};
+
+    #[test]
+    fn encodes_samples() {
+        let mut encoder = PrometheusEncoder::default();
+        encoder.family("http-requests", "counter", "Requests.");
+        encoder.sample(
+            &Key::from_parts("http-requests", vec![Label::new("path", "/say/\"hi\"\\\n")]),
+            2.0,
+        );
+        encoder.sample(&Key::from_name("2xx"), 0.5);
+
+        assert_eq!(
+            encoder.finish(),
+            concat!(
+                "# HELP http_requests Requests.\n",
+                "# TYPE http_requests counter\n",
+                "http_requests{path=\"/say/\\\"hi\\\"\\\\\\n\"} 2\n",
+                "_xx 0.5\n",
+            )
+        );
+    }
+
+    #[test]
+    fn encodes_route_metrics() {
+        let metrics = RouteMetrics::default();
+        let elapsed = Duration::from_millis(250);
+        metrics.record("/users/:id", &Method::GET, Some(StatusCode::OK), elapsed);
+        metrics.record("/users/:id", &Method::GET, Some(StatusCode::OK), elapsed);
+        metrics.record("/users/:id", &Method::GET, None, elapsed);
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(
+            "router_requests_total{route=\"/users/:id\",method=\"GET\",status=\"200\"} 2\n"
+        ));
+        assert!(encoded.contains(
+            "router_request_duration_seconds_sum{route=\"/users/:id\",method=\"GET\"} 0.75\n"
+        ));
+        assert!(encoded.contains(
+            "router_request_duration_seconds_count{route=\"/users/:id\",method=\"GET\"} 3\n"
+        ));
+    }
+}
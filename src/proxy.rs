@@ -0,0 +1,111 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use http::HeaderMap;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The reverse proxies whose `X-Forwarded-For` headers are trusted, set up with
+/// [`Router::with_proxy_headers`](crate::Router::with_proxy_headers).
+#[derive(Clone)]
+pub(crate) struct TrustedProxies(Arc<Vec<IpAddr>>);
+
+impl TrustedProxies {
+    pub(crate) fn new(proxies: Vec<IpAddr>) -> Self {
+        Self(Arc::new(proxies))
+    }
+
+    fn trusts(&self, addr: &IpAddr) -> bool {
+        self.0.contains(addr)
+    }
+
+    /// Finds the address of the client that made a request received from `peer`.
+    ///
+    /// Every proxy appends the address it received the request from to `X-Forwarded-For`, so the
+    /// header is read from the right, skipping trusted proxies, until an address that isn't a
+    /// trusted proxy is found. Addresses further left were added by the client or by proxies
+    /// that can't be trusted, and are ignored. Requests from untrusted peers are answered with the
+    /// peer's address, as the header could be forged.
+    pub(crate) fn client_ip(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.trusts(&peer) {
+            return Some(peer);
+        }
+
+        let mut client = peer;
+        for value in headers.get_all(X_FORWARDED_FOR).iter().rev() {
+            for addr in value.to_str().ok()?.rsplit(',') {
+                client = parse_addr(addr.trim())?;
+                if !self.trusts(&client) {
+                    return Some(client);
+                }
+            }
+        }
+        Some(client)
+    }
+}
+
+/// Parses an address from `X-Forwarded-For`, which some proxies add with a port.
+fn parse_addr(addr: &str) -> Option<IpAddr> {
+    addr.parse()
+        .ok()
+        .or_else(|| addr.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use http::{HeaderMap, HeaderValue};
+
+    use super::TrustedProxies;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn client_ip() {
+        let proxies = TrustedProxies::new(vec![ip("10.0.0.1"), ip("10.0.0.2")]);
+        let headers = |values: &[&'static str]| {
+            let mut headers = HeaderMap::new();
+            for value in values {
+                headers.append("x-forwarded-for", HeaderValue::from_static(value));
+            }
+            headers
+        };
+
+        let forwarded = headers(&["1.1.1.1, 203.0.113.7", "10.0.0.2"]);
+        assert_eq!(
+            proxies.client_ip(Some(ip("10.0.0.1")), &forwarded),
+            Some(ip("203.0.113.7"))
+        );
+        // Untrusted peers can put anything in the header.
+        assert_eq!(
+            proxies.client_ip(Some(ip("192.0.2.1")), &forwarded),
+            Some(ip("192.0.2.1"))
+        );
+        assert_eq!(proxies.client_ip(None, &forwarded), None);
+
+        let only_proxies = headers(&["10.0.0.2"]);
+        assert_eq!(
+            proxies.client_ip(Some(ip("10.0.0.1")), &only_proxies),
+            Some(ip("10.0.0.2"))
+        );
+        let missing = headers(&[]);
+        assert_eq!(
+            proxies.client_ip(Some(ip("10.0.0.1")), &missing),
+            Some(ip("10.0.0.1"))
+        );
+
+        let with_ports = headers(&["203.0.113.7:51234, [2001:db8::1]:443"]);
+        assert_eq!(
+            proxies.client_ip(Some(ip("10.0.0.1")), &with_ports),
+            Some(ip("2001:db8::1"))
+        );
+        let malformed = headers(&["not an address"]);
+        assert_eq!(proxies.client_ip(Some(ip("10.0.0.1")), &malformed), None);
+    }
+}
@@ -0,0 +1,81 @@
+/// Decodes the `application/x-www-form-urlencoded` pairs of a query string, in order, for
+/// [`RouteContext::query`](crate::RouteContext::query).
+///
+/// Keys without a value, like `flag` in `?flag&page=2`, have an empty value. Malformed escapes are
+/// kept as they are, and bytes that don't decode to UTF-8 are replaced.
+pub(crate) fn parse_query(query: Option<&str>) -> Vec<(String, String)> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// Decodes `+` as a space and `%XX` escapes as the byte they encode.
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_byte(digits: &[u8]) -> Option<u8> {
+    if !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_query;
+
+    #[test]
+    fn parse() {
+        let owned = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+
+        assert_eq!(parse_query(None), owned(&[]));
+        assert_eq!(parse_query(Some("")), owned(&[]));
+        assert_eq!(
+            parse_query(Some("q=rust+router&tag=a&tag=b&&flag")),
+            owned(&[
+                ("q", "rust router"),
+                ("tag", "a"),
+                ("tag", "b"),
+                ("flag", "")
+            ])
+        );
+        assert_eq!(
+            parse_query(Some("na%6De=caf%C3%A9&sum=1%2B1%3D2")),
+            owned(&[("name", "café"), ("sum", "1+1=2")])
+        );
+        assert_eq!(
+            parse_query(Some("bad=100%&worse=%zz%4")),
+            owned(&[("bad", "100%"), ("worse", "%zz%4")])
+        );
+        assert_eq!(parse_query(Some("sign=%+1")), owned(&[("sign", "% 1")]));
+    }
+}
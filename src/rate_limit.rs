@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use http::{header::RETRY_AFTER, HeaderMap, HeaderValue, Response, StatusCode};
+
+const X_API_KEY: &str = "x-api-key";
+
+/// How many keys are tracked before the buckets that have refilled are first dropped, so that
+/// clients sending a new key with every request can't grow the map forever.
+const PRUNE_AT: usize = 1024;
+
+/// How many keys are tracked at most. Requests with new keys are turned away while the map is
+/// full, instead of resetting the budget of a key in use.
+const MAX_KEYS: usize = 64 * 1024;
+
+/// How often a full map is pruned, so that requests with new keys don't each scan all of it.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The per-key request budgets set up with
+/// [`Router::rate_limit_by_api_key`](crate::Router::rate_limit_by_api_key).
+///
+/// Clones share their buckets, so every clone of a router counts against the same limits.
+#[derive(Clone)]
+pub(crate) struct ApiKeyRateLimit {
+    buckets: Arc<Mutex<Buckets>>,
+    requests_per_minute: u32,
+}
+
+impl ApiKeyRateLimit {
+    pub(crate) fn new(requests_per_minute: u32) -> Self {
+        assert!(
+            requests_per_minute > 0,
+            "rate limits must allow at least one request per minute"
+        );
+        let buckets = Buckets {
+            by_key: HashMap::new(),
+            prune_at: PRUNE_AT,
+            pruned: Instant::now(),
+        };
+        Self {
+            buckets: Arc::new(Mutex::new(buckets)),
+            requests_per_minute,
+        }
+    }
+
+    /// Takes a request from the budget of the API key in `headers`, returning the response
+    /// rejecting the request if it has no key or its key's budget is used up.
+    pub(crate) fn check(&self, headers: &HeaderMap) -> Result<(), Response<()>> {
+        self.check_at(headers, Instant::now())
+    }
+
+    fn check_at(&self, headers: &HeaderMap, now: Instant) -> Result<(), Response<()>> {
+        let key = match headers.get(X_API_KEY).map(HeaderValue::to_str) {
+            Some(Ok(key)) if !key.is_empty() => key,
+            _ => {
+                let mut resp = Response::new(());
+                *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                return Err(resp);
+            }
+        };
+
+        let capacity = f64::from(self.requests_per_minute);
+        let mut buckets = self.buckets.lock().unwrap();
+        if !buckets.by_key.contains_key(key) && !buckets.make_room(now, capacity) {
+            // Every bucket has refilled a minute after its last request, and can be dropped.
+            let mut resp = Response::new(());
+            *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+            resp.headers_mut().insert(RETRY_AFTER, 60.into());
+            return Err(resp);
+        }
+        let bucket = buckets
+            .by_key
+            .entry(key.to_owned())
+            .or_insert_with(|| TokenBucket::full(capacity, now));
+
+        match bucket.take(now, capacity) {
+            Ok(()) => Ok(()),
+            Err(wait) => {
+                // `Retry-After` is in whole seconds, so round up to not invite an early retry.
+                let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                let mut resp = Response::new(());
+                *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                resp.headers_mut().insert(RETRY_AFTER, secs.into());
+                Err(resp)
+            }
+        }
+    }
+}
+
+/// The buckets of the keys seen recently.
+struct Buckets {
+    by_key: HashMap<String, TokenBucket>,
+    /// How many keys are tracked before the next prune.
+    prune_at: usize,
+    pruned: Instant,
+}
+
+impl Buckets {
+    /// Makes room for a new key, dropping the buckets that have refilled once there are
+    /// `prune_at` keys. Returns `false` if there are still [`MAX_KEYS`] keys.
+    fn make_room(&mut self, now: Instant, capacity: f64) -> bool {
+        let len = self.by_key.len();
+        let throttled =
+            len >= MAX_KEYS && now.saturating_duration_since(self.pruned) < PRUNE_INTERVAL;
+        if len >= self.prune_at && !throttled {
+            self.by_key
+                .retain(|_, bucket| bucket.tokens_at(now, capacity) < capacity);
+            self.pruned = now;
+            // Waiting for the map to double again before the next prune keeps its cost
+            // proportional to the keys added since, even when this one freed little.
+            self.prune_at = (self.by_key.len() * 2).clamp(PRUNE_AT, MAX_KEYS);
+        }
+        self.by_key.len() < MAX_KEYS
+    }
+}
+
+/// A budget of `capacity` requests, refilled at `capacity` requests per minute.
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(capacity: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn tokens_at(&self, now: Instant, capacity: f64) -> f64 {
+        let refilled = now.saturating_duration_since(self.updated).as_secs_f64() * capacity / 60.0;
+        (self.tokens + refilled).min(capacity)
+    }
+
+    /// Takes a token, or returns how long it takes for one to be refilled.
+    fn take(&mut self, now: Instant, capacity: f64) -> Result<(), Duration> {
+        self.tokens = self.tokens_at(now, capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) * 60.0 / capacity,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use http::{HeaderMap, HeaderValue, StatusCode};
+
+    use super::{ApiKeyRateLimit, MAX_KEYS, PRUNE_AT};
+
+    #[test]
+    fn check() {
+        let limit = ApiKeyRateLimit::new(2);
+        let start = Instant::now();
+        let key = |key: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-api-key", HeaderValue::from_static(key));
+            headers
+        };
+
+        assert!(limit.check_at(&key("a"), start).is_ok());
+        assert!(limit.check_at(&key("a"), start).is_ok());
+        let resp = limit.check_at(&key("a"), start).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()["retry-after"], "30");
+
+        // Other keys have their own budget.
+        assert!(limit.check_at(&key("b"), start).is_ok());
+
+        // Two requests a minute refill a token every 30 seconds.
+        let later = start + Duration::from_secs(20);
+        let resp = limit.check_at(&key("a"), later).unwrap_err();
+        assert_eq!(resp.headers()["retry-after"], "10");
+        assert!(limit
+            .check_at(&key("a"), later + Duration::from_secs(10))
+            .is_ok());
+
+        let resp = limit.check_at(&HeaderMap::new(), start).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let resp = limit.check_at(&key(""), start).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn prune() {
+        fn check(limit: &ApiKeyRateLimit, key: usize, now: Instant) -> bool {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-api-key", key.to_string().try_into().unwrap());
+            limit.check_at(&headers, now).is_ok()
+        }
+        let keys = |limit: &ApiKeyRateLimit| limit.buckets.lock().unwrap().by_key.len();
+        let start = Instant::now();
+        let later = start + Duration::from_secs(60);
+
+        // Used up buckets aren't dropped, and the next prune waits for the map to double.
+        let limit = ApiKeyRateLimit::new(1);
+        for key in 0..=PRUNE_AT {
+            assert!(check(&limit, key, start));
+        }
+        assert_eq!(keys(&limit), PRUNE_AT + 1);
+        assert_eq!(limit.buckets.lock().unwrap().prune_at, PRUNE_AT * 2);
+
+        // Refilled buckets are.
+        for key in PRUNE_AT + 1..PRUNE_AT * 2 {
+            assert!(check(&limit, key, later));
+        }
+        assert!(check(&limit, PRUNE_AT * 2, later + Duration::from_secs(1)));
+        assert_eq!(keys(&limit), PRUNE_AT);
+
+        // A full map turns new keys away, but still serves the keys it has.
+        let limit = ApiKeyRateLimit::new(1);
+        for key in 0..MAX_KEYS {
+            assert!(check(&limit, key, start));
+        }
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", HeaderValue::from_static("new"));
+        let resp = limit.check_at(&headers, start).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers()["retry-after"], "60");
+        assert!(check(&limit, 0, later));
+        assert!(limit.check_at(&headers, later).is_ok());
+        assert_eq!(keys(&limit), 2);
+    }
+}
@@ -0,0 +1,96 @@
+use std::net::SocketAddr;
+
+/// Metadata about the connection a request arrived on, shared by every request on it.
+///
+/// A [`MakeRouter`](crate::MakeRouter) creates one for every connection, with an ID unique to
+/// the [`MakeRouter`](crate::MakeRouter), and can fill in the rest from the connection with
+/// [`MakeRouter::request_context`](crate::MakeRouter::request_context). The context is added to
+/// the extensions of every request on the connection, and handlers can get at it through
+/// [`RouteContext::request_context`](crate::RouteContext::request_context).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestContext {
+    connection_id: u64,
+    remote_addr: Option<SocketAddr>,
+    tls: Option<TlsInfo>,
+}
+
+impl RequestContext {
+    pub(crate) const EMPTY: Self = Self {
+        connection_id: 0,
+        remote_addr: None,
+        tls: None,
+    };
+
+    /// Creates a context for the connection with the given ID.
+    pub fn new(connection_id: u64) -> Self {
+        Self {
+            connection_id,
+            ..Self::EMPTY
+        }
+    }
+
+    /// Sets the address of the client on the other end of the connection.
+    pub fn with_remote_addr(mut self, addr: SocketAddr) -> Self {
+        self.remote_addr = Some(addr);
+        self
+    }
+
+    /// Marks the connection as secured with TLS, negotiated as described by `tls`.
+    pub fn with_tls(mut self, tls: TlsInfo) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// The ID of the connection, starting at 1, or 0 if the router wasn't created by a
+    /// [`MakeRouter`](crate::MakeRouter).
+    pub fn connection_id(&self) -> u64 {
+        self.connection_id
+    }
+
+    /// The address of the client on the other end of the connection, if known.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// What was negotiated for the connection's TLS session, or `None` if it isn't secured with
+    /// TLS or that isn't known.
+    pub fn tls(&self) -> Option<&TlsInfo> {
+        self.tls.as_ref()
+    }
+}
+
+/// What was negotiated for a connection's TLS session, part of a [`RequestContext`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    server_name: Option<String>,
+    alpn_protocol: Option<Vec<u8>>,
+}
+
+impl TlsInfo {
+    /// Creates an empty description of a TLS session.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the server name the client asked for with SNI.
+    pub fn with_server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /// Sets the protocol agreed on with ALPN, e.g. `b"h2"`.
+    pub fn with_alpn_protocol(mut self, protocol: impl Into<Vec<u8>>) -> Self {
+        self.alpn_protocol = Some(protocol.into());
+        self
+    }
+
+    /// The server name the client asked for with SNI, if any.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// The protocol agreed on with ALPN, if any.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        self.alpn_protocol.as_deref()
+    }
+}
@@ -0,0 +1,125 @@
+use std::any::Any;
+
+use bytes::Bytes;
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING},
+    HeaderValue, Response, StatusCode,
+};
+
+/// Sets `Content-Length` on responses, set up by
+/// [`Router::auto_content_length`](crate::Router::auto_content_length).
+pub(crate) type SetContentLength<Body> = fn(&mut Response<Body>);
+
+/// Turns a handler's error into a response, set up by
+/// [`Router::auto_json_error`](crate::Router::auto_json_error).
+pub(crate) type ErrorResponse<Body, Error> = fn(Error) -> Response<Body>;
+
+/// Turns the payload of a panic in a handler into a response, set up by
+/// [`Router::with_panic_handler`](crate::Router::with_panic_handler).
+pub(crate) type PanicHandler<Body> = fn(Box<dyn Any + Send>) -> Response<Body>;
+
+/// Answers a panic in a handler with `panic_handler`, logging its message first.
+pub(crate) fn panic_response<Body>(
+    panic: Box<dyn Any + Send>,
+    panic_handler: PanicHandler<Body>,
+) -> Response<Body> {
+    #[cfg(feature = "tracing")]
+    {
+        let message = panic
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        tracing::error!(panic = message, "handler panicked");
+    }
+    panic_handler(panic)
+}
+
+/// Answers `err` with `500 Internal Server Error` and the error serialized as JSON, or without a
+/// body if it can't be serialized.
+#[cfg(feature = "json")]
+pub(crate) fn json_error<Body, Error>(err: Error) -> Response<Body>
+where
+    Body: Default + From<String>,
+    Error: serde::Serialize,
+{
+    let mut resp = match serde_json::to_string(&err) {
+        Ok(json) => {
+            let mut resp = Response::new(Body::from(json));
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            resp
+        }
+        Err(_) => Response::new(Body::default()),
+    };
+    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    resp
+}
+
+/// Sets `Content-Length` to the size of `resp`'s body, unless it's already set or the response
+/// can't have one.
+pub(crate) fn set_content_length<Body>(resp: &mut Response<Body>)
+where
+    Body: AsRef<[u8]>,
+{
+    let status = resp.status();
+    let headers = resp.headers();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || headers.contains_key(CONTENT_LENGTH)
+        || headers.contains_key(TRANSFER_ENCODING)
+    {
+        return;
+    }
+
+    let len = resp.body().as_ref().len();
+    resp.headers_mut()
+        .insert(CONTENT_LENGTH, HeaderValue::from(len));
+}
+
+/// A response whose status, headers, and body are computed once when the router is built.
+pub(crate) struct StaticResponse<Body> {
+    status: StatusCode,
+    content_type: HeaderValue,
+    bytes: Bytes,
+    into_body: fn(Bytes) -> Body,
+}
+
+impl<Body> StaticResponse<Body>
+where
+    Body: From<Bytes>,
+{
+    pub(crate) fn new(status: StatusCode, content_type: &str, bytes: Bytes) -> Self {
+        Self {
+            status,
+            content_type: HeaderValue::from_str(content_type).expect("invalid content type"),
+            bytes,
+            into_body: Body::from,
+        }
+    }
+}
+
+impl<Body> StaticResponse<Body> {
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        let mut resp = Response::new((self.into_body)(self.bytes.clone()));
+        *resp.status_mut() = self.status;
+
+        let headers = resp.headers_mut();
+        headers.insert(CONTENT_TYPE, self.content_type.clone());
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(self.bytes.len()));
+
+        resp
+    }
+}
+
+impl<Body> Clone for StaticResponse<Body> {
+    fn clone(&self) -> Self {
+        Self {
+            status: self.status,
+            content_type: self.content_type.clone(),
+            bytes: self.bytes.clone(),
+            into_body: self.into_body,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use http::{header::CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, Response};
+
+/// Which responses a [`HeaderRule`] applies to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Scope {
+    All,
+    Html,
+}
+
+#[derive(Clone)]
+struct HeaderRule {
+    name: HeaderName,
+    /// The value to set, or `None` to remove the header.
+    value: Option<HeaderValue>,
+    scope: Scope,
+}
+
+/// Headers the router adds to the responses it produces.
+#[derive(Clone, Default)]
+pub(crate) struct ResponseHeaders(Arc<Vec<HeaderRule>>);
+
+impl ResponseHeaders {
+    /// Sets `name` to `value` on every response in `scope`, replacing any previous rule for
+    /// `name`.
+    pub(crate) fn insert(&mut self, name: HeaderName, value: HeaderValue, scope: Scope) {
+        let rules = Arc::make_mut(&mut self.0);
+        rules.retain(|rule| rule.name != name);
+        rules.push(HeaderRule {
+            name,
+            value: Some(value),
+            scope,
+        });
+    }
+
+    /// Removes `name` from every response, replacing any previous rule for `name`.
+    pub(crate) fn remove(&mut self, name: HeaderName) {
+        let rules = Arc::make_mut(&mut self.0);
+        rules.retain(|rule| rule.name != name);
+        rules.push(HeaderRule {
+            name,
+            value: None,
+            scope: Scope::All,
+        });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds or removes the configured headers on `resp`, overwriting any values set by the
+    /// handler.
+    pub(crate) fn apply<Body>(&self, resp: &mut Response<Body>) {
+        let html = is_html(resp);
+        let headers = resp.headers_mut();
+
+        for rule in self.0.iter() {
+            if rule.scope != Scope::All && !html {
+                continue;
+            }
+
+            match &rule.value {
+                Some(value) => {
+                    headers.insert(rule.name.clone(), value.clone());
+                }
+                None => {
+                    headers.remove(&rule.name);
+                }
+            }
+        }
+    }
+}
+
+/// Headers set for a single response with `RouteContext::set_response_header`, shared between
+/// the context handed to the handler and the router.
+///
+/// The map is only created when the first header is set, so most responses, which don't set any,
+/// are answered without locking it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ContextHeaders(Arc<OnceLock<Mutex<HeaderMap>>>);
+
+impl ContextHeaders {
+    /// Sets `name` to `value`, replacing the value set for `name` before.
+    pub(crate) fn insert(&self, name: HeaderName, value: HeaderValue) {
+        self.map().lock().unwrap().insert(name, value);
+    }
+
+    /// Adds `value` for `name`, keeping the values set for `name` before.
+    #[cfg(feature = "sessions")]
+    pub(crate) fn append(&self, name: HeaderName, value: HeaderValue) {
+        self.map().lock().unwrap().append(name, value);
+    }
+
+    /// Adds the headers to `resp`, alongside any values the handler set for the same names.
+    pub(crate) fn apply<Body>(&self, resp: &mut Response<Body>) {
+        let headers = match self.0.get() {
+            Some(headers) => headers,
+            None => return,
+        };
+        let headers = std::mem::take(&mut *headers.lock().unwrap());
+        for (name, value) in headers.iter() {
+            resp.headers_mut().append(name, value.clone());
+        }
+    }
+
+    fn map(&self) -> &Mutex<HeaderMap> {
+        self.0.get_or_init(Mutex::default)
+    }
+}
+
+fn is_html<Body>(resp: &Response<Body>) -> bool {
+    resp.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("text/html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{
+        header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE, SET_COOKIE},
+        HeaderValue, Response,
+    };
+
+    use super::{ContextHeaders, ResponseHeaders, Scope};
+
+    #[test]
+    fn html_scope() {
+        let mut headers = ResponseHeaders::default();
+        headers.insert(
+            CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'self'"),
+            Scope::Html,
+        );
+
+        let mut html = Response::builder()
+            .header(CONTENT_TYPE, "Text/HTML; charset=utf-8")
+            .body(())
+            .unwrap();
+        headers.apply(&mut html);
+        assert_eq!(
+            html.headers()[CONTENT_SECURITY_POLICY],
+            "default-src 'self'"
+        );
+
+        let mut json = Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(())
+            .unwrap();
+        headers.apply(&mut json);
+        assert!(!json.headers().contains_key(CONTENT_SECURITY_POLICY));
+    }
+    #[test]
+    fn context_headers() {
+        let headers = ContextHeaders::default();
+        headers.insert(SET_COOKIE, HeaderValue::from_static("session=old"));
+        headers.insert(SET_COOKIE, HeaderValue::from_static("session=abc"));
+
+        let mut resp = Response::builder()
+            .header(SET_COOKIE, "theme=dark")
+            .body(())
+            .unwrap();
+        headers.apply(&mut resp);
+        let cookies: Vec<_> = resp.headers().get_all(SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["theme=dark", "session=abc"]);
+    }
+}
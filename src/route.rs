@@ -0,0 +1,452 @@
+use std::{collections::HashMap, error::Error, fmt, marker::PhantomData, sync::Arc};
+
+use http::{HeaderValue, Method};
+use matchit::Router as MatchRouter;
+
+#[cfg(feature = "tokio")]
+use crate::watch::{RouteChangeEvent, RouteChanges, RouterWatcher};
+
+/// Joins a path prefix and a route path, e.g. `/api` and `/users` into `/api/users`.
+pub(crate) fn join_paths(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+
+    match path.trim_start_matches('/') {
+        "" if !prefix.is_empty() => prefix.to_owned(),
+        path => format!("{prefix}/{path}"),
+    }
+}
+
+/// The operations the router needs from the tree that matches paths against templates.
+///
+/// Everything specific to `matchit` lives in the implementation of this trait for
+/// [`matchit::Router`], so upgrading `matchit` across a breaking release only requires updating
+/// that implementation and [`Params`].
+pub(crate) trait RouteTree<T>: Default {
+    /// Inserts `value` under the path template `template`.
+    fn insert(&mut self, template: &str, value: T) -> Result<(), RoutingConflictError>;
+
+    /// Finds the value whose template matches `path`, along with the captured parameters.
+    fn at<'m, 'p>(&'m self, path: &'p str) -> Option<Matched<'m, 'p, T>>;
+
+    /// Finds the value whose template matches `path` for modification.
+    fn at_mut(&mut self, path: &str) -> Option<&mut T>;
+}
+
+/// A value matched by a [`RouteTree`] and the parameters captured from the path.
+pub(crate) struct Matched<'m, 'p, T> {
+    pub(crate) value: &'m T,
+    pub(crate) params: Params<'m, 'p>,
+}
+
+/// The parameters captured when matching a path, borrowed from the tree and the path.
+pub(crate) struct Params<'m, 'p>(matchit::Params<'m, 'p>);
+
+impl<'m, 'p> Params<'m, 'p> {
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&'m str, &'p str)> + '_ {
+        self.0.iter()
+    }
+}
+
+impl<T> RouteTree<T> for MatchRouter<T> {
+    fn insert(&mut self, template: &str, value: T) -> Result<(), RoutingConflictError> {
+        MatchRouter::insert(self, template, value).map_err(|err| {
+            let conflicts_with = match &err {
+                matchit::InsertError::Conflict { with } => Some(with.clone()),
+                _ => None,
+            };
+
+            RoutingConflictError {
+                template: template.to_owned(),
+                conflicts_with,
+                source: Arc::new(err),
+            }
+        })
+    }
+
+    fn at<'m, 'p>(&'m self, path: &'p str) -> Option<Matched<'m, 'p, T>> {
+        let matched = MatchRouter::at(self, path).ok()?;
+
+        Some(Matched {
+            value: matched.value,
+            params: Params(matched.params),
+        })
+    }
+
+    fn at_mut(&mut self, path: &str) -> Option<&mut T> {
+        MatchRouter::at_mut(self, path)
+            .ok()
+            .map(|matched| matched.value)
+    }
+}
+
+/// An error registering a route, because its template conflicts with a route registered
+/// before it or is malformed.
+///
+/// Registering such a route panics with this error as the message, and
+/// [`RoutePath::check_conflicts`](crate::RoutePath::check_conflicts) returns it for every route
+/// in a route table that would.
+#[derive(Debug, Clone)]
+pub struct RoutingConflictError {
+    template: String,
+    conflicts_with: Option<String>,
+    source: Arc<dyn Error + Send + Sync>,
+}
+
+impl RoutingConflictError {
+    /// The template of the route that couldn't be registered.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// The template of the route registered before it that it conflicts with, or `None` if the
+    /// template is malformed.
+    pub fn conflicts_with(&self) -> Option<&str> {
+        self.conflicts_with.as_deref()
+    }
+}
+
+impl fmt::Display for RoutingConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.conflicts_with {
+            Some(existing) => write!(
+                f,
+                "route `{}` conflicts with the route `{existing}` registered before it, as \
+                 requests could match both; move one of the routes under a different prefix, or \
+                 serve both with a single route that tells the paths apart in its handler",
+                self.template
+            ),
+            None => write!(f, "route `{}` is malformed: {}", self.template, self.source),
+        }
+    }
+}
+
+impl Error for RoutingConflictError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Checks that `template` can be inserted into an empty route tree.
+pub(crate) fn check_template(template: &str) -> Result<(), String> {
+    let mut tree = MatchRouter::new();
+    RouteTree::insert(&mut tree, template, ()).map_err(|err| err.source.to_string())
+}
+
+/// Inserts `templates` into an empty route tree in order, the way a [`RouteTable`] does, and
+/// returns the error of every insertion that fails.
+pub(crate) fn check_conflicts<'t>(
+    templates: impl IntoIterator<Item = &'t str>,
+) -> Vec<RoutingConflictError> {
+    let mut tree = MatchRouter::new();
+    let mut conflicts = Vec::new();
+
+    for template in templates {
+        if RouteTree::at(&tree, template).is_some() {
+            continue;
+        }
+        if let Err(err) = RouteTree::insert(&mut tree, template, ()) {
+            conflicts.push(err);
+        }
+    }
+
+    conflicts
+}
+
+/// The handlers registered for a single path template.
+pub(crate) struct Route<H> {
+    pub(crate) template: Arc<str>,
+    pub(crate) handlers: HashMap<Method, H>,
+    pub(crate) catchall: Option<H>,
+}
+
+impl<H> Route<H> {
+    /// A method both routes have a handler for, with `any` standing for catchall handlers.
+    fn shared_method(&self, other: &Route<H>) -> Option<&str> {
+        match self
+            .handlers
+            .keys()
+            .find(|method| other.handlers.contains_key(*method))
+        {
+            Some(method) => Some(method.as_str()),
+            None => (self.catchall.is_some() && other.catchall.is_some()).then_some("any"),
+        }
+    }
+
+    /// The methods the route has handlers for, in alphabetical order, including `HEAD` if `HEAD`
+    /// requests are answered by the `GET` handler and `OPTIONS`, which the router answers for
+    /// every route.
+    pub(crate) fn allowed_methods(&self, auto_head: bool) -> Vec<Method> {
+        let mut methods: Vec<Method> = self.handlers.keys().cloned().collect();
+        if auto_head && self.handlers.contains_key(&Method::GET) && !methods.contains(&Method::HEAD)
+        {
+            methods.push(Method::HEAD);
+        }
+        if !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        methods.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+        methods
+    }
+
+    /// The value of the `Allow` header listing the methods in [`Route::allowed_methods`].
+    pub(crate) fn allow(&self, auto_head: bool) -> HeaderValue {
+        let methods = self.allowed_methods(auto_head);
+        let methods: Vec<&str> = methods.iter().map(Method::as_str).collect();
+        HeaderValue::from_str(&methods.join(", ")).expect("methods are valid header values")
+    }
+}
+
+/// The routing table shared by the synchronized and unsynchronized routers.
+pub(crate) struct RouteTable<H, Tree = MatchRouter<Route<H>>> {
+    tree: Tree,
+    templates: Vec<Arc<str>>,
+    /// The templates of the routes registered with a name, by name.
+    names: HashMap<String, Arc<str>>,
+    shadowed: Vec<String>,
+    #[cfg(feature = "tokio")]
+    changes: RouteChanges,
+    _handler: PhantomData<H>,
+}
+
+impl<H, Tree> Default for RouteTable<H, Tree>
+where
+    Tree: RouteTree<Route<H>>,
+{
+    fn default() -> Self {
+        Self {
+            tree: Tree::default(),
+            templates: Vec::new(),
+            names: HashMap::new(),
+            shadowed: Vec::new(),
+            #[cfg(feature = "tokio")]
+            changes: RouteChanges::default(),
+            _handler: PhantomData,
+        }
+    }
+}
+
+impl<H, Tree> RouteTable<H, Tree>
+where
+    Tree: RouteTree<Route<H>>,
+{
+    pub(crate) fn at<'m, 'p>(&'m self, path: &'p str) -> Option<Matched<'m, 'p, Route<H>>> {
+        self.tree.at(path)
+    }
+
+    pub(crate) fn insert_handler(&mut self, path: &str, method: Method, handler: H) {
+        self.insert(path, Some(method), handler);
+    }
+
+    pub(crate) fn try_insert_handler(
+        &mut self,
+        path: &str,
+        method: Method,
+        handler: H,
+    ) -> Result<(), RoutingConflictError> {
+        self.try_insert(path, Some(method), handler)
+    }
+
+    pub(crate) fn insert_catchall(&mut self, path: &str, handler: H) {
+        self.insert(path, None, handler);
+    }
+
+    /// Registers every route of `other` under `prefix`, with the same handlers.
+    ///
+    /// # Panics
+    /// Panics if a route of `other` has a handler for a method this table already has a handler
+    /// for at the same path, or can't be registered.
+    pub(crate) fn nest(&mut self, prefix: &str, other: &Self)
+    where
+        H: Clone,
+    {
+        for template in &other.templates {
+            let route = other.route(template);
+            let path = join_paths(prefix, template);
+
+            if let Some(existing) = self.tree.at(&path) {
+                if let Some(method) = existing.value.shared_method(route) {
+                    panic!(
+                        "unable to add route `{path}` to router: it already has a {method} \
+                         handler for `{}`",
+                        existing.value.template
+                    );
+                }
+            }
+
+            for (method, handler) in &route.handlers {
+                self.insert(&path, Some(method.clone()), handler.clone());
+            }
+            if let Some(handler) = &route.catchall {
+                self.insert(&path, None, handler.clone());
+            }
+        }
+
+        for (name, template) in &other.names {
+            self.name(name, &join_paths(prefix, template));
+        }
+    }
+
+    /// Registers `name` for the route with the template `template`, see [`RouteTable::url_for`].
+    ///
+    /// # Panics
+    /// Panics if `name` is already registered for a different template.
+    pub(crate) fn name(&mut self, name: &str, template: &str) {
+        match self.names.get(name) {
+            Some(existing) if &**existing != template => panic!(
+                "unable to name route `{template}` `{name}`: the route `{existing}` already has \
+                 that name"
+            ),
+            Some(_) => {}
+            None => {
+                self.names.insert(name.to_owned(), template.into());
+            }
+        }
+    }
+
+    /// Builds the path of the route named `name`, with its parameters replaced by the values of
+    /// the same name in `params`, or returns `None` if there's no such route or a parameter is
+    /// missing.
+    pub(crate) fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Option<String> {
+        let template = self.names.get(name)?;
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(param, _)| *param == name)
+                .map(|(_, value)| *value)
+        };
+
+        let mut path = String::with_capacity(template.len());
+        let mut rest = &**template;
+        while let Some(start) = rest.find([':', '*']) {
+            path.push_str(&rest[..start]);
+            // Parameters last until the next segment, catch-alls until the end of the path.
+            let end = match &rest[start..start + 1] {
+                ":" => rest[start..]
+                    .find('/')
+                    .map_or(rest.len(), |end| start + end),
+                _ => rest.len(),
+            };
+            path.push_str(param(&rest[start + 1..end])?);
+            rest = &rest[end..];
+        }
+        path.push_str(rest);
+        Some(path)
+    }
+
+    /// Returns the templates, under `prefix`, of the routes of `other` that [`RouteTable::nest`]
+    /// can't register: those with a handler for a method this table already handles at the same
+    /// path, and those conflicting with a route registered before them.
+    pub(crate) fn nest_conflicts(&self, prefix: &str, other: &Self) -> Vec<String> {
+        let paths: Vec<String> = other
+            .templates
+            .iter()
+            .map(|template| join_paths(prefix, template))
+            .collect();
+
+        let mut conflicts: Vec<String> = other
+            .templates
+            .iter()
+            .zip(&paths)
+            .filter(|(template, path)| {
+                self.tree.at(path).is_some_and(|existing| {
+                    existing
+                        .value
+                        .shared_method(other.route(template))
+                        .is_some()
+                })
+            })
+            .map(|(_, path)| path.clone())
+            .collect();
+
+        let templates = self.templates.iter().map(|template| &**template);
+        for err in check_conflicts(templates.chain(paths.iter().map(String::as_str))) {
+            conflicts.push(err.template().to_owned());
+        }
+        conflicts
+    }
+
+    fn route(&self, template: &str) -> &Route<H> {
+        self.tree
+            .at(template)
+            .expect("templates are registered")
+            .value
+    }
+
+    /// The template of every route, in registration order, with the methods it has handlers for
+    /// in alphabetical order, followed by `None` if it has a catchall handler.
+    pub(crate) fn routes(&self) -> Vec<(String, Vec<Option<Method>>)> {
+        self.templates
+            .iter()
+            .map(|template| {
+                let route = self.route(template);
+                let mut methods: Vec<Method> = route.handlers.keys().cloned().collect();
+                methods.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+                let catchall = route.catchall.as_ref().map(|_| None);
+                let methods = methods.into_iter().map(Some).chain(catchall).collect();
+                (template.to_string(), methods)
+            })
+            .collect()
+    }
+
+    /// Templates whose registrations can never be reached, either because they were folded into
+    /// a route with a different template or because a later registration replaced them.
+    pub(crate) fn shadowed(&self) -> &[String] {
+        &self.shadowed
+    }
+
+    /// Creates a watcher that's notified of every change made to the table from now on.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn watch(&mut self) -> RouterWatcher {
+        self.changes.subscribe()
+    }
+
+    fn insert(&mut self, path: &str, method: Option<Method>, handler: H) {
+        self.try_insert(path, method, handler)
+            .unwrap_or_else(|err| panic!("unable to add route to router: {err}"));
+    }
+
+    fn try_insert(
+        &mut self,
+        path: &str,
+        method: Option<Method>,
+        handler: H,
+    ) -> Result<(), RoutingConflictError> {
+        if self.tree.at(path).is_none() {
+            self.tree.insert(
+                path,
+                Route {
+                    template: path.into(),
+                    handlers: HashMap::new(),
+                    catchall: None,
+                },
+            )?;
+            self.templates.push(path.into());
+        }
+
+        let route = self.tree.at_mut(path).expect("route was just inserted");
+
+        let replaced = match method.clone() {
+            Some(method) => route.handlers.insert(method, handler).is_some(),
+            None => route.catchall.replace(handler).is_some(),
+        };
+
+        if replaced || &*route.template != path {
+            self.shadowed.push(path.to_owned());
+        }
+
+        #[cfg(feature = "tokio")]
+        {
+            let template = route.template.to_string();
+            let removed =
+                replaced.then(|| RouteChangeEvent::Removed(template.clone(), method.clone()));
+            let added = RouteChangeEvent::Added(template, method);
+            self.changes.send(removed.into_iter().chain(Some(added)));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::future::Future;
+
+use http::{Method, Request, Response};
+
+use crate::{handler::AsyncHandler, RouteContext, Router};
+
+/// A handler for one method, or for every method if the method is `None`.
+type MethodHandler<Body, Data, Error, ResBody> =
+    (Option<Method>, AsyncHandler<Body, Data, Error, ResBody>);
+
+/// Registers the handlers for the methods of a single path, created with [`Router::route`] and
+/// merged back into the router with [`RouteBuilder::finish`].
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{Request, Response};
+/// use tower::Service;
+/// use router_service::Router;
+///
+/// let mut router = Router::new()
+///     .route("/items/:id")
+///     .get(|_, ctx| async move {
+///         Ok::<_, Infallible>(Response::new(format!("item {}", ctx.param("id").unwrap())))
+///     })
+///     .delete(|_, _| async move { Ok(Response::new("deleted".to_string())) })
+///     .finish();
+///
+/// let req = Request::delete("/items/42").body(String::new()).unwrap();
+/// assert_eq!(router.call(req).await.unwrap().body(), "deleted");
+/// # });
+/// ```
+pub struct RouteBuilder<Body, Data, Error, ResBody = Body> {
+    router: Router<Body, Data, Error, ResBody>,
+    path: String,
+    handlers: Vec<MethodHandler<Body, Data, Error, ResBody>>,
+}
+
+impl<Body, Data, Error, ResBody> Router<Body, Data, Error, ResBody>
+where
+    Body: 'static,
+    Data: 'static,
+    Error: 'static,
+    ResBody: 'static,
+{
+    /// Starts registering the handlers for the methods of `path`.
+    pub fn route(self, path: impl AsRef<str>) -> RouteBuilder<Body, Data, Error, ResBody> {
+        RouteBuilder {
+            router: self,
+            path: path.as_ref().to_owned(),
+            handlers: Vec::new(),
+        }
+    }
+}
+
+impl<Body, Data, Error, ResBody> RouteBuilder<Body, Data, Error, ResBody>
+where
+    Body: 'static,
+    Data: 'static,
+    Error: 'static,
+    ResBody: 'static,
+{
+    /// Handles `GET` requests with `handler`.
+    pub fn get<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::GET, handler)
+    }
+
+    /// Handles `POST` requests with `handler`.
+    pub fn post<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::POST, handler)
+    }
+
+    /// Handles `PUT` requests with `handler`.
+    pub fn put<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::PUT, handler)
+    }
+
+    /// Handles `DELETE` requests with `handler`.
+    pub fn delete<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::DELETE, handler)
+    }
+
+    /// Handles `HEAD` requests with `handler`.
+    pub fn head<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::HEAD, handler)
+    }
+
+    /// Handles `OPTIONS` requests with `handler`.
+    pub fn options<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::OPTIONS, handler)
+    }
+
+    /// Handles `PATCH` requests with `handler`.
+    pub fn patch<HandlerFn, Fut>(self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.method(Method::PATCH, handler)
+    }
+
+    /// Handles `method` requests with `handler`, for any method.
+    pub fn method<HandlerFn, Fut>(mut self, method: Method, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.handlers.push((Some(method), handler.into()));
+        self
+    }
+
+    /// Handles requests with any method without a handler of its own with `handler`.
+    pub fn any<HandlerFn, Fut>(mut self, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: Sync + Send + 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + Send + Sync + 'static,
+    {
+        self.handlers.push((None, handler.into()));
+        self
+    }
+
+    /// Registers the handlers with the router, all at once.
+    ///
+    /// # Panics
+    /// Panics if the path is malformed or conflicts with a route registered before it.
+    pub fn finish(self) -> Router<Body, Data, Error, ResBody> {
+        {
+            let mut inner = self.router.inner.write().unwrap();
+            for (method, handler) in self.handlers {
+                match method {
+                    Some(method) => inner.insert_handler(&self.path, method, handler),
+                    None => inner.insert_catchall(&self.path, handler),
+                }
+            }
+        }
+
+        self.router
+    }
+}
@@ -0,0 +1,18 @@
+use http::Method;
+use sentry_core::{protocol::Breadcrumb, Hub};
+
+/// Reports a handler's error to the request's hub, set up by
+/// [`Router::with_sentry_integration`](crate::Router::with_sentry_integration).
+pub(crate) type ReportError<Error> = fn(&Hub, &Error);
+
+/// Records that the request being handled by the current hub matched the route with the given
+/// template, naming the hub's transaction after the route.
+pub(crate) fn route_matched(method: &Method, template: &str) {
+    sentry_core::add_breadcrumb(Breadcrumb {
+        ty: "http".into(),
+        category: Some("router".into()),
+        message: Some(format!("{method} {template}")),
+        ..Default::default()
+    });
+    sentry_core::configure_scope(|scope| scope.set_transaction(Some(template)));
+}
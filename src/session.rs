@@ -0,0 +1,277 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use http::{header::SET_COOKIE, HeaderMap, HeaderValue};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::response_headers::ContextHeaders;
+use crate::signed_cookie::{cookies, random_value, Signer};
+
+/// The name of the cookie holding the signed session ID.
+const SESSION_COOKIE: &str = "session";
+
+/// The attributes of the session cookie, after its value.
+const COOKIE_ATTRIBUTES: &str = "Path=/; HttpOnly; SameSite=Lax";
+
+/// The values stored in a session, by key.
+pub type SessionData = HashMap<String, serde_json::Value>;
+
+/// Where the data of the sessions set up with
+/// [`Router::with_session`](crate::Router::with_session) is kept, by session ID.
+///
+/// Stores are called while a handler runs, so stores backed by a database or a cache server
+/// should keep their calls short, e.g. by answering from a local copy.
+pub trait SessionStore: Send + Sync + 'static {
+    /// Returns the data of the session with the ID `id`, or `None` if there's no such session,
+    /// e.g. because it expired.
+    fn load(&self, id: &str) -> Option<SessionData>;
+
+    /// Replaces the data of the session with the ID `id`.
+    fn save(&self, id: &str, data: SessionData);
+
+    /// Removes the session with the ID `id`.
+    fn remove(&self, id: &str);
+}
+
+/// A [`SessionStore`] keeping sessions in memory, so they're lost when the process exits and
+/// aren't shared with other processes.
+///
+/// Clones share their sessions.
+#[derive(Clone, Debug, Default)]
+pub struct MemorySessionStore(Arc<Mutex<HashMap<String, SessionData>>>);
+
+impl MemorySessionStore {
+    /// Creates a store without sessions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self, id: &str) -> Option<SessionData> {
+        self.0.lock().unwrap().get(id).cloned()
+    }
+
+    fn save(&self, id: &str, data: SessionData) {
+        self.0.lock().unwrap().insert(id.to_owned(), data);
+    }
+
+    fn remove(&self, id: &str) {
+        self.0.lock().unwrap().remove(id);
+    }
+}
+
+/// The session store and signing key of a router.
+#[derive(Clone)]
+pub(crate) struct Sessions {
+    store: Arc<dyn SessionStore>,
+    signer: Signer,
+}
+
+impl Sessions {
+    pub(crate) fn new(store: impl SessionStore, key: &[u8]) -> Self {
+        Self {
+            store: Arc::new(store),
+            signer: Signer::new(key),
+        }
+    }
+
+    /// Returns the session of the request with `headers`, setting the session cookie through
+    /// `response_headers` when the session is created or destroyed.
+    ///
+    /// Cookies with a missing or invalid signature are ignored, so the request gets a new
+    /// session.
+    pub(crate) fn start(&self, headers: &HeaderMap, response_headers: ContextHeaders) -> Session {
+        let id = cookies(headers, SESSION_COOKIE)
+            .find_map(|value| self.signer.verify(value))
+            .map(str::to_owned);
+
+        Session {
+            sessions: self.clone(),
+            response_headers,
+            state: Arc::new(Mutex::new(State { id, data: None })),
+        }
+    }
+}
+
+/// The server-side session of a request, returned by
+/// [`RouteContext::session`](crate::RouteContext::session).
+///
+/// The session is loaded from the store the first time it's read or written, and saved to it
+/// every time it's written. Sessions are created when a value is first set, which also sets a
+/// `session` cookie on the response with the session's ID and its signature. The cookie is
+/// `HttpOnly` and `SameSite=Lax`, but not `Secure`, as the router can't tell whether it's
+/// served over HTTPS.
+///
+/// Clones refer to the same session.
+#[derive(Clone)]
+pub struct Session {
+    sessions: Sessions,
+    response_headers: ContextHeaders,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    /// The ID of the session, or `None` if the request doesn't have a session yet.
+    id: Option<String>,
+    /// The data of the session, or `None` if it hasn't been loaded yet.
+    data: Option<SessionData>,
+}
+
+impl Session {
+    /// Returns the value stored under `key`, or `None` if there's no such value or it isn't a
+    /// valid `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        let value = self.data(&mut state).get(key)?.clone();
+        serde_json::from_value(value).ok()
+    }
+
+    /// Stores `value` under `key`, replacing any value stored under it before, and creates the
+    /// session if the request doesn't have one yet.
+    ///
+    /// # Errors
+    /// Returns an error if `value` can't be serialized to JSON, e.g. because it's a map with
+    /// keys that aren't strings.
+    pub fn set<T: Serialize>(&self, key: &str, value: T) -> serde_json::Result<()> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.state.lock().unwrap();
+        self.data(&mut state).insert(key.to_owned(), value);
+        self.save(&mut state);
+        Ok(())
+    }
+
+    /// Removes the value stored under `key`, returning whether there was one.
+    pub fn remove(&self, key: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let removed = self.data(&mut state).remove(key).is_some();
+        if removed {
+            self.save(&mut state);
+        }
+        removed
+    }
+
+    /// Removes the session from the store and expires its cookie, e.g. when the user logs out.
+    ///
+    /// Setting a value afterwards creates a new session.
+    pub fn destroy(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.data = Some(SessionData::new());
+        if let Some(id) = state.id.take() {
+            self.sessions.store.remove(&id);
+            let cookie = format!("{SESSION_COOKIE}=; Max-Age=0; {COOKIE_ATTRIBUTES}");
+            self.set_cookie(&cookie);
+        }
+    }
+
+    /// Returns the session's data, loading it first if needed. Sessions missing from the store
+    /// are dropped, so setting a value creates a new one.
+    fn data<'s>(&self, state: &'s mut State) -> &'s mut SessionData {
+        if state.data.is_none() {
+            let data = state
+                .id
+                .as_deref()
+                .and_then(|id| self.sessions.store.load(id));
+            if data.is_none() {
+                state.id = None;
+            }
+            state.data = Some(data.unwrap_or_default());
+        }
+        state.data.as_mut().unwrap()
+    }
+
+    /// Saves the session's data, creating the session first if needed.
+    fn save(&self, state: &mut State) {
+        let id = match &state.id {
+            Some(id) => id.clone(),
+            None => {
+                let id = random_value();
+                let signed = self.sessions.signer.sign(&id);
+                self.set_cookie(&format!("{SESSION_COOKIE}={signed}; {COOKIE_ATTRIBUTES}"));
+                state.id = Some(id.clone());
+                id
+            }
+        };
+        let data = state.data.clone().unwrap_or_default();
+        self.sessions.store.save(&id, data);
+    }
+
+    fn set_cookie(&self, cookie: &str) {
+        let value = HeaderValue::try_from(cookie).unwrap();
+        self.response_headers.append(SET_COOKIE, value);
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header::COOKIE, HeaderMap, HeaderValue, Response};
+
+    use super::{MemorySessionStore, SessionStore, Sessions};
+    use crate::response_headers::ContextHeaders;
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn signed_cookie() {
+        let store = MemorySessionStore::new();
+        let sessions = Sessions::new(store.clone(), KEY);
+        let start = |cookie: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(cookie) = cookie {
+                headers.insert(COOKIE, HeaderValue::try_from(cookie).unwrap());
+            }
+            let response_headers = ContextHeaders::default();
+            let session = sessions.start(&headers, response_headers.clone());
+            (session, response_headers)
+        };
+        let set_cookie = |response_headers: &ContextHeaders| {
+            let mut resp = Response::new(());
+            response_headers.apply(&mut resp);
+            resp.headers()
+                .get("set-cookie")
+                .map(|value| value.to_str().unwrap().to_owned())
+        };
+
+        let (session, response_headers) = start(None);
+        assert_eq!(session.get::<u32>("user"), None);
+        session.set("user", 42).unwrap();
+        let cookie = set_cookie(&response_headers).unwrap();
+        let value = cookie.split(';').next().unwrap();
+        let id = value["session=".len()..].split('.').next().unwrap();
+        assert_eq!(id.len(), 64);
+        assert!(store.load(id).is_some());
+
+        let (session, response_headers) = start(Some(&format!("theme=dark; {value}")));
+        assert_eq!(session.get::<u32>("user"), Some(42));
+        assert_eq!(session.get::<String>("user"), None);
+        session.set("user", 7).unwrap();
+        assert_eq!(set_cookie(&response_headers), None);
+        assert_eq!(start(Some(value)).0.get::<u32>("user"), Some(7));
+
+        // Tampered IDs and signatures are ignored.
+        let other = if id.ends_with('0') { "1" } else { "0" };
+        let forged = value.replace(id, &format!("{}{other}", &id[..63]));
+        assert_eq!(start(Some(&forged)).0.get::<u32>("user"), None);
+        let forged = format!("session={id}.00");
+        assert_eq!(start(Some(&forged)).0.get::<u32>("user"), None);
+
+        let (session, response_headers) = start(Some(value));
+        assert!(session.remove("user"));
+        assert!(!session.remove("user"));
+        session.destroy();
+        assert!(store.load(id).is_none());
+        assert_eq!(
+            set_cookie(&response_headers).unwrap(),
+            "session=; Max-Age=0; Path=/; HttpOnly; SameSite=Lax"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use http::{header::COOKIE, HeaderMap};
+use sha2::Sha256;
+
+/// Signs cookie values with HMAC-SHA256, so that clients can't pick their own values.
+#[derive(Clone)]
+pub(crate) struct Signer {
+    key: Arc<[u8]>,
+}
+
+impl Signer {
+    /// # Panics
+    /// Panics if `key` is shorter than 32 bytes.
+    pub(crate) fn new(key: &[u8]) -> Self {
+        assert!(
+            key.len() >= 32,
+            "signing keys must be at least 32 bytes long"
+        );
+        Self { key: key.into() }
+    }
+
+    /// Returns `value` followed by a `.` and its signature, hex-encoded.
+    pub(crate) fn sign(&self, value: &str) -> String {
+        let signature = self.mac(value).finalize().into_bytes();
+        format!("{value}.{}", hex(&signature))
+    }
+
+    /// Returns the value of a string returned by [`Signer::sign`], or `None` if it's not signed
+    /// with this signer's key.
+    pub(crate) fn verify<'s>(&self, signed: &'s str) -> Option<&'s str> {
+        let (value, signature) = signed.rsplit_once('.')?;
+        let signature = unhex(signature)?;
+        self.mac(value).verify_slice(&signature).ok()?;
+        Some(value)
+    }
+
+    fn mac(&self, value: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+        mac.update(value.as_bytes());
+        mac
+    }
+}
+
+/// Returns 32 random bytes, hex-encoded, e.g. for a session ID.
+pub(crate) fn random_value() -> String {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes).expect("unable to generate random bytes");
+    hex(&bytes)
+}
+
+/// Returns the values of the cookies named `name` in the `Cookie` headers of a request.
+pub(crate) fn cookies<'h>(headers: &'h HeaderMap, name: &'h str) -> impl Iterator<Item = &'h str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(move |cookie| {
+            let (cookie_name, value) = cookie.trim().split_once('=')?;
+            (cookie_name == name).then_some(value)
+        })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn unhex(hex: &str) -> Option<Vec<u8>> {
+    let pairs = hex.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).ok()?;
+            if !pair.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+                return None;
+            }
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
@@ -0,0 +1,148 @@
+//! Utilities for testing routers, available with the `testing` feature.
+//!
+//! A router is a plain [`Service`], so tests can call it directly. The [`TestClient`] takes care
+//! of the boilerplate around that, and can follow redirects the way an HTTP client would.
+//!
+//! Redirects are followed by wrapping the router in `tower_http`'s [`FollowRedirect`], which
+//! works with any router outside of tests too, e.g. when a router is used to answer requests made
+//! by a client in the same process. It re-issues the request to the `Location` of every
+//! redirect, which it resolves against the request's URI, so requests need absolute URIs such
+//! as `http://localhost/old` for redirects to be followed. The router's body type has to
+//! implement `http_body::Body`, like `String` or `hyper::Body` do, and since the standard policy
+//! doesn't copy bodies, requests with a body only follow redirects that drop it, like
+//! `303 See Other`.
+
+use futures_util::future::poll_fn;
+use http::Request;
+use tower::Service;
+pub use tower_http::follow_redirect::FollowRedirect;
+
+/// A client that sends requests to a router, or any other service, in tests.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{header::LOCATION, Response, StatusCode};
+/// use router_service::{testing::TestClient, Router};
+///
+/// let router: Router<String, (), Infallible> = Router::new()
+///     .get("/old", |_, _| async move {
+///         let resp = Response::builder()
+///             .status(StatusCode::MOVED_PERMANENTLY)
+///             .header(LOCATION, "/new")
+///             .body(String::new())
+///             .unwrap();
+///         Ok(resp)
+///     })
+///     .get("/new", |_, _| async move { Ok(Response::new("new".to_string())) });
+/// let mut client = TestClient::new(router.clone());
+///
+/// let resp = client.get("/old").await.unwrap();
+/// assert_eq!(resp.status(), StatusCode::MOVED_PERMANENTLY);
+///
+/// let mut client = TestClient::new(router).with_redirect_following();
+/// let resp = client.get("/old").await.unwrap();
+/// assert_eq!(resp.body(), "new");
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct TestClient<S> {
+    service: S,
+}
+
+impl<S> TestClient<S> {
+    /// Creates a client that sends requests to `service`.
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    /// Makes the client follow redirects, with the standard policy of [`FollowRedirect`]: at
+    /// most 20 redirects are followed, and credentials aren't passed on to other origins.
+    pub fn with_redirect_following(self) -> TestClient<FollowRedirect<S>>
+    where
+        S: Clone,
+    {
+        TestClient::new(FollowRedirect::new(self.service))
+    }
+
+    /// Sends `req` to the service once it's ready.
+    pub async fn send<Body>(&mut self, req: Request<Body>) -> Result<S::Response, S::Error>
+    where
+        S: Service<Request<Body>>,
+    {
+        poll_fn(|cx| self.service.poll_ready(cx)).await?;
+        self.service.call(req).await
+    }
+
+    /// Sends a `GET` request without a body to `path`, on `http://localhost`.
+    pub async fn get<Body>(&mut self, path: &str) -> Result<S::Response, S::Error>
+    where
+        S: Service<Request<Body>>,
+        Body: Default,
+    {
+        let req = Request::get(format!("http://localhost{path}"))
+            .body(Body::default())
+            .expect("invalid path");
+        self.send(req).await
+    }
+
+    /// Returns the service the client sends requests to.
+    pub fn into_inner(self) -> S {
+        self.service
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use http::{header::LOCATION, Method, Request, Response, StatusCode};
+
+    use super::TestClient;
+    use crate::unsync::Router;
+
+    fn redirect(status: StatusCode, location: &'static str) -> Response<String> {
+        Response::builder()
+            .status(status)
+            .header(LOCATION, location)
+            .body(String::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn follows_redirects() {
+        futures::executor::block_on(async move {
+            let router = Router::new()
+                .get("/a", |_, _| async move {
+                    Ok::<_, Infallible>(redirect(StatusCode::FOUND, "/b"))
+                })
+                .get("/b", |_, _| async move {
+                    Ok(redirect(
+                        StatusCode::TEMPORARY_REDIRECT,
+                        "http://localhost/c",
+                    ))
+                })
+                .post("/form", |_, _| async move {
+                    Ok(redirect(StatusCode::SEE_OTHER, "/c"))
+                })
+                .get("/c", |req, _| async move {
+                    Ok(Response::new(format!("{} {}", req.method(), req.uri())))
+                });
+            let mut client = TestClient::new(router).with_redirect_following();
+
+            let resp = client.get("/a").await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+            assert_eq!(resp.body(), "GET http://localhost/c");
+
+            let req = Request::builder()
+                .method(Method::POST)
+                .uri("http://localhost/form")
+                .body("name=alice".to_string())
+                .unwrap();
+            let resp = client.send(req).await.unwrap();
+            assert_eq!(resp.body(), "GET http://localhost/c");
+        });
+    }
+}
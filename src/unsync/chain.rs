@@ -0,0 +1,77 @@
+use std::task::{Context, Poll};
+
+use futures_util::future::LocalBoxFuture;
+use http::{Request, Response};
+use tower::Service;
+
+use crate::chain::call_chain;
+
+/// A service that tries several services in order, returning the first response that isn't
+/// `404 Not Found`.
+///
+/// This is the unsynchronized counterpart of [`crate::FallbackChain`], for services whose futures
+/// aren't [`Send`]. If every service responds with `404`, the last service's response is
+/// returned.
+///
+/// Since the request has to be replayed, its body must be [`Clone`]. Every service but the last
+/// receives a copy of the request's method, URI, version, headers, and body, but not its
+/// extensions, which are only passed to the last service.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{Request, Response};
+/// use tower::Service;
+/// use router_service::unsync::{FallbackChain, Router};
+///
+/// let v1: Router<String, (), Infallible> = Router::new()
+///     .get("/users", |_, _| async move { Ok(Response::new("v1 users".into())) })
+///     .get("/posts", |_, _| async move { Ok(Response::new("v1 posts".into())) });
+/// let v2 = Router::new()
+///     .get("/users", |_, _| async move { Ok(Response::new("v2 users".into())) });
+///
+/// let mut chain = FallbackChain::new(vec![v2, v1]);
+///
+/// let resp = chain.call(Request::get("/users").body(String::new()).unwrap()).await.unwrap();
+/// assert_eq!(resp.body(), "v2 users");
+///
+/// let resp = chain.call(Request::get("/posts").body(String::new()).unwrap()).await.unwrap();
+/// assert_eq!(resp.body(), "v1 posts");
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct FallbackChain<S> {
+    services: Vec<S>,
+}
+
+impl<S> FallbackChain<S> {
+    /// Creates a chain that tries `services` in order.
+    pub fn new(services: Vec<S>) -> Self {
+        Self { services }
+    }
+}
+
+impl<S, Body, ResBody> Service<Request<Body>> for FallbackChain<S>
+where
+    S: Service<Request<Body>, Response = Response<ResBody>>,
+    S: Clone + 'static,
+    S::Error: 'static,
+    Body: Clone + 'static,
+    ResBody: Default + 'static,
+{
+    type Response = Response<ResBody>;
+
+    type Error = S::Error;
+
+    type Future = LocalBoxFuture<'static, Result<Response<ResBody>, S::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        Box::pin(call_chain(self.services.clone(), req))
+    }
+}
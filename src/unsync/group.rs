@@ -0,0 +1,282 @@
+use std::{future::Future, rc::Rc};
+
+use futures_util::future::LocalBoxFuture;
+use http::{Method, Request, Response, StatusCode};
+
+use crate::{
+    handler::AsyncUnsyncHandler,
+    route::join_paths,
+    unsync::{RouteContext, Router},
+};
+
+type Guard<Body> = dyn Fn(&Request<Body>) -> Result<(), StatusCode> + 'static;
+
+type Middleware<Body, Data, Error, ResBody> = dyn Fn(
+        Request<Body>,
+        RouteContext<Data>,
+        Next<Body, Data, Error, ResBody>,
+    ) -> LocalBoxFuture<'static, Result<Response<ResBody>, Error>>
+    + 'static;
+
+type DataFn<Data> = dyn Fn() -> Data + 'static;
+
+/// The remainder of a group's middleware chain, ending with the route's handler.
+pub struct Next<Body, Data, Error, ResBody = Body>(AsyncUnsyncHandler<Body, Data, Error, ResBody>);
+
+impl<Body, Data, Error, ResBody> Next<Body, Data, Error, ResBody> {
+    /// Runs the rest of the middleware chain and the route's handler.
+    pub fn run(
+        self,
+        req: Request<Body>,
+        ctx: RouteContext<Data>,
+    ) -> impl Future<Output = Result<Response<ResBody>, Error>> {
+        (self.0 .0)(req, ctx)
+    }
+}
+
+/// A set of routes sharing a path prefix, guards, middleware, and optionally data.
+///
+/// Created with [`Router::group`] and merged back into the router with [`Group::finish`].
+///
+/// Every request to a route in the group first passes through the group's guards in the order
+/// they were added, then through its middleware with the first added middleware outermost, and
+/// finally reaches the route's handler. Settings only apply to routes registered after them.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::convert::Infallible;
+///
+/// use http::{header::AUTHORIZATION, Request, Response, StatusCode};
+/// use tower::Service;
+/// use router_service::unsync::Router;
+///
+/// let mut router: Router<(), (), Infallible> = Router::new()
+///     .get("/", |_, _| async move { Ok(Response::new(())) })
+///     .group("/admin")
+///     .guard(|req| match req.headers().contains_key(AUTHORIZATION) {
+///         true => Ok(()),
+///         false => Err(StatusCode::UNAUTHORIZED),
+///     })
+///     .get("/users", |_, _| async move { Ok(Response::new(())) })
+///     .finish();
+///
+/// let req = Request::get("/admin/users").body(()).unwrap();
+/// let resp = router.call(req).await.unwrap();
+/// assert_eq!(resp.status(), 401);
+/// # });
+/// ```
+pub struct Group<Body, Data, Error, ResBody = Body> {
+    router: Router<Body, Data, Error, ResBody>,
+    prefix: String,
+    guards: Vec<Rc<Guard<Body>>>,
+    middleware: Vec<Rc<Middleware<Body, Data, Error, ResBody>>>,
+    data: Option<Rc<DataFn<Data>>>,
+}
+
+impl<Body, Data, Error, ResBody> Router<Body, Data, Error, ResBody>
+where
+    Body: 'static,
+    Data: 'static,
+    Error: 'static,
+    ResBody: Default + 'static,
+{
+    /// Starts a group of routes sharing the path `prefix`.
+    pub fn group(self, prefix: impl AsRef<str>) -> Group<Body, Data, Error, ResBody> {
+        Group {
+            router: self,
+            prefix: prefix.as_ref().to_owned(),
+            guards: Vec::new(),
+            middleware: Vec::new(),
+            data: None,
+        }
+    }
+}
+
+impl<Body, Data, Error, ResBody> Group<Body, Data, Error, ResBody>
+where
+    Body: 'static,
+    Data: 'static,
+    Error: 'static,
+    ResBody: Default + 'static,
+{
+    /// Adds a guard that rejects requests with the returned status code before they reach any
+    /// middleware or handler.
+    pub fn guard<GuardFn>(mut self, guard: GuardFn) -> Self
+    where
+        GuardFn: Fn(&Request<Body>) -> Result<(), StatusCode> + 'static,
+    {
+        self.guards.push(Rc::new(guard));
+        self
+    }
+
+    /// Adds middleware that wraps every handler in the group.
+    pub fn middleware<MiddlewareFn, Fut>(mut self, middleware: MiddlewareFn) -> Self
+    where
+        MiddlewareFn:
+            Fn(Request<Body>, RouteContext<Data>, Next<Body, Data, Error, ResBody>) -> Fut,
+        MiddlewareFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.middleware.push(Rc::new(move |req, ctx, next| {
+            Box::pin(middleware(req, ctx, next))
+        }));
+        self
+    }
+
+    /// Passes `data` to the group's handlers instead of the router's data.
+    pub fn with_data(mut self, data: Data) -> Self
+    where
+        Data: Clone,
+    {
+        self.data = Some(Rc::new(move || data.clone()));
+        self
+    }
+
+    /// Registers a route requiring the `GET` method.
+    pub fn get<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::GET), handler)
+    }
+
+    /// Registers a route requiring the `POST` method.
+    pub fn post<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::POST), handler)
+    }
+
+    /// Registers a route requiring the `PUT` method.
+    pub fn put<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::PUT), handler)
+    }
+
+    /// Registers a route requiring the `DELETE` method.
+    pub fn delete<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::DELETE), handler)
+    }
+
+    /// Registers a route requiring the `HEAD` method.
+    pub fn head<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::HEAD), handler)
+    }
+
+    /// Registers a route requiring the `OPTIONS` method.
+    pub fn options<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::OPTIONS), handler)
+    }
+
+    /// Registers a route requiring the `PATCH` method.
+    pub fn patch<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, Some(Method::PATCH), handler)
+    }
+
+    /// Registers a route matching any method.
+    pub fn any<HandlerFn, Fut>(self, path: impl AsRef<str>, handler: HandlerFn) -> Self
+    where
+        HandlerFn: Fn(Request<Body>, RouteContext<Data>) -> Fut,
+        HandlerFn: 'static,
+        Fut: Future<Output = Result<Response<ResBody>, Error>> + 'static,
+    {
+        self.insert_handler(path, None, handler)
+    }
+
+    /// Merges the group's routes back into the router.
+    pub fn finish(self) -> Router<Body, Data, Error, ResBody> {
+        self.router
+    }
+
+    fn insert_handler<H>(self, path: impl AsRef<str>, method: Option<Method>, handler: H) -> Self
+    where
+        H: Into<AsyncUnsyncHandler<Body, Data, Error, ResBody>>,
+    {
+        let path = join_paths(&self.prefix, path.as_ref());
+        let handler = self.wrap(handler.into());
+
+        {
+            let mut inner = self.router.inner.write().unwrap();
+            match method {
+                Some(method) => inner.insert_handler(&path, method, handler),
+                None => inner.insert_catchall(&path, handler),
+            }
+        }
+
+        self
+    }
+
+    fn wrap(
+        &self,
+        handler: AsyncUnsyncHandler<Body, Data, Error, ResBody>,
+    ) -> AsyncUnsyncHandler<Body, Data, Error, ResBody> {
+        let handler = self
+            .middleware
+            .iter()
+            .rev()
+            .fold(handler, |next, middleware| {
+                let middleware = middleware.clone();
+                let name = next.1;
+                AsyncUnsyncHandler(
+                    Rc::new(move |req, ctx| middleware(req, ctx, Next(next.clone()))),
+                    name,
+                )
+            });
+
+        let guards = self.guards.clone();
+        let data = self.data.clone();
+
+        let name = handler.1;
+        AsyncUnsyncHandler(
+            Rc::new(move |req, mut ctx| {
+                for guard in &guards {
+                    if let Err(status) = guard(&req) {
+                        return Box::pin(async move {
+                            Ok(Response::builder()
+                                .status(status)
+                                .body(ResBody::default())
+                                .unwrap())
+                        });
+                    }
+                }
+
+                if let Some(data) = &data {
+                    ctx.data = data();
+                }
+
+                (handler.0)(req, ctx)
+            }),
+            name,
+        )
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    cell::Cell,
+    convert::Infallible,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::future::{ready, Ready};
+use tower::Service;
+
+use crate::{unsync::Router, RequestContext};
+
+type AugmentFn<Data, ConnInfo> = dyn Fn(&Data, &ConnInfo) -> Data;
+type DescribeFn<ConnInfo> = dyn Fn(RequestContext, &ConnInfo) -> RequestContext;
+
+/// A `tower::MakeService` that creates a router for every connection, with data
+/// derived from the connection.
+///
+/// This is the unsynchronized counterpart of [`crate::MakeRouter`]: `augment` is called with the
+/// router's data and the connection's info, and the connection is served by a router whose
+/// handlers see the result. The route table is shared between every connection's router, and
+/// every connection gets a [`RequestContext`] with an ID of its own.
+///
+/// # Example
+/// ```
+/// # futures::executor::block_on(async move {
+/// use std::{convert::Infallible, net::SocketAddr};
+///
+/// use http::{Request, Response};
+/// use tower::Service;
+/// use router_service::unsync::{MakeRouter, Router};
+///
+/// let router = Router::with_data(None::<SocketAddr>).get("/", |_, ctx| async move {
+///     Ok::<_, Infallible>(Response::new(ctx.data.unwrap().to_string()))
+/// });
+/// let mut make_router = MakeRouter::new(router, |_, addr: &SocketAddr| Some(*addr));
+///
+/// let addr = SocketAddr::from(([127, 0, 0, 1], 50000));
+/// let mut router = make_router.call(&addr).await.unwrap();
+///
+/// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await.unwrap();
+/// assert_eq!(resp.body(), "127.0.0.1:50000");
+/// # });
+/// ```
+pub struct MakeRouter<Body, Data, Error, ConnInfo, ResBody = Body> {
+    router: Router<Body, Data, Error, ResBody>,
+    augment: Rc<AugmentFn<Data, ConnInfo>>,
+    describe: Option<Rc<DescribeFn<ConnInfo>>>,
+    connections: Rc<Cell<u64>>,
+}
+
+impl<Body, Data, Error, ConnInfo, ResBody> MakeRouter<Body, Data, Error, ConnInfo, ResBody> {
+    /// Creates a [`MakeRouter`] whose routers' data is computed by `augment` from `router`'s data
+    /// and each connection's info.
+    pub fn new<F>(router: Router<Body, Data, Error, ResBody>, augment: F) -> Self
+    where
+        F: Fn(&Data, &ConnInfo) -> Data + 'static,
+    {
+        Self {
+            router,
+            augment: Rc::new(augment),
+            describe: None,
+            connections: Default::default(),
+        }
+    }
+
+    /// Fills in the [`RequestContext`] of every connection with `describe`, called with a context
+    /// holding just the connection's ID and the connection's info.
+    ///
+    /// # Example
+    /// ```
+    /// # futures::executor::block_on(async move {
+    /// use std::{convert::Infallible, net::SocketAddr};
+    ///
+    /// use http::{Request, Response};
+    /// use tower::Service;
+    /// use router_service::unsync::{MakeRouter, Router};
+    ///
+    /// let router = Router::new().get("/", |_, ctx| async move {
+    ///     let conn = ctx.request_context();
+    ///     let body = format!("#{} from {}", conn.connection_id(), conn.remote_addr().unwrap());
+    ///     Ok::<_, Infallible>(Response::new(body))
+    /// });
+    /// let mut make_router = MakeRouter::new(router, |_, _: &SocketAddr| ())
+    ///     .request_context(|ctx, addr| ctx.with_remote_addr(*addr));
+    ///
+    /// let addr = SocketAddr::from(([127, 0, 0, 1], 50000));
+    /// let mut router = make_router.call(&addr).await.unwrap();
+    ///
+    /// let resp = router.call(Request::get("/").body(String::new()).unwrap()).await.unwrap();
+    /// assert_eq!(resp.body(), "#1 from 127.0.0.1:50000");
+    /// # });
+    /// ```
+    pub fn request_context<F>(mut self, describe: F) -> Self
+    where
+        F: Fn(RequestContext, &ConnInfo) -> RequestContext + 'static,
+    {
+        self.describe = Some(Rc::new(describe));
+        self
+    }
+}
+
+impl<Body, Data, Error, ConnInfo, ResBody> Clone
+    for MakeRouter<Body, Data, Error, ConnInfo, ResBody>
+{
+    fn clone(&self) -> Self {
+        Self {
+            router: self.router.clone(),
+            augment: self.augment.clone(),
+            describe: self.describe.clone(),
+            connections: self.connections.clone(),
+        }
+    }
+}
+
+impl<'a, Body, Data, Error, ConnInfo, ResBody> Service<&'a ConnInfo>
+    for MakeRouter<Body, Data, Error, ConnInfo, ResBody>
+{
+    type Response = Router<Body, Data, Error, ResBody>;
+
+    type Error = Infallible;
+
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, conn: &'a ConnInfo) -> Self::Future {
+        let mut router = self.router.clone();
+        router.data = Arc::new((self.augment)(&self.router.data, conn));
+
+        self.connections.set(self.connections.get() + 1);
+        let ctx = RequestContext::new(self.connections.get());
+        let ctx = match &self.describe {
+            Some(describe) => describe(ctx, conn),
+            None => ctx,
+        };
+        router.request_context = Some(Arc::new(ctx));
+        ready(Ok(router))
+    }
+}
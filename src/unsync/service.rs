@@ -1,38 +1,264 @@
+#[cfg(feature = "sentry")]
+use std::sync::Arc;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 use std::{
     collections::HashMap,
     future::Future,
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
     pin::Pin,
     task::{Context, Poll},
 };
 
-use futures_util::{future::LocalBoxFuture, FutureExt};
-use http::{Request, Response, StatusCode};
+use futures_util::{future::LocalBoxFuture, FutureExt, TryFutureExt};
+use http::{
+    header::{ALLOW, CONTENT_LENGTH},
+    HeaderValue, Method, Request, Response, StatusCode,
+};
+#[cfg(feature = "sentry")]
+use sentry_core::{Hub, SentryFutureExt};
 use tower::Service;
+#[cfg(feature = "datadog")]
+use tracing::Instrument;
 
-use crate::unsync::{RouteContext, Router};
+#[cfg(feature = "tracing")]
+use crate::access_log::AccessLogEntry;
+#[cfg(feature = "csrf")]
+use crate::csrf;
+#[cfg(feature = "datadog")]
+use crate::datadog::DatadogTrace;
+#[cfg(feature = "sessions")]
+use crate::session::Session;
+use crate::{
+    body_limit::{exceeds_max_size, BodyLimit, BodyTooLarge},
+    https::https_redirect,
+    path::{route_path, trim_trailing_slash},
+    query::parse_query,
+    response::{panic_response, PanicHandler},
+    response_headers::ContextHeaders,
+    unsync::{RouteContext, Router},
+    RequestContext,
+};
 
-impl<Body, Data, Error> Service<Request<Body>> for Router<Body, Data, Error>
+impl<Body, Data, Error, ResBody> Service<Request<Body>> for Router<Body, Data, Error, ResBody>
 where
-    Body: Default,
+    Body: Default + 'static,
     Data: Clone,
+    Error: 'static,
+    ResBody: Default + 'static,
 {
-    type Response = Response<Body>;
+    type Response = Response<ResBody>;
 
     type Error = Error;
 
-    type Future = ResponseFuture<Body, Error>;
+    type Future = ResponseFuture<ResBody, Error>;
 
     fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, req: Request<Body>) -> Self::Future {
-        let uri = req.uri();
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        if let Some(router) = self.hosts.as_ref().and_then(|hosts| hosts.find(&req)) {
+            let mut router = router.clone();
+            if router.request_context.is_none() {
+                router.request_context = self.request_context.clone();
+            }
+            return router.call(req);
+        }
+
+        if let Some(ctx) = &self.request_context {
+            req.extensions_mut().insert(RequestContext::clone(ctx));
+        }
+
+        #[cfg(feature = "tracing")]
+        if let Some(format) = self.log_format {
+            let entry = AccessLogEntry::new(format, &req);
+            let fut = self.dispatch_in_hub(req);
+            return ResponseFuture(Box::pin(
+                fut.inspect(move |res| entry.finish(res.as_ref().ok())),
+            ));
+        }
+
+        self.dispatch_in_hub(req)
+    }
+}
+
+impl<Body, Data, Error, ResBody> Router<Body, Data, Error, ResBody>
+where
+    Body: Default + 'static,
+    Data: Clone,
+    Error: 'static,
+    ResBody: Default + 'static,
+{
+    /// Answers `req`, applying the request and response transforms, the router-wide response
+    /// headers and `Content-Length`.
+    fn dispatch(&self, req: Request<Body>) -> ResponseFuture<ResBody, Error> {
+        let req = match self.request_transform {
+            Some(transform) => transform(req),
+            None => req,
+        };
+        let allowed_origin = self
+            .cors
+            .as_ref()
+            .and_then(|cors| cors.allowed_origin(&req));
+        // Responses to `HEAD` requests have no body, but the length of the `GET` response's.
+        let content_length = self.content_length.filter(|_| req.method() != Method::HEAD);
+
+        let guard = match &self.drain {
+            Some(drain) => match drain.enter() {
+                Some(guard) => Some(guard),
+                None => return ResponseFuture::status(StatusCode::SERVICE_UNAVAILABLE),
+            },
+            None => None,
+        };
+
+        let fut = match self.panic_handler {
+            Some(panic_handler) => self.route_catching_panics(req, panic_handler),
+            None => self.route_request(req),
+        };
+        let fut = match self.error_response {
+            Some(error_response) => ResponseFuture(Box::pin(
+                fut.map(move |result| result.or_else(|err| Ok(error_response(err)))),
+            )),
+            None => fut,
+        };
+        let fut = match guard {
+            Some(guard) => ResponseFuture(Box::pin(guard.run(fut))),
+            None => fut,
+        };
+
+        let transform = self.response_transform;
+        if self.response_headers.is_empty()
+            && allowed_origin.is_none()
+            && content_length.is_none()
+            && transform.is_none()
+        {
+            return fut;
+        }
+
+        let headers = self.response_headers.clone();
+        ResponseFuture(Box::pin(fut.map_ok(move |resp| {
+            let mut resp = match transform {
+                Some(transform) => transform(resp),
+                None => resp,
+            };
+            headers.apply(&mut resp);
+            if let Some(origin) = allowed_origin {
+                origin.apply(&mut resp);
+            }
+            if let Some(set_content_length) = content_length {
+                set_content_length(&mut resp);
+            }
+            resp
+        })))
+    }
+
+    /// Routes `req` like [`Router::route_request`], answering panics in the handler, both when
+    /// it's called and while its future is polled, with `panic_handler`.
+    fn route_catching_panics(
+        &self,
+        req: Request<Body>,
+        panic_handler: PanicHandler<ResBody>,
+    ) -> ResponseFuture<ResBody, Error> {
+        let fut = match panic::catch_unwind(AssertUnwindSafe(|| self.route_request(req))) {
+            Ok(fut) => fut,
+            Err(panic) => {
+                return ResponseFuture(Box::pin(
+                    async move { Ok(panic_response(panic, panic_handler)) },
+                ))
+            }
+        };
+
+        ResponseFuture(Box::pin(AssertUnwindSafe(fut).catch_unwind().map(
+            move |res| res.unwrap_or_else(|panic| Ok(panic_response(panic, panic_handler))),
+        )))
+    }
+
+    fn route_request(&self, req: Request<Body>) -> ResponseFuture<ResBody, Error> {
+        if self.require_https {
+            if let Some(redirect) = https_redirect(&req) {
+                return ResponseFuture::empty(redirect);
+            }
+        }
+
+        if let Some(preflight) = self.cors.as_ref().and_then(|cors| cors.preflight(&req)) {
+            return ResponseFuture::empty(preflight);
+        }
+
+        if let Some(limit) = &self.api_key_limit {
+            if let Err(resp) = limit.check(req.headers()) {
+                return ResponseFuture::empty(resp);
+            }
+        }
+
+        #[cfg(feature = "csrf")]
+        if let Some((csrf, into_body)) = &self.csrf {
+            if req.method() == Method::GET && route_path(&req) == csrf::TOKEN_PATH {
+                let resp = csrf.token_response(req.headers());
+                let into_body = *into_body;
+                return ResponseFuture(Box::pin(async move { Ok(resp.map(into_body)) }));
+            }
+            if let Err(resp) = csrf.check(req.method(), req.headers()) {
+                return ResponseFuture::empty(resp);
+            }
+        }
+
+        let (max, limit_body) = match self.max_request_size {
+            Some(max_request_size) => max_request_size,
+            None => return self.route_checked(req),
+        };
+        if exceeds_max_size(&req, max) {
+            return ResponseFuture::status(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        let limit = BodyLimit::new(max);
+        let (parts, body) = req.into_parts();
+        let req = match limit_body(body, limit.clone()) {
+            Ok(body) => Request::from_parts(parts, body),
+            Err(BodyTooLarge) => return ResponseFuture::status(StatusCode::PAYLOAD_TOO_LARGE),
+        };
+
+        // Handlers may answer a body that failed while they read it with any response, so the
+        // router answers it instead.
+        let fut = self.route_checked(req);
+        ResponseFuture(Box::pin(fut.map(move |res| {
+            if !limit.exceeded() {
+                return res;
+            }
+            let mut resp = Response::new(ResBody::default());
+            *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+            Ok(resp)
+        })))
+    }
+
+    /// Routes `req` to its handler, once the checks that reject requests before routing passed.
+    fn route_checked(&self, req: Request<Body>) -> ResponseFuture<ResBody, Error> {
+        if !self.content_types.allows(&req) {
+            return ResponseFuture::status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        }
+
+        let req = if self.any_trailing_slash {
+            trim_trailing_slash(req)
+        } else {
+            req
+        };
 
         let inner = self.inner.read().unwrap();
 
-        if let Ok(node) = inner.at(uri.path()) {
+        let with_slash;
+        let mut node = inner.at(route_path(&req));
+        if node.is_none() && self.any_trailing_slash {
+            with_slash = format!("{}/", route_path(&req));
+            node = inner.at(&with_slash);
+        }
+
+        if let Some(node) = node {
+            if matches!(self.max_params, Some(max) if node.params.len() > max) {
+                return ResponseFuture::status(StatusCode::BAD_REQUEST);
+            }
+
             let route = node.value;
+            let response_headers = ContextHeaders::default();
             let ctx = RouteContext {
                 params: {
                     let mut params = HashMap::with_capacity(node.params.len());
@@ -41,30 +267,279 @@ where
                     }
                     params
                 },
-                data: self.data.clone(),
+                data: Data::clone(&self.data),
+                base_path: self.base_path.clone(),
+                template: Some(route.template.clone()),
+                request_context: self.request_context.clone(),
+                client_ip: self.client_ip(&req),
+                path_and_query: req.uri().path_and_query().cloned(),
+                query: parse_query(req.uri().query()),
+                response_headers: response_headers.clone(),
+                allowed_methods: Vec::new(),
+                #[cfg(feature = "sessions")]
+                session: self.session(&req, response_headers),
             };
 
-            if let Some(handler) = route.handlers.get(req.method()) {
-                return ResponseFuture((handler.0)(req, ctx));
+            let mut handler = route.handlers.get(req.method()).or(route.catchall.as_ref());
+            // Without a handler of its own, `HEAD` is answered by the `GET` handler, minus the
+            // body.
+            let head_from_get = handler.is_none() && self.auto_head && req.method() == Method::HEAD;
+            if head_from_get {
+                handler = route.handlers.get(&Method::GET);
             }
+            if let Some(handler) = handler {
+                #[cfg(feature = "tokio")]
+                self.shadow(&req);
+                let method = req.method().clone();
+                #[cfg(feature = "sentry")]
+                if self.sentry.is_some() {
+                    crate::sentry::route_matched(&method, &route.template);
+                }
+                #[cfg(feature = "datadog")]
+                let mut req = req;
+                #[cfg(feature = "datadog")]
+                let span = self.datadog.as_ref().map(|service| {
+                    let resource = format!("{method} {}", route.template);
+                    let trace = DatadogTrace::extract(req.headers(), service.clone(), resource);
+                    let span = trace.span();
+                    req.extensions_mut().insert(trace);
+                    span
+                });
+                let response_headers = ctx.response_headers.clone();
+                let fut = (handler.0)(req, ctx).map_ok(move |mut resp| {
+                    response_headers.apply(&mut resp);
+                    if head_from_get {
+                        resp.map(|_| ResBody::default())
+                    } else {
+                        resp
+                    }
+                });
+                let fut = ResponseFuture(Box::pin(fut));
+                #[cfg(feature = "datadog")]
+                let fut = match span {
+                    Some(span) => ResponseFuture(Box::pin(fut.instrument(span))),
+                    None => fut,
+                };
+                return self.observe(&route.template, &method, fut);
+            }
+
+            // The path has a route, just not for this method. `OPTIONS` requests are answered
+            // with the methods it does have, and others by the `method_not_allowed` handler, if
+            // there is one.
+            if !route.handlers.is_empty() {
+                let not_options = req.method() != Method::OPTIONS;
+                if let Some(handler) = self.method_not_allowed.as_ref().filter(|_| not_options) {
+                    let allow = route.allow(self.auto_head);
+                    let ctx = RouteContext {
+                        allowed_methods: route.allowed_methods(self.auto_head),
+                        ..ctx
+                    };
+                    let response_headers = ctx.response_headers.clone();
+                    let fut = (handler.0)(req, ctx).map_ok(move |mut resp| {
+                        response_headers.apply(&mut resp);
+                        resp.headers_mut().entry(ALLOW).or_insert(allow);
+                        resp
+                    });
+                    return ResponseFuture(Box::pin(fut));
+                }
 
-            if let Some(handler) = &route.catchall {
-                return ResponseFuture((handler.0)(req, ctx));
+                let mut resp = Response::new(());
+                if req.method() == Method::OPTIONS {
+                    resp.headers_mut()
+                        .insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+                } else {
+                    *resp.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                }
+                resp.headers_mut()
+                    .insert(ALLOW, route.allow(self.auto_head));
+                return ResponseFuture::empty(resp);
             }
         }
 
-        ResponseFuture(Box::pin(async move {
-            Ok(Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(Body::default())
-                .unwrap())
-        }))
+        if let Some(fallback) = &self.fallback {
+            let response_headers = ContextHeaders::default();
+            let ctx = RouteContext {
+                params: HashMap::new(),
+                data: Data::clone(&self.data),
+                base_path: self.base_path.clone(),
+                template: None,
+                request_context: self.request_context.clone(),
+                client_ip: self.client_ip(&req),
+                path_and_query: req.uri().path_and_query().cloned(),
+                query: parse_query(req.uri().query()),
+                response_headers: response_headers.clone(),
+                allowed_methods: Vec::new(),
+                #[cfg(feature = "sessions")]
+                session: self.session(&req, response_headers),
+            };
+            let response_headers = ctx.response_headers.clone();
+            let fut = (fallback.0)(req, ctx).map_ok(move |mut resp| {
+                response_headers.apply(&mut resp);
+                resp
+            });
+            return ResponseFuture(Box::pin(fut));
+        }
+
+        if let Some(not_found) = self.not_found.clone() {
+            return ResponseFuture(Box::pin(async move { Ok(not_found.to_response()) }));
+        }
+
+        ResponseFuture::status(StatusCode::NOT_FOUND)
+    }
+
+    /// Sends a copy of `req`, without its body, to the shadow route matching its path, if any.
+    ///
+    /// The copy is dropped when the router isn't called from a task of a Tokio `LocalSet`, as its
+    /// handler can't be spawned then.
+    #[cfg(feature = "tokio")]
+    fn shadow(&self, req: &Request<Body>) {
+        let shadows = match &self.shadows {
+            Some(shadows) => shadows.read().unwrap(),
+            None => return,
+        };
+        // The router isn't `Send`, so the task it's called from, if any, is a local one.
+        if tokio::task::try_id().is_none() {
+            return;
+        }
+        let node = match shadows.at(route_path(req)) {
+            Some(node) => node,
+            None => return,
+        };
+        let handler = match &node.value.catchall {
+            Some(handler) => handler,
+            None => return,
+        };
+
+        let response_headers = ContextHeaders::default();
+        let ctx = RouteContext {
+            params: node
+                .params
+                .iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+            data: Data::clone(&self.data),
+            base_path: self.base_path.clone(),
+            template: Some(node.value.template.clone()),
+            request_context: self.request_context.clone(),
+            client_ip: self.client_ip(req),
+            path_and_query: req.uri().path_and_query().cloned(),
+            query: parse_query(req.uri().query()),
+            response_headers: response_headers.clone(),
+            allowed_methods: Vec::new(),
+            #[cfg(feature = "sessions")]
+            session: self.session(req, response_headers),
+        };
+
+        let mut copy = Request::new(Body::default());
+        *copy.method_mut() = req.method().clone();
+        *copy.uri_mut() = req.uri().clone();
+        *copy.version_mut() = req.version();
+        *copy.headers_mut() = req.headers().clone();
+
+        let fut = (handler.0)(copy, ctx);
+        tokio::task::spawn_local(async move {
+            let _ = fut.await;
+        });
+    }
+
+    /// Starts the session of `req`, if the router has sessions, see [`RouteContext::session`].
+    #[cfg(feature = "sessions")]
+    fn session(&self, req: &Request<Body>, response_headers: ContextHeaders) -> Option<Session> {
+        let sessions = self.sessions.as_ref()?;
+        Some(sessions.start(req.headers(), response_headers))
+    }
+
+    /// Finds the address of the client that made `req`, see [`RouteContext::client_ip`].
+    fn client_ip(&self, req: &Request<Body>) -> Option<IpAddr> {
+        let peer = self
+            .request_context
+            .as_ref()
+            .and_then(|ctx| ctx.remote_addr())
+            .map(|addr| addr.ip());
+
+        match &self.trusted_proxies {
+            Some(proxies) => proxies.client_ip(peer, req.headers()),
+            None => peer,
+        }
+    }
+
+    /// Records how long requests to the route with the given template take and how they're
+    /// answered, for the router's metrics endpoint.
+    #[cfg(feature = "metrics")]
+    fn observe(
+        &self,
+        template: &str,
+        method: &Method,
+        fut: ResponseFuture<ResBody, Error>,
+    ) -> ResponseFuture<ResBody, Error> {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics.clone(),
+            None => return fut,
+        };
+        let template = template.to_owned();
+        let method = method.clone();
+        let start = Instant::now();
+
+        ResponseFuture(Box::pin(fut.inspect(move |res| {
+            let status = res.as_ref().ok().map(Response::status);
+            metrics.record(&template, &method, status, start.elapsed());
+        })))
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    fn observe(
+        &self,
+        _template: &str,
+        _method: &Method,
+        fut: ResponseFuture<ResBody, Error>,
+    ) -> ResponseFuture<ResBody, Error> {
+        fut
+    }
+
+    /// Dispatches `req` in a Sentry hub of its own that handler errors are reported to, if the
+    /// router has Sentry integration.
+    #[cfg(feature = "sentry")]
+    fn dispatch_in_hub(&self, req: Request<Body>) -> ResponseFuture<ResBody, Error> {
+        let report = match self.sentry {
+            Some(report) => report,
+            None => return self.dispatch(req),
+        };
+        let hub = Arc::new(Hub::new_from_top(Hub::current()));
+        let fut = Hub::run(hub.clone(), || self.dispatch(req));
+
+        ResponseFuture(Box::pin(fut.bind_hub(hub.clone()).inspect(move |res| {
+            if let Err(err) = res {
+                report(&hub, err);
+            }
+        })))
+    }
+
+    #[cfg(not(feature = "sentry"))]
+    fn dispatch_in_hub(&self, req: Request<Body>) -> ResponseFuture<ResBody, Error> {
+        self.dispatch(req)
     }
 }
 
 /// A [`Future`] that resolves to a [`Response`](http::Response).
 pub struct ResponseFuture<Body, Error>(LocalBoxFuture<'static, Result<Response<Body>, Error>>);
 
+impl<Body, Error> ResponseFuture<Body, Error>
+where
+    Body: Default + 'static,
+{
+    /// Resolves to an empty response with the given status.
+    fn status(status: StatusCode) -> Self {
+        let mut resp = Response::new(());
+        *resp.status_mut() = status;
+        Self::empty(resp)
+    }
+
+    /// Resolves to `resp` with an empty body.
+    fn empty(resp: Response<()>) -> Self {
+        ResponseFuture(Box::pin(async move { Ok(resp.map(|()| Body::default())) }))
+    }
+}
+
 impl<Body, Error> Future for ResponseFuture<Body, Error> {
     type Output = Result<Response<Body>, Error>;
 
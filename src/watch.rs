@@ -0,0 +1,66 @@
+use http::Method;
+use tokio::sync::watch;
+
+/// A change to a router's route table, reported by a [`RouterWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteChangeEvent {
+    /// A handler was registered for a path template and method, or for every method if the
+    /// method is `None`.
+    Added(String, Option<Method>),
+    /// A handler was removed from a path template and method, or from every method if the method
+    /// is `None`. Registering a handler for a path and method that already have one removes the
+    /// previous handler.
+    Removed(String, Option<Method>),
+}
+
+/// Receives the changes made to a router's route table.
+///
+/// Created with [`Router::watcher`](crate::Router::watcher). Every router sharing the route table,
+/// including clones and groups of the router the watcher was created from, reports its changes
+/// to the watcher. Requires the `tokio` feature.
+pub struct RouterWatcher {
+    rx: watch::Receiver<Vec<RouteChangeEvent>>,
+    seen: usize,
+}
+
+impl RouterWatcher {
+    pub(crate) fn new(rx: watch::Receiver<Vec<RouteChangeEvent>>) -> Self {
+        let seen = rx.borrow().len();
+        Self { rx, seen }
+    }
+
+    /// Waits for the route table to change, returning the changes made since the last call.
+    ///
+    /// Returns `None` once every router sharing the route table has been dropped.
+    pub async fn changed(&mut self) -> Option<Vec<RouteChangeEvent>> {
+        loop {
+            {
+                let events = self.rx.borrow_and_update();
+                if events.len() > self.seen {
+                    let changes = events[self.seen..].to_vec();
+                    self.seen = events.len();
+                    return Some(changes);
+                }
+            }
+
+            self.rx.changed().await.ok()?;
+        }
+    }
+}
+
+/// Broadcasts route table changes to every [`RouterWatcher`].
+#[derive(Default)]
+pub(crate) struct RouteChanges(Option<watch::Sender<Vec<RouteChangeEvent>>>);
+
+impl RouteChanges {
+    pub(crate) fn subscribe(&mut self) -> RouterWatcher {
+        let tx = self.0.get_or_insert_with(|| watch::channel(Vec::new()).0);
+        RouterWatcher::new(tx.subscribe())
+    }
+
+    pub(crate) fn send(&self, events: impl IntoIterator<Item = RouteChangeEvent>) {
+        if let Some(tx) = &self.0 {
+            tx.send_modify(|log| log.extend(events));
+        }
+    }
+}
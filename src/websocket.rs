@@ -0,0 +1,133 @@
+use http::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE},
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response, StatusCode,
+};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use tokio_tungstenite::tungstenite::{handshake::derive_accept_key, protocol::Role};
+
+/// A WebSocket connection accepted by a route registered with
+/// [`Router::websocket_upgrade`](crate::Router::websocket_upgrade).
+pub type WebSocketStream = tokio_tungstenite::WebSocketStream<Upgraded>;
+
+/// Checks the opening handshake of a WebSocket connection (RFC 6455, section 4.2.1), returning
+/// the `101 Switching Protocols` response completing it and the pending upgrade of the
+/// connection, or the response rejecting it.
+///
+/// Requests that don't ask for a WebSocket connection, or ask for a version other than 13, are
+/// answered with `426 Upgrade Required`, and requests with a malformed key with
+/// `400 Bad Request`.
+pub(crate) fn accept<ReqBody, ResBody>(
+    req: &mut Request<ReqBody>,
+) -> Result<(Response<ResBody>, OnUpgrade), Response<ResBody>>
+where
+    ResBody: Default,
+{
+    let headers = req.headers();
+    let is_upgrade = req.method() == Method::GET
+        && has_token(headers, CONNECTION, "upgrade")
+        && has_token(headers, UPGRADE, "websocket");
+    if !is_upgrade
+        || !matches!(headers.get(SEC_WEBSOCKET_VERSION), Some(version) if version == "13")
+    {
+        let resp = Response::builder()
+            .status(StatusCode::UPGRADE_REQUIRED)
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_VERSION, "13")
+            .body(ResBody::default())
+            .unwrap();
+        return Err(resp);
+    }
+
+    // The key is 16 random bytes, encoded in base64.
+    let key = match headers.get(SEC_WEBSOCKET_KEY) {
+        Some(key) if key.len() == 24 => key,
+        _ => {
+            let mut resp = Response::new(ResBody::default());
+            *resp.status_mut() = StatusCode::BAD_REQUEST;
+            return Err(resp);
+        }
+    };
+    let accept = HeaderValue::try_from(derive_accept_key(key.as_bytes())).unwrap();
+
+    let resp = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(ResBody::default())
+        .unwrap();
+    Ok((resp, hyper::upgrade::on(req)))
+}
+
+/// Answers a valid handshake with `500 Internal Server Error` when the connection's handler
+/// can't be spawned, because the router isn't called from a Tokio runtime, or for the unsync
+/// router, from a task of a `LocalSet`.
+pub(crate) fn cannot_spawn<ResBody>() -> Response<ResBody>
+where
+    ResBody: Default,
+{
+    let mut resp = Response::new(ResBody::default());
+    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    resp
+}
+
+/// Waits for the server to hand over the connection once the `101 Switching Protocols`
+/// response is sent, returning `None` if it never does, e.g. because the client went away or
+/// the request didn't come from a hyper server.
+pub(crate) async fn upgraded(on_upgrade: OnUpgrade) -> Option<WebSocketStream> {
+    let upgraded = on_upgrade.await.ok()?;
+    Some(WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await)
+}
+
+/// Whether the comma-separated `header` lists `token`, ignoring case.
+fn has_token(headers: &HeaderMap, header: HeaderName, token: &str) -> bool {
+    headers
+        .get_all(header)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+#[cfg(test)]
+mod tests {
+    use http::{header::SEC_WEBSOCKET_ACCEPT, Request, StatusCode};
+
+    use super::accept;
+
+    #[test]
+    fn handshake() {
+        let req = |connection: &str, version: &str, key: &str| {
+            Request::get("/ws")
+                .header("connection", connection)
+                .header("upgrade", "WebSocket")
+                .header("sec-websocket-version", version)
+                .header("sec-websocket-key", key)
+                .body(())
+                .unwrap()
+        };
+
+        // The example from RFC 6455, section 1.3.
+        let mut upgrade = req("keep-alive, Upgrade", "13", "dGhlIHNhbXBsZSBub25jZQ==");
+        let (resp, _) = accept::<_, ()>(&mut upgrade).unwrap();
+        assert_eq!(resp.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            resp.headers()[SEC_WEBSOCKET_ACCEPT],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let mut old_version = req("upgrade", "8", "dGhlIHNhbXBsZSBub25jZQ==");
+        let resp = accept::<_, ()>(&mut old_version).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(resp.headers()["sec-websocket-version"], "13");
+
+        let mut not_upgrade = req("keep-alive", "13", "dGhlIHNhbXBsZSBub25jZQ==");
+        let resp = accept::<_, ()>(&mut not_upgrade).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::UPGRADE_REQUIRED);
+
+        let mut bad_key = req("upgrade", "13", "short");
+        let resp = accept::<_, ()>(&mut bad_key).unwrap_err();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+}